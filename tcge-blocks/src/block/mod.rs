@@ -16,15 +16,28 @@ impl PartialEq for dyn Block {
 	}
 }
 
+/// The properties of a block state, packed into bits; what they mean is up to the type of block.
+pub type BlockStateData = u16;
+
 #[derive(Copy, Clone, Eq, Debug)]
 pub struct BlockState {
 	pub id: BlockId,
-	pub data: ()
+	pub data: BlockStateData
+}
+
+impl BlockState {
+	/// Returns the state of the same type of block with the given properties.
+	pub fn with_data(&self, data: BlockStateData) -> BlockState {
+		BlockState {
+			id: self.id,
+			data
+		}
+	}
 }
 
 impl PartialEq for BlockState {
 	fn eq(&self, other: &BlockState) -> bool {
-		self.id == other.id
+		self.id == other.id && self.data == other.data
 	}
 }
 
@@ -16,7 +16,7 @@ impl SimpleBlock {
 	pub fn new(id: BlockId, name: &str, layers: BlockLayers) -> Self {
 		let name = name.to_string();
 		let default = BlockState {
-			id, data: ()
+			id, data: 0
 		};
 		
 		Self {
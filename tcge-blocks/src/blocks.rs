@@ -24,6 +24,8 @@ impl Blocks {
 		use super::block::simple::SimpleBlock;
 		new.register_block(Box::new(SimpleBlock::new(BlockId::new(0), "air", BlockLayers::default())));
 		new.register_block(Box::new(SimpleBlock::new(BlockId::new(1), "adm", BlockLayers::default())));
+		new.register_block(Box::new(SimpleBlock::new(BlockId::new(2), "adm_fence", BlockLayers::default())));
+		new.register_block(Box::new(SimpleBlock::new(BlockId::new(3), "adm_cross", BlockLayers::default())));
 		
 		new
 	}
@@ -11,6 +11,7 @@ pub use face::Face;
 pub mod block;
 pub use block::Block;
 pub use block::BlockState;
+pub use block::BlockStateData;
 
 pub mod layers;
 pub use layers::BlockLayers;
@@ -0,0 +1,28 @@
+//! The asset loaders of the client.
+
+use common::assets::{AssetManager, AssetLoader, AssetError, LoadContext};
+use common::resources::ResourceLocation;
+use crate::render;
+
+/// Registers the loaders for all asset types of the client.
+pub fn register_loaders(assets: &mut AssetManager, gl: &gl::Gl) {
+	assets.register_loader(render::TextureLoader::new(gl));
+	assets.register_loader(TomlLoader);
+}
+
+/// Loads TOML files whose root is a table, like configuration files.
+pub struct TomlLoader;
+
+impl AssetLoader for TomlLoader {
+	type Asset = toml::value::Table;
+	
+	fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<toml::value::Table, AssetError> {
+		let source = context.read_string(location)?;
+		
+		match toml::from_str(&source) {
+			Ok(toml::Value::Table(table)) => Ok(table),
+			Ok(_) => Err(AssetError::Load(location.to_string(), "The root is not a table".to_string())),
+			Err(err) => Err(AssetError::Load(location.to_string(), err.to_string())),
+		}
+	}
+}
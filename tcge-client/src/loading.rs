@@ -0,0 +1,150 @@
+//! The startup of the client: A sequence of loading tasks, shown by the loading screen.
+
+use crate::glfw_context::GlfwContext;
+use crate::{backbone, render, RenderEvent};
+use common::loading::{LoadingSequence, LoadingProgress, WorkerPool};
+use common::resources::Resources;
+use common::AssetManager;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type LoadingProgressComp = crate::WrapperComponent<LoadingProgress>;
+
+/// How long the loading tasks may run per frame, before the loading screen is drawn again.
+pub const FRAME_BUDGET: Duration = Duration::from_millis(16);
+
+/// Everything the startup tasks work with.
+pub struct Startup<'a> {
+	pub backbone: &'a mut backbone::Backbone,
+	pub glfw_context: &'a mut GlfwContext,
+	pub res: Arc<Resources>,
+	pub assets: &'a mut AssetManager,
+	
+	/// For loading and decoding resources in the background; the results are uploaded on the main thread.
+	pub workers: &'a WorkerPool,
+}
+
+pub type StartupSequence<'a> = LoadingSequence<Startup<'a>>;
+
+/// Runs the startup tasks, drawing a frame of the current location (the loading screen) after every update.
+///
+/// Returns `false` if a task failed or the window was closed.
+pub fn run<'a>(sequence: &mut StartupSequence<'a>, startup: &mut Startup<'a>, progress: &mut LoadingProgressComp) -> bool {
+	loop {
+		startup.glfw_context.update();
+		
+		if startup.glfw_context.completion() {
+			info!("The window was closed while loading.");
+			return false;
+		}
+		
+		for (_time, event) in glfw::flush_messages(&startup.glfw_context.events) {
+			if let glfw::WindowEvent::FramebufferSize(width, height) = event {
+				unsafe {
+					startup.glfw_context.gl.Viewport(0, 0, width, height);
+				}
+			}
+		}
+		
+		let done = match sequence.update(startup, FRAME_BUDGET) {
+			Ok(done) => done,
+			Err(err) => {
+				error!("{}", err);
+				return false;
+			}
+		};
+		
+		**progress = sequence.progress();
+		
+		if done {
+			return true;
+		}
+		
+		startup.backbone.update();
+		
+		let window_size = startup.glfw_context.window.get_framebuffer_size();
+		if window_size.0 == 0 || window_size.1 == 0 {
+			continue;
+		}
+		
+		startup.backbone.fire_event(&mut RenderEvent {
+			gl: startup.glfw_context.gl.clone(),
+			time: startup.glfw_context.glfw.get_time(),
+			width:  window_size.0,
+			height: window_size.1,
+			interpolation: 0.0,
+			delta: FRAME_BUDGET.as_secs_f32(),
+		});
+	}
+}
+
+/// The node shown while loading: A progress bar, and the current task once the font is loaded.
+pub struct LoadingScreen {}
+
+impl backbone::Handler for LoadingScreen {
+	fn on_event<'a>(&mut self, event: &mut backbone::Wrapper, context: &mut backbone::Context) {
+		let phase = event.get_phase().clone();
+		
+		if let Some(render_event) = event.downcast::<RenderEvent>() {
+			if let backbone::Phase::Action = phase {
+				let progress = match context.component_get::<LoadingProgressComp>() {
+					Ok(progress) => progress,
+					Err(_) => return,
+				};
+				
+				let text = context.component_get_mut::<render::text::TextRendererComp>().ok();
+				Self::render(render_event, progress, text);
+			}
+			
+			return
+		}
+	}
+}
+
+impl LoadingScreen {
+	fn render(render_event: &RenderEvent, progress: &LoadingProgress, text: Option<&mut render::text::TextRendererComp>) {
+		let gl = &render_event.gl;
+		let (width, height) = (render_event.width, render_event.height);
+		
+		// The bar is drawn by clearing rectangles, so it needs nothing that has to be loaded first.
+		let bar_width = width / 2;
+		let bar_height = 8;
+		let bar_x = (width - bar_width) / 2;
+		let bar_y = height / 2 - bar_height / 2;
+		let filled = (bar_width as f32 * progress.fraction) as i32;
+		
+		unsafe {
+			gl.Enable(gl::SCISSOR_TEST);
+			
+			gl.Scissor(bar_x, bar_y, bar_width, bar_height);
+			gl.ClearColor(0.2, 0.2, 0.2, 1.0);
+			gl.Clear(gl::COLOR_BUFFER_BIT);
+			
+			if filled > 0 {
+				gl.Scissor(bar_x, bar_y, filled, bar_height);
+				gl.ClearColor(0.9, 0.9, 0.9, 1.0);
+				gl.Clear(gl::COLOR_BUFFER_BIT);
+			}
+			
+			gl.Disable(gl::SCISSOR_TEST);
+		}
+		
+		if let Some(text) = text {
+			unsafe {
+				gl.Disable(gl::DEPTH_TEST);
+				gl.Enable(gl::BLEND);
+				gl.BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+			}
+			
+			text.transform = cgmath::Matrix4::from(cgmath::ortho(
+				0.0, width as f32,
+				height as f32, 0.0,
+				-1.0, 1.0
+			));
+			
+			// Window coordinates start at the bottom, text coordinates at the top.
+			let y = (height - bar_y) as f32 + 4.0;
+			text.draw_text(&format!("Loading {}... {:.0}%", progress.task, progress.fraction * 100.0), 16.0, bar_x as f32, y);
+		}
+	}
+}
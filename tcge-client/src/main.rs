@@ -3,7 +3,6 @@
 extern crate simplelog;
 extern crate rustc_hash;
 extern crate cgmath;
-extern crate half;
 
 extern crate glfw;
 extern crate tcge_common as common;
@@ -13,14 +12,16 @@ extern crate tcge_opengl as gl;
 
 use common::gameloop;
 use backbone::WrapperComponent;
+use std::sync::Arc;
 
 mod glfw_context;
 use glfw_context::GlfwContext;
 use glfw_context::{ResizeEvent, MouseMoveEvent, MouseEvent, KeyEvent};
-use common::resources::ResourceProvider;
 
 mod render;
 mod playground;
+mod assets;
+mod loading;
 
 const DEFAULT_TICKS_PER_SECOND: i32 = 60;
 
@@ -31,57 +32,104 @@ fn main() {
 	let mut backbone = backbone::Backbone::new();
 	let root_id = backbone.root_get_id();
 	
-	let root_node_handler = RootNodeHandler {};
+	let root_node_handler = RootNodeHandler {
+		screenshot: false
+	};
 	let root_node_handler = Box::new(root_node_handler );
 	backbone.set_root_node_handler(root_node_handler);
 	
 	// Attach GlfwContext and get a mutable reference to it...
 	let glfw_context = backbone.node_component_attach(root_id, GlfwContext::new());
 	
-	let resources = common::resources::new();
-	let resources = WrapperComponent::new("Resources", resources);
-	let resources = backbone.node_component_attach(root_id, resources);
+	let mut resources = common::resources::new();
 	
 	// Register all default (core) resources embedded in the binary
 	let mut includes: common::resources::Includes = vec![];
 	includes.extend(&render::text::TEXT_RENDERER_FILES);
+	includes.extend(&render::materials::SHADER_LIBRARY_FILES);
 	includes.extend(&render::materials::SOLID_COLOR_MATERIAL_FILES);
 	includes.extend(&playground::sky::SKY_MATERIAL_FILES);
 	includes.extend(&playground::grid::GRID_MATERIAL_FILES);
 	includes.extend(&playground::test_blocks::BLOCKS_MATERIAL_FILES);
+	includes.extend(&playground::test_blocks::BLOCK_MODEL_FILES);
+	includes.extend(&playground::test_blocks::BLOCK_TEXTURE_FILES);
 	
-	// Register the embedded files.
+	// Register the embedded files, as the defaults every other provider can override.
 	let includes = common::resources::IncludeProvider::new(includes);
-	resources.register_provider_by_type(includes);
+	resources.register_provider_by_type(includes, common::resources::PRIORITY_INCLUDES);
 	
-	for iterator in resources.res_list() {
-		for path in iterator {
-			info!("Found Resource: {}", path);
+	for resolved in resources.res_list_resolved() {
+		for (path, provider) in resolved {
+			info!("Found Resource: {} ({})", path, provider);
 		}
 	}
 	
-	// Setup the text renderer
-	let text_renderer = render::text::new(&glfw_context.gl, resources, "hack")
-		.ok().expect("TextRenderer initialization failed.");
-	let text_renderer = WrapperComponent::new("TextRenderer", text_renderer);
-	backbone.node_component_attach(root_id, text_renderer);
-	
+	// From here on, the resources are shared with the loader threads.
+	let resources = Arc::new(resources);
+	backbone.node_component_attach(root_id, WrapperComponent::new("Resources", resources.clone()));
 	
-	// Setup the playground.
-	// TODO: Eventually move this into the initializer.
-	playground::setup(
-		&mut backbone,
-		glfw_context,
-		resources
+	// Saves, screenshots and settings go to the user data; it is never read as assets.
+	let user_data: Arc<dyn common::resources::ResourceStorage> = Arc::new(
+		common::resources::FilesystemProvider::new(user_data_path())
 	);
+	backbone.node_component_attach(root_id, WrapperComponent::new("UserData", user_data));
 	
-	backbone.location_set("/playground").unwrap();
+	let mut asset_manager = common::AssetManager::new();
+	assets::register_loaders(&mut asset_manager, &glfw_context.gl);
+	let asset_manager = WrapperComponent::new("Assets", asset_manager);
+	let asset_manager = backbone.node_component_attach(root_id, asset_manager);
+	
+	// Show the loading screen, while the rest is loaded.
+	let progress = WrapperComponent::new("LoadingProgress", common::loading::LoadingProgress::default());
+	let progress = backbone.node_component_attach(root_id, progress);
+	
+	let loading_screen = Box::new(loading::LoadingScreen {});
+	backbone.node_new(root_id, "loading", Some(loading_screen)).unwrap();
+	backbone.location_set("/loading").unwrap();
 	
-	// Show the window and wait until things calm down.
 	glfw_context.window.show();
 	glfw_context.gl.flush();
 	backbone.update_until_idle();
 	
+	let workers = common::loading::WorkerPool::with_available_threads("Loader");
+	let loaded = {
+		let mut sequence = loading::StartupSequence::new();
+		
+		sequence.add("font", 1.0, |startup| {
+			let text_renderer = render::text::new(&startup.glfw_context.gl, &startup.res, startup.assets, "hack")
+				.map_err(|_| "TextRenderer initialization failed.".to_string())?;
+			
+			let text_renderer = WrapperComponent::new("TextRenderer", text_renderer);
+			let root_id = startup.backbone.root_get_id();
+			startup.backbone.node_component_attach(root_id, text_renderer);
+			Ok(common::loading::TaskStatus::Done)
+		});
+		
+		// TODO: Eventually move this into the initializer.
+		playground::add_loading_tasks(&mut sequence);
+		
+		let mut startup = loading::Startup {
+			backbone: &mut backbone,
+			glfw_context: &mut *glfw_context,
+			res: resources,
+			assets: &mut *asset_manager,
+			workers: &workers,
+		};
+		
+		loading::run(&mut sequence, &mut startup, progress)
+	};
+	
+	// Lets the loader threads finish.
+	drop(workers);
+	
+	if !loaded {
+		info!("Goodbye, world!");
+		return;
+	}
+	
+	backbone.location_set("/playground").unwrap();
+	backbone.update_until_idle();
+	
 	// Create the gameloop, attach it to the backbone, then run it.
 	let gameloop = gameloop::new(DEFAULT_TICKS_PER_SECOND);
 	let gameloop = WrapperComponent::new("Gameloop", gameloop);
@@ -273,11 +321,37 @@ fn main_loop(
 			_ => ()
 		}
 	}
-	
+
 }
 
 
 
+/// The resources, shared with the loader threads.
+pub type ResourcesComp = WrapperComponent<Arc<common::resources::Resources>>;
+
+/// The writable storage for everything the user creates.
+pub type UserDataComp = WrapperComponent<Arc<dyn common::resources::ResourceStorage>>;
+
+/// The root of the user data: Given by `--user-data <path>`, or `<exe>/userdata`.
+fn user_data_path() -> std::path::PathBuf {
+	let mut args = std::env::args().skip(1);
+	
+	while let Some(arg) = args.next() {
+		if arg == "--user-data" {
+			match args.next() {
+				Some(path) => return path.into(),
+				None => error!("No path given for --user-data, using default."),
+			}
+		} else if arg.starts_with("--user-data=") {
+			return arg["--user-data=".len()..].into();
+		}
+	}
+	
+	common::resources::get_exe_path()
+		.unwrap_or_default()
+		.join("userdata")
+}
+
 pub struct TickEvent {
 	pub tps: i32,
 	pub time: f64,
@@ -315,7 +389,70 @@ impl backbone::Event for CommandEvent {
 	}
 }
 
-struct RootNodeHandler {}
+/// Logs which providers supply the resource at the given location.
+fn print_resource_suppliers(context: &mut backbone::Context, location: &str) {
+	use common::resources::{ResourceLocation, MergeStrategy};
+	
+	let location = match ResourceLocation::parse(location) {
+		Ok(location) => location,
+		Err(err) => {
+			error!("{}", err);
+			return
+		}
+	};
+	
+	let resources = match context.component_get::<ResourcesComp>() {
+		Ok(resources) => resources,
+		Err(_) => return,
+	};
+	
+	let suppliers = resources.res_suppliers(&location);
+	let strategy = resources.get_merge_strategy(&location);
+	
+	match suppliers.split_first() {
+		None => info!("Resource {} is not supplied by any provider.", location),
+		Some((supplier, [])) => info!("Resource {} is supplied by: {}", location, supplier),
+		Some(_) if strategy != MergeStrategy::Override => {
+			info!("Resource {} is merged ({:?}) from: {}", location, strategy, suppliers.join(", "))
+		},
+		Some((supplier, overridden)) => {
+			info!("Resource {} is supplied by: {} (overriding {})", location, supplier, overridden.join(", "))
+		},
+	}
+}
+
+/// Writes the current frame to `screenshots:screenshot-<millis>.png` in the user data.
+fn take_screenshot(context: &mut backbone::Context, render_event: &RenderEvent) {
+	let user_data = match context.component_get::<UserDataComp>() {
+		Ok(user_data) => user_data,
+		Err(_) => return,
+	};
+	
+	let millis = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map(|time| time.as_millis())
+		.unwrap_or(0);
+	
+	let location = match common::resources::ResourceLocation::parse(&format!("screenshots:screenshot-{}.png", millis)) {
+		Ok(location) => location,
+		Err(err) => {
+			error!("Failed to save screenshot: {}", err);
+			return;
+		},
+	};
+	let pixels = render::screenshot::capture(&render_event.gl, render_event.width, render_event.height);
+	
+	match render::screenshot::save(&***user_data, &location, render_event.width, render_event.height, &pixels) {
+		Ok(()) => info!("Saved screenshot: {}", location),
+		Err(common::resources::ResourceError::Io(err)) => error!("Failed to save screenshot {}: {}", location, err),
+		Err(_) => error!("Failed to save screenshot {}.", location),
+	}
+}
+
+struct RootNodeHandler {
+	/// Whether to take a screenshot at the end of the next frame.
+	screenshot: bool,
+}
 
 impl backbone::Handler for RootNodeHandler {
 	fn on_event<'a>(&mut self, event: &mut backbone::Wrapper, context: &mut backbone::Context) {
@@ -360,6 +497,21 @@ impl backbone::Handler for RootNodeHandler {
 				return;
 			}
 			
+			if command.starts_with("res-which ") {
+				if let Some(mid) = command.find(' ') {
+					let (_, location) = command.split_at(mid);
+					print_resource_suppliers(context, location.trim());
+				}
+				event.stop();
+				return;
+			}
+			
+			if &command == "screenshot" {
+				self.screenshot = true;
+				event.stop();
+				return;
+			}
+			
 			if command.starts_with("set-tps ") {
 				let mut tps = DEFAULT_TICKS_PER_SECOND;
 				if let Some(mid) = command.find(' ') {
@@ -437,6 +589,11 @@ impl backbone::Handler for RootNodeHandler {
 				backbone::Phase::Action => {},
 				
 				backbone::Phase::Bubbling => {
+					if self.screenshot {
+						self.screenshot = false;
+						take_screenshot(context, render_event);
+					}
+					
 					use glfw::Context;
 					glfw_context.window.swap_buffers();
 				},
@@ -11,11 +11,7 @@ float grid_xz(vec2 vertex) {
   return 1.0 - min(line, 1.0);
 }
 
-vec3 hsv2rgb(vec3 c) {
-  vec4 K = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
-  vec3 p = abs(fract(c.xxx + K.xyz) * 6.0 - K.www);
-  return c.z * mix(K.xxx, clamp(p - K.xxx, 0.0, 1.0), c.y);
-}
+#include "core:shaders/lib/color.glsl"
 
 const float grid_level_0 = 1.0 / 1.0;
 const float grid_level_1 = 1.0 / 16.0;
@@ -1,7 +1,7 @@
 //! Module for prototyping things.
 
 use crate::glfw_context::{GlfwContext, GlInfo};
-use crate::{backbone, RenderEvent, TickEvent, KeyEvent, MouseEvent, MouseMoveEvent};
+use crate::{backbone, RenderEvent, TickEvent, KeyEvent, MouseEvent, MouseMoveEvent, CommandEvent};
 use crate::common::resources;
 use crate::blocks;
 use crate::render;
@@ -25,113 +25,212 @@ pub mod test_blocks;
 use test_blocks::ChunkStorage;
 use test_blocks::ChunkRenderManager;
 use test_blocks::StaticBlockBakery;
-use common::resources::ResourceProvider;
+use common::AssetManager;
+use common::loading::{TaskStatus, JobStatus, Pending};
+use crate::loading::StartupSequence;
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-pub fn setup(
-	backbone: &mut backbone::Backbone,
-	glfw_context: &mut GlfwContext,
-	res: &mut resources::Resources,
-) {
-	info!("Attempting to load ./assets/playground.toml ...");
-	let config = match res.res_as_string(&resources::ResourceLocation::from_str("playground.toml")) {
-		Ok(config) => match toml::from_str(&config) {
-			Ok(config) => if let toml::Value::Table(config) = config {
+/// The parts of the playground, as they are loaded by the startup tasks.
+#[derive(Default)]
+struct PlaygroundParts {
+	config: toml::value::Table,
+	blocks: Option<blocks::BlocksRef>,
+	block_models: Option<test_blocks::BlockModelSet>,
+	
+	/// The block textures are read and decoded in the background; with the number of textures loaded so far.
+	block_textures: Option<(Pending<Result<test_blocks::BlockTextureArray, String>>, Arc<AtomicUsize>, usize)>,
+	
+	bakery: Option<test_blocks::StaticBlockBakeryRef>,
+	chunks_renderer: Option<ChunkRenderManager>,
+	sky: Option<sky::SkyRenderer>,
+	grid: Option<grid::GridRenderer>,
+	solid_color_material: Option<Rc<render::Material>>,
+}
+
+/// Adds the tasks that load the playground and create its node.
+pub fn add_loading_tasks(sequence: &mut StartupSequence) {
+	let parts = Rc::new(RefCell::new(PlaygroundParts::default()));
+	
+	let p = parts.clone();
+	sequence.add("configuration", 0.5, move |startup| {
+		info!("Attempting to load ./assets/core/playground.toml ...");
+		let location = resources::ResourceLocation::parse("core:playground.toml")
+			.map_err(|err| err.to_string())?;
+		p.borrow_mut().config = match startup.assets.load::<toml::value::Table>(&*startup.res, &location) {
+			Ok(config) => {
 				info!("Loaded configuration.");
-				config
-			} else {
-				error!("Root is not a table in playground.toml");
-				toml::value::Table::new()
-			}
+				config.get().clone()
+			},
 			Err(e) => {
-				error!("Failed to parse playground.toml: {}", e.to_string());
+				error!("{}", e);
 				toml::value::Table::new()
 			}
-		},
-		Err(e) => {
-			error!("Failed to read playground.toml");
-			toml::value::Table::new()
-		}
-	};
+		};
+		
+		Ok(TaskStatus::Done)
+	});
 	
+	let p = parts.clone();
+	sequence.add("block models", 1.0, move |startup| {
+		let mut parts = p.borrow_mut();
+		
+		let blocks = blocks::Blocks::new().to_ref();
+		let block_models = StaticBlockBakery::load_models(&startup.res, &blocks);
+		
+		// Reading and decoding the textures takes longest, so it happens in the background.
+		let res = startup.res.clone();
+		let textures = block_models.textures.clone();
+		let total = textures.len();
+		let loaded = Arc::new(AtomicUsize::new(0));
+		let progress = loaded.clone();
+		let pending = startup.workers.spawn(move || {
+			test_blocks::BlockTextureArray::build_with_progress(&res, &textures, &|count| progress.store(count, Ordering::Relaxed))
+				.map_err(|err| err.to_string())
+		});
+		
+		parts.blocks = Some(blocks);
+		parts.block_models = Some(block_models);
+		parts.block_textures = Some((pending, loaded, total));
+		Ok(TaskStatus::Done)
+	});
 	
+	let p = parts.clone();
+	sequence.add("block textures", 4.0, move |_startup| {
+		let mut parts = p.borrow_mut();
+		
+		let block_textures = match &parts.block_textures {
+			None => return Err("The block textures were never started".to_string()),
+			Some((pending, loaded, total)) => match pending.poll() {
+				JobStatus::Running => {
+					let loaded = loaded.load(Ordering::Relaxed);
+					return Ok(TaskStatus::Running(loaded as f32 / std::cmp::max(*total, 1) as f32));
+				},
+				JobStatus::Done(block_textures) => block_textures?,
+				JobStatus::Failed => return Err("Loading the block textures crashed".to_string()),
+			}
+		};
+		
+		let blocks = parts.blocks.clone().ok_or("The blocks were never created")?;
+		let block_models = parts.block_models.take().ok_or("The block models were never loaded")?;
+		
+		parts.block_textures = None;
+		parts.bakery = Some(Rc::new(StaticBlockBakery::bake(&blocks, block_models, block_textures)));
+		Ok(TaskStatus::Done)
+	});
 	
-	let entity_universe = Universe::new();
-	let mut entity_world = entity_universe.create_world();
+	let p = parts.clone();
+	sequence.add("block material", 1.0, move |startup| {
+		let mut parts = p.borrow_mut();
+		let blocks = parts.blocks.clone().ok_or("The blocks were never created")?;
+		let bakery = parts.bakery.clone().ok_or("The block bakery was never created")?;
+		
+		// Uploads the block textures.
+		let chunks_renderer = ChunkRenderManager::new(
+			&startup.glfw_context.gl,
+			&startup.res,
+			&blocks,
+			bakery
+		).map_err(|err| format!("Failed to load 'Blocks' material: {}", err))?;
+		
+		parts.chunks_renderer = Some(chunks_renderer);
+		Ok(TaskStatus::Done)
+	});
 	
-	let mut freecam = Freecam::new();
+	let p = parts.clone();
+	sequence.add("materials", 1.0, move |startup| {
+		let mut parts = p.borrow_mut();
+		let gl = &startup.glfw_context.gl;
+		let res = &*startup.res;
+		
+		parts.sky = Some(sky::SkyRenderer::new(gl, res)
+			.map_err(|err| format!("Failed to load 'Sky' material: {}", err))?);
+		
+		parts.grid = Some(grid::GridRenderer::new(gl, res)
+			.map_err(|err| format!("Failed to load 'Grid' material: {}", err))?);
+		
+		let solid_color_material = resources::ResourceLocation::parse(render::materials::SOLID_COLOR_MATERIAL)
+			.map_err(render::MaterialError::from)
+			.and_then(|location| render::Material::load(gl, res, &location))
+			.map_err(|err| format!("Failed to load 'Solid Color' material: {}", err))?;
+		
+		parts.solid_color_material = Some(Rc::new(solid_color_material));
+		Ok(TaskStatus::Done)
+	});
 	
-	if let Some(t) = config.get("freecam") {
-		if let toml::Value::Table(t) = t {
-			freecam.config(t);
+	let p = parts;
+	sequence.add("playground", 0.5, move |startup| {
+		let mut parts = p.borrow_mut();
+		let missing = |part: &str| format!("The {} was never loaded", part);
+		
+		let blocks = parts.blocks.take().ok_or_else(|| missing("block registry"))?;
+		let chunks_renderer = parts.chunks_renderer.take().ok_or_else(|| missing("chunk renderer"))?;
+		let sky = parts.sky.take().ok_or_else(|| missing("sky"))?;
+		let grid = parts.grid.take().ok_or_else(|| missing("grid"))?;
+		let solid_color_material = parts.solid_color_material.take().ok_or_else(|| missing("solid color material"))?;
+		
+		let entity_universe = Universe::new();
+		let mut entity_world = entity_universe.create_world();
+		
+		let mut freecam = Freecam::new();
+		
+		if let Some(t) = parts.config.get("freecam") {
+			if let toml::Value::Table(t) = t {
+				freecam.config(t);
+			}
 		}
-	}
-	
-	// Create the player entity
-	let entity_player = entity_world.insert(
-		(),
-		vec![
-			(freecam, Inventory::default(), )
-		]
-	)[0];
-	
-	/*
-	debug!("query start");
-	for (mut camera,) in <(Write<Freecam>,)>::query().iter(&mut entity_world) {
-		debug!("camera {:?}", &camera);
-	}
-	debug!("query end");
-	*/
-	
-	let blocks = blocks::Blocks::new().to_ref();
-	
-	let chunks = ChunkStorage::new(&blocks);
-	
-	let bakery = Rc::new(StaticBlockBakery::new(res, &blocks).expect("StaticBlockBakery initialization must not fail"));
-	
-	let chunks_renderer = ChunkRenderManager::new(
-		&glfw_context.gl,
-		res,
-		&blocks,
-		bakery.clone()
-	).map_err(|_| {
-		error!("Failed to load 'Blocks' material.");
-	}).unwrap();
-	
-	let sky = sky::SkyRenderer::new(&glfw_context.gl, res).map_err(|_| {
-		error!("Failed to load 'Blocks' material.");
-	}).unwrap();
-	
-	let grid = grid::GridRenderer::new(&glfw_context.gl, res).map_err(|_| {
-		error!("Failed to load 'Grid' material.");
-	}).unwrap();
-	
-	let solid_color_material = render::materials::SolidColorMaterial::new(&glfw_context.gl, &res).map_err(|_| {
-		error!("Failed to load 'Grid' material.");
-	}).unwrap();
-	let solid_color_material = Rc::new(solid_color_material);
-	
-	let crosshair_2d = crosshair::CrosshairRenderer2D::new(&glfw_context.gl, &solid_color_material);
-	let crosshair_3d = crosshair::CrosshairRenderer3D::new(&glfw_context.gl, &solid_color_material);
-	
-	let playground = Playground {
-		entity_universe,
-		entity_world,
-		entity_player,
-		blocks,
-		chunks,
-		chunks_renderer,
-		sky,
-		grid,
-		crosshair_2d,
-		crosshair_3d,
-	};
-	
-	let playground = Box::new(playground);
-	let _playground_id = backbone.node_new(
-		backbone.root_get_id(),
-		"playground",
-		Some(playground)
-	).unwrap();
+		
+		// Create the player entity
+		let entity_player = entity_world.insert(
+			(),
+			vec![
+				(freecam, Inventory::default(), )
+			]
+		)[0];
+		
+		/*
+		debug!("query start");
+		for (mut camera,) in <(Write<Freecam>,)>::query().iter(&mut entity_world) {
+			debug!("camera {:?}", &camera);
+		}
+		debug!("query end");
+		*/
+		
+		let chunks = ChunkStorage::new(&blocks);
+		
+		let gl = &startup.glfw_context.gl;
+		let crosshair_2d = crosshair::CrosshairRenderer2D::new(gl, &solid_color_material);
+		let crosshair_3d = crosshair::CrosshairRenderer3D::new(gl, &solid_color_material);
+		
+		let watcher = resources::ResourceWatcher::new(&*startup.res);
+		
+		let playground = Playground {
+			entity_universe,
+			entity_world,
+			entity_player,
+			blocks,
+			chunks,
+			chunks_renderer,
+			sky,
+			grid,
+			solid_color_material,
+			crosshair_2d,
+			crosshair_3d,
+			watcher,
+			watcher_ticks: 0,
+		};
+		
+		let playground = Box::new(playground);
+		let backbone = &mut startup.backbone;
+		let _playground_id = backbone.node_new(
+			backbone.root_get_id(),
+			"playground",
+			Some(playground)
+		).map_err(|_| "Failed to create the playground node".to_string())?;
+		
+		Ok(TaskStatus::Done)
+	});
 }
 
 pub struct Playground {
@@ -143,8 +242,13 @@ pub struct Playground {
 	chunks_renderer: ChunkRenderManager,
 	sky: sky::SkyRenderer,
 	grid: grid::GridRenderer,
+	solid_color_material: Rc<render::Material>,
 	crosshair_2d: crosshair::CrosshairRenderer2D,
 	crosshair_3d: crosshair::CrosshairRenderer3D,
+	
+	/// Polled for changed resources once per second, which are then reloaded.
+	watcher: resources::ResourceWatcher,
+	watcher_ticks: i32,
 }
 
 impl backbone::Handler for Playground {
@@ -208,7 +312,7 @@ impl backbone::Handler for Playground {
 							self.chunks.set_block(&pos, block);
 						}
 					}
-					
+				
 				},
 				_ => (),
 			};
@@ -234,6 +338,16 @@ impl backbone::Handler for Playground {
 					inventory.block = Some(self.blocks.get_block_by_name_unchecked("adm").get_default_state());
 				},
 				
+				KeyEvent{key: glfw::Key::Num2, scancode: _, action: glfw::Action::Press, modifiers: _} => {
+					let mut inventory  = self.entity_world.get_component_mut::<Inventory>(self.entity_player).expect("player entity inventory component");
+					inventory.block = Some(self.blocks.get_block_by_name_unchecked("adm_fence").get_default_state());
+				},
+				
+				KeyEvent{key: glfw::Key::Num3, scancode: _, action: glfw::Action::Press, modifiers: _} => {
+					let mut inventory  = self.entity_world.get_component_mut::<Inventory>(self.entity_player).expect("player entity inventory component");
+					inventory.block = Some(self.blocks.get_block_by_name_unchecked("adm_cross").get_default_state());
+				},
+				
 				},
 				
 				_ => (),
@@ -242,10 +356,57 @@ impl backbone::Handler for Playground {
 			return
 		}
 		
+		if let Some(cmd) = event.downcast::<CommandEvent>() {
+			if let backbone::Phase::Action = phase {
+				if cmd.command == "reload" {
+					let glfw_context = context
+						.component_get::<GlfwContext>().ok().unwrap();
+					
+					let res = context
+						.component_get::<crate::ResourcesComp>().ok().unwrap();
+					
+					let text_renderer = context
+						.component_get_mut::<render::text::TextRendererComp>().ok().unwrap();
+					
+					let assets = context
+						.component_get_mut::<backbone::WrapperComponent<AssetManager>>().ok().unwrap();
+					
+					// Everything is reloaded, so the changes until now must not cause another reload.
+					self.watcher.poll(&***res);
+					self.reload(&glfw_context.gl, res, assets, text_renderer, None);
+					event.stop();
+				}
+			}
+			
+			return
+		}
+		
 		if let Some(tick) = event.downcast::<TickEvent>() {
 			let glfw_context = context
 				.component_get_mut::<GlfwContext>().ok().unwrap();
 			
+			self.watcher_ticks += 1;
+			if self.watcher_ticks >= tick.tps {
+				self.watcher_ticks = 0;
+				
+				let res = context
+					.component_get::<crate::ResourcesComp>().ok().unwrap();
+				
+				let changed = self.watcher.poll(&***res);
+				if !changed.is_empty() {
+					let names: Vec<String> = changed.iter().map(|location| location.to_string()).collect();
+					info!("Resources changed: {}", names.join(", "));
+					
+					let text_renderer = context
+						.component_get_mut::<render::text::TextRendererComp>().ok().unwrap();
+					
+					let assets = context
+						.component_get_mut::<backbone::WrapperComponent<AssetManager>>().ok().unwrap();
+					
+					self.reload(&glfw_context.gl, res, assets, text_renderer, Some(&changed));
+				}
+			}
+			
 			let mut camera  = self.entity_world.get_component_mut::<Freecam>(self.entity_player).expect("player entity freecam component");
 			
 			camera.active = glfw_context.window.get_cursor_mode() == glfw::CursorMode::Disabled;
@@ -257,6 +418,8 @@ impl backbone::Handler for Playground {
 			} else {
 				camera.target = None;
 			}
+			
+			self.chunks_renderer.tick();
 			return
 		}
 		
@@ -284,6 +447,59 @@ impl backbone::Handler for Playground {
 
 impl Playground {
 	
+	/// Rebuilds all assets, materials, textures and block models that were loaded from one of the `changed` resources;
+	/// or all of them, if `None`. Whatever fails to load is kept as it was, with an error logged.
+	pub fn reload(&mut self, gl: &gl::Gl, res: &resources::Resources, assets: &mut AssetManager, text: &mut render::text::TextRendererComp, changed: Option<&[resources::ResourceLocation]>) {
+		let reloaded = assets.reload(res, changed);
+		if !reloaded.is_empty() {
+			let names: Vec<String> = reloaded.iter().map(|key| key.to_string()).collect();
+			info!("Reloaded assets: {}", names.join(", "));
+		}
+		
+		let affected = |material: &render::Material| changed.map_or(true, |changed| material.is_affected_by(changed));
+		
+		if affected(self.sky.get_material()) {
+			info!("Reloading 'Sky' material...");
+			match sky::SkyRenderer::new(gl, res) {
+				Ok(sky) => self.sky = sky,
+				Err(err) => error!("Failed to reload 'Sky' material, keeping the old one: {}", err),
+			}
+		}
+		
+		if affected(self.grid.get_material()) {
+			info!("Reloading 'Grid' material...");
+			match grid::GridRenderer::new(gl, res) {
+				Ok(grid) => self.grid = grid,
+				Err(err) => error!("Failed to reload 'Grid' material, keeping the old one: {}", err),
+			}
+		}
+		
+		if affected(&self.solid_color_material) {
+			info!("Reloading 'Solid Color' material...");
+			let material = resources::ResourceLocation::parse(render::materials::SOLID_COLOR_MATERIAL)
+				.map_err(render::MaterialError::from)
+				.and_then(|location| render::Material::load(gl, res, &location));
+			
+			match material {
+				Ok(material) => {
+					self.solid_color_material = Rc::new(material);
+					self.crosshair_2d.set_material(&self.solid_color_material);
+					self.crosshair_3d.set_material(&self.solid_color_material);
+				},
+				Err(err) => error!("Failed to reload 'Solid Color' material, keeping the old one: {}", err),
+			}
+		}
+		
+		if affected(&text.material.shader) {
+			info!("Reloading text material...");
+			if let Err(err) = text.material.reload(gl, res) {
+				error!("Failed to reload text material, keeping the old one: {}", err);
+			}
+		}
+		
+		self.chunks_renderer.reload(res, changed);
+	}
+	
 	pub fn render_scene(&mut self, render_event: &RenderEvent) {
 		use crate::render::*;
 		
@@ -310,7 +526,7 @@ impl Playground {
 			render_event.gl.Enable(gl::DEPTH_TEST);
 		}
 		
-		self.chunks_renderer.render(&self.chunks, &transform);
+		self.chunks_renderer.render(&self.chunks, &(proj_matrix * skyw_matrix), &position);
 		
 		if let Some(target) = &camera.target {
 			self.crosshair_3d.draw(&transform, target)
@@ -343,6 +559,19 @@ impl Playground {
 		text.draw_text(&format!("Blocks: {}", self.chunks.get_approximate_volume()), 16.0, 1.0, y_offset);
 		y_offset += 16.0;
 		
+		let chunk_stats = self.chunks_renderer.get_stats();
+		text.draw_text(&format!("Chunks: {} / {} visible", chunk_stats.visible, chunk_stats.total), 16.0, 1.0, y_offset);
+		y_offset += 16.0;
+		
+		let mib = |vertices: usize| (vertices * std::mem::size_of::<test_blocks::ChunkMeshVertex>()) as f32 / (1024.0 * 1024.0);
+		text.draw_text(&format!("Chunk Buffers: {:.1} / {:.1} MiB in {} pages, {:.0}% fragmented",
+			mib(chunk_stats.buffer_used),
+			mib(chunk_stats.buffer_capacity),
+			chunk_stats.buffer_pages,
+			chunk_stats.buffer_fragmentation * 100.0
+		), 16.0, 1.0, y_offset);
+		y_offset += 16.0;
+		
 		let block  = self.entity_world.get_component::<Inventory>(self.entity_player).expect("player entity freecam component").block;
 		let camera  = self.entity_world.get_component::<Freecam>(self.entity_player).expect("player entity freecam component");
 		
@@ -399,5 +628,5 @@ impl Playground {
 			}
 		}
 	}
-	
+
 }
\ No newline at end of file
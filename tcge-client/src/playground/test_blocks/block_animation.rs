@@ -0,0 +1,439 @@
+//! Animated block textures.
+//!
+//! A block texture is animated if a definition file `core:textures/block/<name>.toml` exists next to its image,
+//! with an `[animation]` table. The image is then a vertical strip of square frames, top to bottom. The table may contain:
+//!
+//! - `frametime`: The number of ticks each frame is shown for, unless overridden; defaults to 1.
+//! - `interpolate`: Whether to blend smoothly from each frame into the next; defaults to `false`.
+//! - `frames`: The order of the frames, as frame indices or `{ index, time }` tables; defaults to every frame once, in order.
+//!
+//! Animations are advanced once per tick, and only layers whose image changed are uploaded again.
+//! The frame shown at a given tick only depends on the definition, never on the frame rate.
+
+use std::convert::TryFrom;
+use super::resources::ResourceLocation;
+use super::resources::ResourceLocationError;
+use super::resources::ResourceProvider;
+use super::resources::ResourceError;
+use super::resources::Resources;
+use crate::render::TextureObject;
+
+/// A single step of an animation: Which frame of the strip is shown, and for how many ticks.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlockAnimationFrame {
+	pub index: usize,
+	pub time: u32,
+}
+
+/// The definition of an animation, as written in the texture definition file.
+#[derive(Clone, Debug)]
+pub struct BlockAnimationDef {
+	pub frametime: u32,
+	pub interpolate: bool,
+	pub frames: Option<Vec<BlockAnimationFrame>>,
+}
+
+impl BlockAnimationDef {
+	/// Returns the resource location of the definition of the block texture with the given name.
+	pub fn get_location(name: &str) -> Result<ResourceLocation, ResourceLocationError> {
+		ResourceLocation::parse(&format!("core:textures/block/{}.toml", name))
+	}
+	
+	/// Loads the animation of the block texture with the given name; `None` if it has none.
+	pub fn load(res: &Resources, name: &str) -> Result<Option<Self>, String> {
+		let location = Self::get_location(name)
+			.map_err(|err| err.to_string())?;
+		
+		let source = match res.res_as_string(&location) {
+			Ok(source) => source,
+			Err(ResourceError::NotFound) => return Ok(None),
+			Err(_) => return Err("Could not read texture definition".to_string()),
+		};
+		
+		let root = match toml::from_str(&source) {
+			Ok(toml::Value::Table(root)) => root,
+			Ok(_) => return Err("Root is not a table".to_string()),
+			Err(e) => return Err(e.to_string()),
+		};
+		
+		match root.get("animation") {
+			Some(value) => Ok(Some(Self::parse(value)?)),
+			None => Ok(None),
+		}
+	}
+	
+	/// Parses an `[animation]` table.
+	pub fn parse(value: &toml::Value) -> Result<Self, String> {
+		let table = match value {
+			toml::Value::Table(table) => table,
+			_ => return Err("'animation' must be a table".to_string()),
+		};
+		
+		let frametime = match table.get("frametime") {
+			Some(toml::Value::Integer(time)) => u32::try_from(*time).ok().filter(|time| *time > 0),
+			Some(_) => None,
+			None => Some(1),
+		}.ok_or_else(|| "'frametime' must be a positive integer".to_string())?;
+		
+		let interpolate = match table.get("interpolate") {
+			Some(toml::Value::Boolean(interpolate)) => *interpolate,
+			Some(_) => return Err("'interpolate' must be a boolean".to_string()),
+			None => false,
+		};
+		
+		let frames = match table.get("frames") {
+			Some(toml::Value::Array(array)) => {
+				let mut frames = Vec::with_capacity(array.len());
+				for frame in array.iter() {
+					frames.push(Self::parse_frame(frame, frametime)?);
+				}
+				
+				if frames.is_empty() {
+					return Err("'frames' must not be empty".to_string());
+				}
+				
+				Some(frames)
+			},
+			Some(_) => return Err("'frames' must be an array".to_string()),
+			None => None,
+		};
+		
+		Ok(Self {
+			frametime,
+			interpolate,
+			frames,
+		})
+	}
+	
+	fn parse_frame(value: &toml::Value, frametime: u32) -> Result<BlockAnimationFrame, String> {
+		match value {
+			toml::Value::Integer(index) if *index >= 0 => Ok(BlockAnimationFrame {
+				index: *index as usize,
+				time: frametime,
+			}),
+			toml::Value::Table(table) => {
+				let index = match table.get("index") {
+					Some(toml::Value::Integer(index)) if *index >= 0 => *index as usize,
+					_ => return Err("Frame must have a non-negative 'index'".to_string()),
+				};
+				
+				let time = match table.get("time") {
+					Some(toml::Value::Integer(time)) => u32::try_from(*time).ok().filter(|time| *time > 0),
+					Some(_) => None,
+					None => Some(frametime),
+				}.ok_or_else(|| "Frame 'time' must be a positive integer".to_string())?;
+				
+				Ok(BlockAnimationFrame {
+					index,
+					time,
+				})
+			},
+			_ => Err("Frame must be a non-negative integer or a table".to_string()),
+		}
+	}
+	
+	/// Builds the sequence of frames for a strip of the given number of frames.
+	pub fn sequence(&self, frame_count: usize) -> Result<Vec<BlockAnimationFrame>, String> {
+		let frames = match &self.frames {
+			Some(frames) => frames.clone(),
+			None => (0..frame_count)
+				.map(|index| BlockAnimationFrame { index, time: self.frametime })
+				.collect(),
+		};
+		
+		if let Some(frame) = frames.iter().find(|frame| frame.index >= frame_count) {
+			return Err(format!("Frame {} does not exist; the strip has {} frames", frame.index, frame_count));
+		}
+		
+		Ok(frames)
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// What an animation shows at a given tick.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BlockAnimationSample {
+	/// The frame being shown.
+	pub frame: usize,
+	
+	/// The frame being blended into; the same as `frame` without interpolation.
+	pub next: usize,
+	
+	/// How far the blend into `next` has progressed, in 1/256ths.
+	pub blend: u8,
+}
+
+/// The timing of an animation; independent of any images.
+#[derive(Clone, Debug)]
+pub struct BlockAnimationTiming {
+	pub sequence: Vec<BlockAnimationFrame>,
+	pub interpolate: bool,
+	
+	/// The length of one loop of the animation, in ticks.
+	pub duration: u64,
+}
+
+impl BlockAnimationTiming {
+	pub fn new(sequence: Vec<BlockAnimationFrame>, interpolate: bool) -> Self {
+		assert!(!sequence.is_empty(), "An animation needs at least one frame.");
+		let duration = sequence.iter().map(|frame| frame.time as u64).sum();
+		
+		Self {
+			sequence,
+			interpolate,
+			duration,
+		}
+	}
+	
+	/// Returns what the animation shows at the given tick.
+	pub fn sample(&self, tick: u64) -> BlockAnimationSample {
+		let mut time = tick % self.duration;
+		
+		for (position, frame) in self.sequence.iter().enumerate() {
+			let length = frame.time as u64;
+			
+			if time < length {
+				if !self.interpolate {
+					return BlockAnimationSample {
+						frame: frame.index,
+						next: frame.index,
+						blend: 0,
+					}
+				}
+				
+				let next = &self.sequence[(position + 1) % self.sequence.len()];
+				return BlockAnimationSample {
+					frame: frame.index,
+					next: next.index,
+					blend: (time * 256 / length) as u8,
+				}
+			}
+			
+			time -= length;
+		}
+		
+		unreachable!("The tick is always within the duration of the animation.")
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// An animated layer of the block texture array.
+#[derive(Clone)]
+pub struct BlockTextureAnimation {
+	/// The layer of the texture array the animation is shown in.
+	pub layer: u32,
+	
+	/// The RGBA pixels of every frame, scaled to the size of the layers.
+	pub frames: Vec<Vec<u8>>,
+	
+	pub timing: BlockAnimationTiming,
+}
+
+impl BlockTextureAnimation {
+	/// Writes the image for the given sample into `out`.
+	pub fn render(&self, sample: &BlockAnimationSample, out: &mut Vec<u8>) {
+		out.clear();
+		
+		let current = &self.frames[sample.frame];
+		if sample.blend == 0 || sample.frame == sample.next {
+			out.extend_from_slice(current);
+			return;
+		}
+		
+		let next = &self.frames[sample.next];
+		let blend = sample.blend as u32;
+		
+		out.extend(current.iter().zip(next.iter()).map(|(a, b)| {
+			((*a as u32 * (256 - blend) + *b as u32 * blend) / 256) as u8
+		}));
+	}
+}
+
+/// Advances all texture animations, tick by tick, uploading the layers that changed.
+pub struct BlockTextureAnimator {
+	animations: Vec<BlockTextureAnimation>,
+	
+	/// The sample each animation currently shows.
+	samples: Vec<BlockAnimationSample>,
+	
+	ticks: u64,
+	pixels: Vec<u8>,
+}
+
+impl BlockTextureAnimator {
+	pub fn new(animations: Vec<BlockTextureAnimation>) -> Self {
+		let samples = animations.iter()
+			.map(|animation| animation.timing.sample(0))
+			.collect();
+		
+		Self {
+			animations,
+			samples,
+			ticks: 0,
+			pixels: vec![],
+		}
+	}
+	
+	/// Advances all animations by one tick, calling `changed` with the layer and image of each animation that changed.
+	pub fn tick(&mut self, changed: &mut dyn FnMut(u32, &[u8])) {
+		self.ticks += 1;
+		
+		for (animation, previous) in self.animations.iter().zip(self.samples.iter_mut()) {
+			let sample = animation.timing.sample(self.ticks);
+			
+			if sample == *previous {
+				continue;
+			}
+			
+			*previous = sample;
+			animation.render(&sample, &mut self.pixels);
+			changed(animation.layer, &self.pixels);
+		}
+	}
+	
+	/// Advances all animations by one tick, and uploads the changed layers into the given array texture.
+	pub fn tick_and_upload(&mut self, texture: &TextureObject) {
+		let mut uploaded = false;
+		
+		self.tick(&mut |layer, pixels| {
+			texture.upload_layer(layer, pixels);
+			uploaded = true;
+		});
+		
+		if uploaded {
+			texture.generate_mipmaps();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::BlockTextureSprite;
+	
+	fn frames(frames: &[(usize, u32)]) -> Vec<BlockAnimationFrame> {
+		frames.iter()
+			.map(|(index, time)| BlockAnimationFrame { index: *index, time: *time })
+			.collect()
+	}
+	
+	fn shown(timing: &BlockAnimationTiming, tick: u64) -> usize {
+		timing.sample(tick).frame
+	}
+	
+	#[test]
+	fn per_frame_durations() {
+		let timing = BlockAnimationTiming::new(frames(&[(0, 2), (3, 3), (1, 1)]), false);
+		assert_eq!(timing.duration, 6);
+		
+		let expected = [0, 0, 3, 3, 3, 1];
+		for (tick, frame) in expected.iter().enumerate() {
+			assert_eq!(shown(&timing, tick as u64), *frame, "tick {}", tick);
+		}
+	}
+	
+	#[test]
+	fn wraps_around() {
+		let timing = BlockAnimationTiming::new(frames(&[(0, 2), (3, 3), (1, 1)]), false);
+		
+		for tick in 0..6 {
+			assert_eq!(shown(&timing, tick), shown(&timing, tick + 6));
+			assert_eq!(shown(&timing, tick), shown(&timing, tick + 6 * 1000));
+		}
+		
+		let sample = timing.sample(5);
+		assert_eq!((sample.frame, sample.next, sample.blend), (1, 1, 0));
+	}
+	
+	#[test]
+	fn times_must_fit_in_32_bits() {
+		let parse = |src: &str| BlockAnimationDef::parse(&src.parse::<toml::Value>().unwrap());
+		
+		assert!(parse("frametime = 4294967295").is_ok());
+		assert!(parse("frametime = 4294967296").is_err());
+		assert!(parse("frametime = 0").is_err());
+		assert!(parse("frames = [{ index = 0, time = 4294967296 }]").is_err());
+		assert!(parse("frames = [{ index = 0, time = -1 }]").is_err());
+	}
+	
+	#[test]
+	fn interpolation_factor() {
+		let timing = BlockAnimationTiming::new(frames(&[(0, 4), (1, 2)]), true);
+		
+		let expected = [
+			(0, 1, 0), (0, 1, 64), (0, 1, 128), (0, 1, 192),
+			// The last frame blends back into the first.
+			(1, 0, 0), (1, 0, 128),
+			(0, 1, 0),
+		];
+		
+		for (tick, (frame, next, blend)) in expected.iter().enumerate() {
+			assert_eq!(timing.sample(tick as u64), BlockAnimationSample { frame: *frame, next: *next, blend: *blend }, "tick {}", tick);
+		}
+	}
+	
+	#[test]
+	fn render_blends_frames() {
+		let animation = BlockTextureAnimation {
+			layer: 1,
+			frames: vec![vec![0, 0, 0, 255], vec![255, 255, 255, 255]],
+			timing: BlockAnimationTiming::new(frames(&[(0, 2), (1, 2)]), true),
+		};
+		
+		let mut out = vec![];
+		animation.render(&BlockAnimationSample { frame: 0, next: 1, blend: 128 }, &mut out);
+		assert_eq!(out, vec![127, 127, 127, 255]);
+		
+		animation.render(&BlockAnimationSample { frame: 1, next: 0, blend: 0 }, &mut out);
+		assert_eq!(out, vec![255, 255, 255, 255]);
+	}
+	
+	#[test]
+	fn animate_splits_square_frames() {
+		let def = BlockAnimationDef {
+			frametime: 2,
+			interpolate: false,
+			frames: None,
+		};
+		
+		let mut sprite = BlockTextureSprite {
+			name: "strip".to_string(),
+			width: 2,
+			height: 6,
+			pixels: (0..2 * 6 * 4).map(|byte| byte as u8).collect(),
+			animation: None,
+		};
+		
+		assert!(sprite.animate(&def).is_ok());
+		assert_eq!((sprite.width, sprite.height), (2, 2));
+		
+		let (frames, timing) = sprite.animation.as_ref().unwrap();
+		assert_eq!(frames.len(), 3);
+		assert_eq!(frames[1][0], 16);
+		assert_eq!(timing.duration, 6);
+		assert_eq!(sprite.pixels, frames[0]);
+	}
+	
+	#[test]
+	fn animate_rejects_non_square_strips() {
+		let def = BlockAnimationDef {
+			frametime: 1,
+			interpolate: false,
+			frames: None,
+		};
+		
+		let mut sprite = BlockTextureSprite {
+			name: "strip".to_string(),
+			width: 4,
+			height: 6,
+			pixels: vec![0; 4 * 6 * 4],
+			animation: None,
+		};
+		
+		assert!(sprite.animate(&def).is_err());
+		assert!(sprite.animation.is_none());
+		assert_eq!((sprite.width, sprite.height), (4, 6));
+	}
+}
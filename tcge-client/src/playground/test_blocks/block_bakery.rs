@@ -7,57 +7,173 @@ pub type StaticBlockBakeryRef = Rc<StaticBlockBakery>;
 
 pub struct StaticBlockBakery {
 	baked_blocks: Vec<Box<dyn BakedBlock>>,
+	occlusion: Vec<[FaceMask; 8]>,
+	layers: Vec<BlockRenderLayer>,
+	tints: Vec<Option<Rc<dyn BlockTintProvider>>>,
+	textures: BlockTextureArray,
+}
+
+/// The resolved models of all blocks, and the textures they use: The first stage of building a bakery.
+pub struct BlockModelSet {
+	models: Vec<(BlockId, BlockModel)>,
+	
+	/// The names of all textures used by the models, sorted and without duplicates.
+	pub textures: Vec<String>,
 }
 
 impl StaticBlockBakery {
 	//
 	
-	pub fn new(_res: &resources::Resources, blocks: &BlocksRef) -> Result<StaticBlockBakery, ()> {
+	pub fn new(res: &resources::Resources, blocks: &BlocksRef) -> Result<StaticBlockBakery, ()> {
+		let models = Self::load_models(res, blocks);
 		
-		// TODO: The texture atlas should be built outside the bakery...
-		let mut textures: FxHashMap<String, BlockUv> = FxHashMap::default();
-		textures.insert("missingno".to_string(), BlockUv::unit());
-		textures.insert("tex1".to_string(), BlockUv::new_from_pos(0, 0));
-		textures.insert("tex2".to_string(), BlockUv::new_from_pos(1, 0));
-		textures.insert("tex3".to_string(), BlockUv::new_from_pos(2, 0));
-		textures.insert("tex4".to_string(), BlockUv::new_from_pos(3, 0));
-		textures.insert("tex5".to_string(), BlockUv::new_from_pos(4, 0));
+		let block_textures = BlockTextureArray::build(res, &models.textures).map_err(|err| {
+			error!("{}", err);
+		})?;
 		
-		// --- Create rendering-table for all blocks...
-		let mut baked_blocks: Vec<Box<dyn BakedBlock>> = Vec::with_capacity(blocks.get_blocks().len() + 1);
-		for _ in 0..blocks.get_blocks().len() {
-			// ...and fill it with EmptyBakedBlock's.
-			baked_blocks.push(Box::new(EmptyBakedBlock {}));
-		}
+		Ok(Self::bake(blocks, models, block_textures))
+	}
+	
+	/// Loads and resolves the models of all blocks, and collects the textures they use.
+	///
+	/// The textures can then be built independently (even on another thread) and passed to `bake`.
+	pub fn load_models(res: &resources::Resources, blocks: &BlocksRef) -> BlockModelSet {
+		
+		// --- Load and resolve the models of all blocks, before baking any of them.
+		let mut loader = BlockModelLoader::new();
+		let mut block_models: Vec<(BlockId, BlockModel)> = Vec::with_capacity(blocks.get_blocks().len());
 		
-		// --- Go trough all blocks and bake them.
 		for (id, block) in blocks.get_blocks() {
 			
 			if block.get_name() == "air" {
-				// Do not bake air.
+				// Air has no model.
 				continue;
 			}
 			
-			// TODO: Load model from a file, BEFORE the bakery, somehow...
-			let mut block_model = BlockModel::default();
-			block_model.textures[0] = format!("tex{}", id.raw());
+			let name = format!("block/{}", block.get_name());
+			let block_model = match loader.resolve(res, &name) {
+				Ok(block_model) => block_model,
+				Err(err) => {
+					error!("{}", err);
+					BlockModel::default()
+				}
+			};
+			
+			block_models.push((*id, block_model));
+		}
+		
+		// --- Collect all textures used by the models.
+		let mut textures: Vec<String> = vec![];
+		for (_, block_model) in block_models.iter() {
+			block_model.collect_textures(&mut textures);
+		}
+		
+		textures.sort();
+		textures.dedup();
+		
+		BlockModelSet {
+			models: block_models,
+			textures,
+		}
+	}
+	
+	/// Bakes the given models, whose textures must all be placed in `block_textures`.
+	pub fn bake(blocks: &BlocksRef, models: BlockModelSet, block_textures: BlockTextureArray) -> StaticBlockBakery {
+		let block_models = models.models;
+		
+		// --- Create rendering-table for all blocks...
+		let mut baked_blocks: Vec<Box<dyn BakedBlock>> = Vec::with_capacity(blocks.get_blocks().len() + 1);
+		let mut occlusion: Vec<[FaceMask; 8]> = Vec::with_capacity(blocks.get_blocks().len() + 1);
+		let mut layers: Vec<BlockRenderLayer> = Vec::with_capacity(blocks.get_blocks().len() + 1);
+		let mut tints: Vec<Option<Rc<dyn BlockTintProvider>>> = Vec::with_capacity(blocks.get_blocks().len() + 1);
+		for _ in 0..blocks.get_blocks().len() {
+			// ...and fill it with EmptyBakedBlock's, which occlude nothing.
+			baked_blocks.push(Box::new(EmptyBakedBlock {}));
+			occlusion.push([FACE_MASK_EMPTY; 8]);
+			layers.push(BlockRenderLayer::Opaque);
+			tints.push(None);
+		}
+		
+		let tint_providers = BlockTintProviders::new();
+		
+		// --- Go trough all models and bake them.
+		for (id, block_model) in block_models.iter() {
+			let block = blocks.get_block_by_id_unchecked(*id);
 			
 			// Bake the model for the block...
-			let baked_block = Self::bake_model(block.borrow(), &block_model, &textures);
+			let baked_block = Self::bake_model(blocks, block.borrow(), block_model, &block_textures);
 			
 			// ...and place it into the bakery's list.
 			baked_blocks[id.raw() as usize] = baked_block;
+			occlusion[id.raw() as usize] = Self::bake_occlusion(block_model);
+			layers[id.raw() as usize] = block_model.layer;
+			
+			// Look up the tint provider of the block, if it has one.
+			tints[id.raw() as usize] = block_model.tint.as_ref().and_then(|tint| {
+				let provider = tint_providers.get(tint);
+				if provider.is_none() {
+					warn!("Block '{}' uses an unknown tint provider '{}'", block.get_name(), tint);
+				}
+				provider
+			});
 		}
 		
 		// Nothing went wrong, yay!
-		Ok(StaticBlockBakery {
-			baked_blocks
+		StaticBlockBakery {
+			baked_blocks,
+			occlusion,
+			layers,
+			tints,
+			textures: block_textures
+		}
+	}
+	
+	fn bake_model(blocks: &BlocksRef, _block: &dyn Block, block_model: &BlockModel, textures: &BlockTextureArray) -> Box<dyn BakedBlock> {
+		let base = Self::bake_basic(block_model, textures);
+		
+		if !block_model.is_connected() {
+			return Box::new(base);
+		}
+		
+		let bake_part = |part: &BlockModelPart| ConnectedBakedPart {
+			when: part.when.iter()
+				.map(|condition| Self::bake_condition(blocks, condition))
+				.collect(),
+			baked: Self::bake_basic(&part.model, textures),
+		};
+		
+		Box::new(ConnectedBakedBlock {
+			base,
+			variants: block_model.variants.iter().map(bake_part).collect(),
+			parts: block_model.parts.iter().map(bake_part).collect(),
 		})
 	}
 	
-	fn bake_model(block: &dyn Block, block_model: &BlockModel, textures: &FxHashMap<String, BlockUv>) -> Box<dyn BakedBlock> {
+	/// Resolves the block a condition refers to; conditions on unknown blocks never match.
+	fn bake_condition(blocks: &BlocksRef, condition: &BlockModelCondition) -> BakedCondition {
+		let (x, y, z) = condition.offset;
 		
-		let mut sides: [smallvec::SmallVec<[BakedBlockMeshFace;6]>; 8] = [
+		let matches = match &condition.matches {
+			BlockModelMatch::Same => BakedMatch::Same,
+			BlockModelMatch::Solid => BakedMatch::Solid,
+			BlockModelMatch::Block(name) => match blocks.get_block_by_name(name) {
+				Some(block) => BakedMatch::Block(block.get_id()),
+				None => {
+					warn!("Block model condition refers to unknown block '{}'", name);
+					BakedMatch::Nothing
+				}
+			},
+		};
+		
+		BakedCondition {
+			index: BakeryContext::get_neighbour_index(x as isize, y as isize, z as isize),
+			matches,
+			negate: condition.negate,
+		}
+	}
+	
+	fn bake_basic(block_model: &BlockModel, textures: &BlockTextureArray) -> BasicBakedBlock {
+		let mut sides: [smallvec::SmallVec<[(BakedBlockMeshFace, FaceMask);6]>; 8] = [
 			smallvec![],
 			smallvec![],
 			smallvec![],
@@ -69,125 +185,228 @@ impl StaticBlockBakery {
 		];
 		
 		for element in &block_model.elements {
-			
-			let [min_x, min_y, min_z, max_x, max_y, max_z] = element.bounds.clone();
-			
-			// TODO: What about rotation?
-			
-			{ // Positive Y: Top
-				let i_face = Face::PositiveY;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
+			for face in element.faces.iter() {
+				let baked_face = match Self::bake_face(block_model, element, face, textures) {
+					Some(baked_face) => baked_face,
+					None => continue,
+				};
 				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					let uv = uv.subset(&face.uv);
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(min_x, max_y, max_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, max_y, max_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, max_y, min_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(min_x, max_y, min_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
-				}
-			}
-			
-			{ // Negative Y: Bottom
-				let i_face = Face::NegativeY;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
+				let side = face.cull.unwrap_or(Face::EveryDir);
+				let mask = Self::cull_mask(element, face);
 				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(min_x, min_y, min_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, min_y, min_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, min_y, max_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(min_x, min_y, max_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
+				if element.outside {
+					sides[side.uid()].push((baked_face, mask));
 				}
-			}
-			
-			{ // Negative X
-				let i_face = Face::NegativeX;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
 				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(min_x, max_y, max_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(min_x, max_y, min_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(min_x, min_y, min_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(min_x, min_y, max_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
+				if element.inside {
+					sides[side.uid()].push((baked_face.flipped(), mask));
 				}
 			}
+		}
+		
+		BasicBakedBlock {
+			sides
+		}
+	}
+	
+	/// Bakes a single face of a model element, if it has a texture.
+	fn bake_face(
+		block_model: &BlockModel,
+		element: &BlockModelElement,
+		face: &BlockModelElementFace,
+		textures: &BlockTextureArray
+	) -> Option<BakedBlockMeshFace> {
+		let texture_id = face.texture?;
+		let texture = &block_model.textures[texture_id as usize];
+		let layer = textures.get_layer(texture) as u16;
+		
+		// The texture coordinates of the four corners, rotated in quarter turns.
+		let [umin, vmin, umax, vmax] = face.uv;
+		let uvs = [
+			(umin, vmin),
+			(umax, vmin),
+			(umax, vmax),
+			(umin, vmax),
+		];
+		
+		let [min_x, min_y, min_z, max_x, max_y, max_z] = element.bounds;
+		let corners = Self::face_corners(face.side);
+		
+		let (nx, ny, nz) = face.side.normal();
+		let normal = match &element.tform {
+			Some(tform) => tform.apply_normal([nx, ny, nz]),
+			None => [nx, ny, nz],
+		};
+		
+		let mut vertices = [BakedBlockMeshVertex::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0); 4];
+		
+		for (index, vertex) in vertices.iter_mut().enumerate() {
+			let [x, y, z] = corners[index];
+			let position = [
+				if x { max_x } else { min_x },
+				if y { max_y } else { min_y },
+				if z { max_z } else { min_z },
+			];
 			
-			{ // Positive X
-				let i_face = Face::PositiveX;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
-				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(max_x, min_y, max_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, min_y, min_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, max_y, min_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(max_x, max_y, max_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
-				}
-			}
+			let position = match &element.tform {
+				Some(tform) => tform.apply_point(position),
+				None => position,
+			};
 			
-			{ // Negative Z
-				let i_face = Face::NegativeZ;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
-				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(min_x, max_y, min_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, max_y, min_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, min_y, min_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(min_x, min_y, min_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
-				}
+			let (u, v) = uvs[(index + face.uv_spin as usize) % 4];
+			
+			*vertex = BakedBlockMeshVertex::new(
+				position[0], position[1], position[2],
+				u, v,
+				normal[0], normal[1], normal[2]
+			);
+			vertex.texture = layer;
+		}
+		
+		Some(BakedBlockMeshFace {
+			a: vertices[0],
+			b: vertices[1],
+			c: vertices[2],
+			d: vertices[3],
+			layer: block_model.layer,
+			tint: face.tint,
+		})
+	}
+	
+	/// Computes the area of each side of the block that is covered by the outside faces of the model.
+	///
+	/// Only faces lying flat on the side of the block count; rotated elements never occlude anything.
+	/// The variants and parts of a model are ignored, since they depend on the neighbours of the block.
+	fn bake_occlusion(block_model: &BlockModel) -> [FaceMask; 8] {
+		let mut occlusion = [FACE_MASK_EMPTY; 8];
+		
+		for element in &block_model.elements {
+			if element.tform.is_some() || !element.outside {
+				continue;
 			}
 			
-			{ // Positive Z
-				let i_face = Face::PositiveZ;
-				let face = element.faces[i_face.uid() - 1];
-				let n = i_face.normal();
+			let [min_x, min_y, min_z, max_x, max_y, max_z] = element.bounds;
+			
+			for face in element.faces.iter() {
+				if face.texture.is_none() {
+					continue;
+				}
 				
-				if let Some(texture_id) = face.texture {
-					let texture = &block_model.textures[texture_id as usize];
-					let uv = textures.get(texture).expect("valid texture reference");
-					
-					sides[if face.cull { i_face.uid()} else {Face::EveryDir.uid()}].push((
-						(min_x, min_y, max_z, uv.umin, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, min_y, max_z, uv.umax, uv.vmin, n.0, n.1, n.2).into(),
-						(max_x, max_y, max_z, uv.umax, uv.vmax, n.0, n.1, n.2).into(),
-						(min_x, max_y, max_z, uv.umin, uv.vmax, n.0, n.1, n.2).into(),
-					).into());
+				let on_side = match face.side {
+					Face::PositiveX => max_x >= 1.0,
+					Face::NegativeX => min_x <= 0.0,
+					Face::PositiveY => max_y >= 1.0,
+					Face::NegativeY => min_y <= 0.0,
+					Face::PositiveZ => max_z >= 1.0,
+					Face::NegativeZ => min_z <= 0.0,
+					Face::EveryDir => false,
+				};
+				
+				if on_side {
+					occlusion[face.side.uid()] |= face_mask(Self::face_rect(element, face.side), true);
 				}
 			}
-			
 		}
 		
-		Box::new(BasicBakedBlock {
-			sides
-		})
+		occlusion
+	}
+	
+	/// Computes the area a face must have covered by its neighbour to be culled.
+	///
+	/// Faces that are rotated or not parallel to their culling side must be covered completely.
+	fn cull_mask(element: &BlockModelElement, face: &BlockModelElementFace) -> FaceMask {
+		match face.cull {
+			Some(side) if side == face.side && element.tform.is_none() => face_mask(Self::face_rect(element, side), false),
+			_ => FACE_MASK_FULL,
+		}
+	}
+	
+	/// Projects the bounds of an element onto the plane of the given side, as `[amin, bmin, amax, bmax]`.
+	///
+	/// The plane axes are `(y, z)` for the X sides, `(x, z)` for the Y sides and `(x, y)` for the Z sides.
+	fn face_rect(element: &BlockModelElement, side: Face) -> [f32; 4] {
+		let [min_x, min_y, min_z, max_x, max_y, max_z] = element.bounds;
+		match side {
+			Face::PositiveX | Face::NegativeX => [min_y, min_z, max_y, max_z],
+			Face::PositiveY | Face::NegativeY => [min_x, min_z, max_x, max_z],
+			Face::PositiveZ | Face::NegativeZ => [min_x, min_y, max_x, max_y],
+			Face::EveryDir => [0.0, 0.0, 1.0, 1.0],
+		}
+	}
+	
+	/// Returns the four corners of the given side of a box, in the winding order expected by the quad index buffer.
+	///
+	/// Each corner is given as a `(x, y, z)` triple of flags, that are `true` for the maximum of the axis.
+	fn face_corners(side: Face) -> [[bool; 3]; 4] {
+		const F: bool = false;
+		const T: bool = true;
+		match side {
+			Face::PositiveX => [[T, F, T], [T, F, F], [T, T, F], [T, T, T]],
+			Face::NegativeX => [[F, T, T], [F, T, F], [F, F, F], [F, F, T]],
+			Face::PositiveY => [[F, T, T], [T, T, T], [T, T, F], [F, T, F]],
+			Face::NegativeY => [[F, F, F], [T, F, F], [T, F, T], [F, F, T]],
+			Face::PositiveZ => [[F, F, T], [T, F, T], [T, T, T], [F, T, T]],
+			Face::NegativeZ => [[F, T, F], [T, T, F], [T, F, F], [F, F, F]],
+			Face::EveryDir => [[F, F, F]; 4],
+		}
+	}
+	
+	/// Returns whether the bakery reads the resource at the given location: A block model, texture or animation.
+	pub fn is_affected_by(location: &resources::ResourceLocation) -> bool {
+		location.namespace() == resources::location::DEFAULT_NAMESPACE
+			&& (location.path().starts_with("models/") || location.path().starts_with("textures/block/"))
+	}
+	
+	/// Returns the texture array all baked blocks refer to.
+	pub fn get_textures(&self) -> &BlockTextureArray {
+		&self.textures
+	}
+	
+	/// Returns the area of the given side of the `neighbour` that hides faces of the `block` next to it.
+	///
+	/// Blocks outside of the opaque layer only hide faces of blocks of the same type, like the inside of a body of water.
+	pub fn get_occlusion(&self, neighbour: &BlockState, side: Face, block: &BlockState) -> FaceMask {
+		if self.get_layer(neighbour) != BlockRenderLayer::Opaque && neighbour.id != block.id {
+			return FACE_MASK_EMPTY
+		}
+		
+		match self.occlusion.get(neighbour.id.raw() as usize) {
+			Some(occlusion) => occlusion[side.uid()],
+			None => FACE_MASK_EMPTY
+		}
+	}
+	
+	/// Returns the render layer of the given block.
+	pub fn get_layer(&self, block: &BlockState) -> BlockRenderLayer {
+		match self.layers.get(block.id.raw() as usize) {
+			Some(layer) => *layer,
+			None => BlockRenderLayer::Opaque
+		}
+	}
+	
+	/// Returns the colour of the given face of the given block, placed at the given position.
+	pub fn get_tint(&self, block: &BlockState, pos: &BlockCoord, face: &BakedBlockMeshFace) -> BlockTint {
+		let index = match face.tint {
+			Some(index) => index,
+			None => return TINT_NONE
+		};
+		
+		match self.tints.get(block.id.raw() as usize) {
+			Some(Some(provider)) => provider.get_tint(pos, block, index),
+			_ => TINT_NONE
+		}
+	}
+	
+	/// Returns `true` if the given block is opaque and covers all of its sides, i.e. cannot be seen through.
+	pub fn is_opaque(&self, block: &BlockState) -> bool {
+		if self.get_layer(block) != BlockRenderLayer::Opaque {
+			return false
+		}
+		
+		match self.occlusion.get(block.id.raw() as usize) {
+			Some(occlusion) => occlusion[1..7].iter().all(|mask| *mask == FACE_MASK_FULL),
+			None => false
+		}
 	}
 	
 	pub fn render_block(&self, context: &BakeryContext, block: &BlockState, out: &mut dyn FnMut(&BakedBlockMeshFace)) {
@@ -200,25 +419,112 @@ impl StaticBlockBakery {
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
+/// The area of one side of a block, as a grid of 8x8 cells; one bit per cell.
+///
+/// Cell `(a, b)` is bit `b * 8 + a`, with the plane axes as described by `face_rect`.
+pub type FaceMask = u64;
+
+/// A mask covering nothing.
+pub const FACE_MASK_EMPTY: FaceMask = 0;
+
+/// A mask covering the entire side of a block.
+pub const FACE_MASK_FULL: FaceMask = !0;
+
+/// Converts a rectangle `[amin, bmin, amax, bmax]` on the side of a block into a mask.
+///
+/// If `inner` is set, only cells completely within the rectangle are included;
+/// otherwise every cell the rectangle touches is.
+pub fn face_mask(rect: [f32; 4], inner: bool) -> FaceMask {
+	let [amin, bmin, amax, bmax] = rect;
+	let mut mask = FACE_MASK_EMPTY;
+	
+	for b in 0..8 {
+		for a in 0..8 {
+			let (a0, a1) = (a as f32 / 8.0, (a + 1) as f32 / 8.0);
+			let (b0, b1) = (b as f32 / 8.0, (b + 1) as f32 / 8.0);
+			
+			let hit = if inner {
+				amin <= a0 && a1 <= amax && bmin <= b0 && b1 <= bmax
+			} else {
+				amin < a1 && a0 < amax && bmin < b1 && b0 < bmax
+			};
+			
+			if hit {
+				mask |= 1 << (b * 8 + a);
+			}
+		}
+	}
+	
+	mask
+}
+
+/// Everything a baked block needs to know about its surroundings.
 pub struct BakeryContext {
-	pub occluded: [bool;8]
+	/// For every side, the area covered by the neighbouring block on that side.
+	///
+	/// The last entry (`EveryDir`) hides all faces that are never culled, if full.
+	pub occlusion: [FaceMask;8],
+	
+	/// The 3x3x3 blocks around (and including) the block, indexed as given by `get_neighbour_index`.
+	pub neighbours: [BlockState;27],
+	
+	/// The state of air, which neighbours are set to when they are not known.
+	pub air: BlockState,
 }
 
 impl BakeryContext {
-	pub fn new() -> BakeryContext {
+	pub fn new(air: BlockState) -> BakeryContext {
 		BakeryContext {
-			occluded: [false;8],
+			occlusion: [FACE_MASK_EMPTY;8],
+			neighbours: [air;27],
+			air,
 		}
 	}
 	
+	/// Returns the index into `neighbours` of the block at the given offset; each component must be in `-1..=1`.
+	#[inline]
+	pub fn get_neighbour_index(x: isize, y: isize, z: isize) -> usize {
+		((y + 1) * 9 + (z + 1) * 3 + (x + 1)) as usize
+	}
+	
+	/// Returns the block at the given offset from the block; each component must be in `-1..=1`.
+	#[allow(dead_code)]
+	#[inline]
+	pub fn get_neighbour(&self, x: isize, y: isize, z: isize) -> BlockState {
+		self.neighbours[Self::get_neighbour_index(x, y, z)]
+	}
+	
+	/// Sets the block at the given offset from the block; each component must be in `-1..=1`.
+	#[inline]
+	pub fn set_neighbour(&mut self, x: isize, y: isize, z: isize, state: BlockState) {
+		self.neighbours[Self::get_neighbour_index(x, y, z)] = state;
+	}
+	
+	/// Sets every side to be either fully occluded or not at all.
 	pub fn set_occlusion(&mut self, x_pos: bool, y_pos: bool, z_pos: bool, x_neg: bool, y_neg: bool, z_neg: bool, omni: bool) {
-		self.occluded[1] = x_pos;
-		self.occluded[2] = x_neg;
-		self.occluded[3] = y_pos;
-		self.occluded[4] = y_neg;
-		self.occluded[5] = z_pos;
-		self.occluded[6] = z_neg;
-		self.occluded[7] = omni;
+		let mask = |occluded: bool| if occluded { FACE_MASK_FULL } else { FACE_MASK_EMPTY };
+		self.set_occlusion_masks(
+			mask(x_pos), mask(y_pos), mask(z_pos),
+			mask(x_neg), mask(y_neg), mask(z_neg),
+			mask(omni)
+		);
+	}
+	
+	/// Sets the area of every side that is covered by the neighbouring block.
+	pub fn set_occlusion_masks(&mut self, x_pos: FaceMask, y_pos: FaceMask, z_pos: FaceMask, x_neg: FaceMask, y_neg: FaceMask, z_neg: FaceMask, omni: FaceMask) {
+		self.occlusion[1] = x_pos;
+		self.occlusion[2] = x_neg;
+		self.occlusion[3] = y_pos;
+		self.occlusion[4] = y_neg;
+		self.occlusion[5] = z_pos;
+		self.occlusion[6] = z_neg;
+		self.occlusion[7] = omni;
+	}
+	
+	/// Returns `true` if a face needing the given area to be covered on the given side is hidden.
+	#[inline]
+	pub fn is_occluded(&self, side: Face, mask: FaceMask) -> bool {
+		mask & !self.occlusion[side.uid()] == 0
 	}
 }
 
@@ -236,19 +542,18 @@ trait BakedBlock {
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
 struct BasicBakedBlock {
-	sides: [smallvec::SmallVec<[BakedBlockMeshFace;6]>;8],
+	/// The faces of the block, grouped by the side that culls them, along with the area they need covered to be culled.
+	sides: [smallvec::SmallVec<[(BakedBlockMeshFace, FaceMask);6]>;8],
 }
 
 impl BasicBakedBlock {
 	fn transfer(&self, context: &BakeryContext, face: Face, out: &mut dyn FnMut(&BakedBlockMeshFace)) {
 		let face_id = face.id() as usize;
 		
-		if context.occluded[face_id] {
-			return;
-		}
-		
-		for vertex in self.sides[face_id].iter() {
-			out(&vertex);
+		for (vertex, mask) in self.sides[face_id].iter() {
+			if !context.is_occluded(face, *mask) {
+				out(&vertex);
+			}
 		}
 	}
 }
@@ -271,6 +576,85 @@ impl BakedBlock for BasicBakedBlock {
 }
 
 
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A block whose geometry depends on its neighbours, as described by the variants and parts of its model.
+struct ConnectedBakedBlock {
+	/// The geometry used if no variant matches.
+	base: BasicBakedBlock,
+	
+	/// Replacements of the base geometry; the first matching one is used.
+	variants: Vec<ConnectedBakedPart>,
+	
+	/// Additional geometry; every matching part is added.
+	parts: Vec<ConnectedBakedPart>,
+}
+
+struct ConnectedBakedPart {
+	when: smallvec::SmallVec<[BakedCondition;4]>,
+	baked: BasicBakedBlock,
+}
+
+impl ConnectedBakedPart {
+	fn matches(&self, context: &BakeryContext, block: &BlockState) -> bool {
+		self.when.iter().all(|condition| condition.matches(context, block))
+	}
+}
+
+/// A `BlockModelCondition` with its neighbour and block resolved.
+struct BakedCondition {
+	index: usize,
+	matches: BakedMatch,
+	negate: bool,
+}
+
+enum BakedMatch {
+	Same,
+	Solid,
+	Block(BlockId),
+	
+	/// A block that does not exist.
+	Nothing,
+}
+
+impl BakedCondition {
+	#[inline]
+	fn matches(&self, context: &BakeryContext, block: &BlockState) -> bool {
+		let neighbour = &context.neighbours[self.index];
+		
+		let matches = match &self.matches {
+			BakedMatch::Same => neighbour.id == block.id,
+			BakedMatch::Solid => *neighbour != context.air,
+			BakedMatch::Block(id) => neighbour.id == *id,
+			BakedMatch::Nothing => false,
+		};
+		
+		matches != self.negate
+	}
+}
+
+impl BakedBlock for ConnectedBakedBlock {
+	fn build(
+		&self,
+		context: &BakeryContext,
+		block: &BlockState,
+		out: &mut dyn FnMut(&BakedBlockMeshFace)
+	) {
+		let variant = self.variants.iter()
+			.find(|variant| variant.matches(context, block))
+			.map(|variant| &variant.baked)
+			.unwrap_or(&self.base);
+		
+		variant.build(context, block, out);
+		
+		for part in self.parts.iter() {
+			if part.matches(context, block) {
+				part.baked.build(context, block, out);
+			}
+		}
+	}
+}
+
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 
 struct EmptyBakedBlock;
@@ -288,7 +672,27 @@ pub struct BakedBlockMeshFace {
 	pub a: BakedBlockMeshVertex,
 	pub b: BakedBlockMeshVertex,
 	pub c: BakedBlockMeshVertex,
-	pub d: BakedBlockMeshVertex
+	pub d: BakedBlockMeshVertex,
+	
+	/// The render pass this face is drawn in.
+	pub layer: BlockRenderLayer,
+	
+	/// The index passed to the tint provider of the block; untinted if `None`.
+	pub tint: Option<u8>,
+}
+
+impl BakedBlockMeshFace {
+	/// Returns the back-side of this face, with reversed winding and inverted normals.
+	pub fn flipped(&self) -> Self {
+		Self {
+			a: self.d.flipped(),
+			b: self.c.flipped(),
+			c: self.b.flipped(),
+			d: self.a.flipped(),
+			layer: self.layer,
+			tint: self.tint,
+		}
+	}
 }
 
 impl From<(BakedBlockMeshVertex, BakedBlockMeshVertex, BakedBlockMeshVertex, BakedBlockMeshVertex)> for BakedBlockMeshFace {
@@ -298,6 +702,8 @@ impl From<(BakedBlockMeshVertex, BakedBlockMeshVertex, BakedBlockMeshVertex, Bak
 			b: vertices.1,
 			c: vertices.2,
 			d: vertices.3,
+			layer: BlockRenderLayer::Opaque,
+			tint: None,
 		}
 	}
 }
@@ -317,12 +723,27 @@ pub struct BakedBlockMeshVertex {
 	pub nx: f32,
 	pub ny: f32,
 	pub nz: f32,
+	
+	/// The layer of the texture array to sample.
+	pub texture: u16,
 }
 
 impl BakedBlockMeshVertex {
 	pub fn new(x: f32, y: f32, z: f32, u: f32, v: f32, nx: f32, ny: f32, nz: f32) -> Self {
 		Self {
-			x, y, z, u, v, nx, ny, nz
+			x, y, z, u, v, nx, ny, nz,
+			texture: 0,
+		}
+	}
+}
+
+impl BakedBlockMeshVertex {
+	fn flipped(&self) -> Self {
+		Self {
+			nx: -self.nx,
+			ny: -self.ny,
+			nz: -self.nz,
+			.. *self
 		}
 	}
 }
@@ -333,46 +754,261 @@ impl From<(f32, f32, f32, f32, f32, f32, f32, f32)> for BakedBlockMeshVertex {
 	}
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-
-// TODO: Move this out of the bakery...
-struct BlockUv {
-	umin: f32,
-	umax: f32,
-	vmin: f32,
-	vmax: f32,
+#[cfg(test)]
+impl StaticBlockBakery {
+	/// Bakes the block models and textures embedded in the binary, for tests that need real geometry.
+	pub fn from_includes(blocks: &BlocksRef) -> StaticBlockBakery {
+		let mut includes: resources::Includes = vec![];
+		includes.extend(&BLOCK_MODEL_FILES);
+		includes.extend(&BLOCK_TEXTURE_FILES);
+		
+		let mut res = resources::new();
+		res.register_provider_by_type(resources::IncludeProvider::new(includes), resources::PRIORITY_INCLUDES);
+		
+		match Self::new(&res, blocks) {
+			Ok(bakery) => bakery,
+			Err(()) => panic!("Failed to bake the embedded block models."),
+		}
+	}
 }
 
-impl BlockUv {
-	fn new_from_pos(x: u8, y: u8) -> Self {
-		let x = (x as f32) / 16.0;
-		let y = (y as f32) / 16.0;
-		let s = 1.0 / 16.0;
-		Self {
-			umin: x,
-			umax: x+s,
-			vmin: y,
-			vmax: y+s,
-		}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	const CUBE: &str = "[[elements]]\nfaces = { east = { texture = \"a\" }, west = { texture = \"a\" }, up = { texture = \"a\" }, down = { texture = \"a\" }, south = { texture = \"a\" }, north = { texture = \"a\" } }";
+	const SLAB: &str = "[[elements]]\nto = [1, 0.5, 1]\nfaces = { east = { texture = \"a\" }, west = { texture = \"a\" }, up = { texture = \"a\", cull = false }, down = { texture = \"a\" }, south = { texture = \"a\" }, north = { texture = \"a\" } }";
+	
+	fn model(source: &str) -> BlockModel {
+		let file = parse_model(source).unwrap_or_else(|err| panic!("{}", err));
+		resolve_model("test", &[&file]).unwrap_or_else(|err| panic!("{}", err))
 	}
 	
-	fn unit() -> Self {
-		Self {
-			umin: 0.0, umax: 1.0,
-			vmin: 0.0, vmax: 1.0,
-		}
+	/// Bakes a model and renders it in the given context, returning the normals of the rendered faces in order.
+	fn render(model: &BlockModel, context: &BakeryContext) -> Vec<(f32, f32, f32)> {
+		let textures = BlockTextureArray::assemble(&[BlockTextureSprite::missingno()])
+			.unwrap_or_else(|err| panic!("{}", err));
+		
+		let baked = StaticBlockBakery::bake_basic(model, &textures);
+		let mut normals = vec![];
+		baked.build(context, &context.air, &mut |face| normals.push((face.a.nx, face.a.ny, face.a.nz)));
+		normals
 	}
 	
-	fn subset(&self, uv: &[f32; 4]) -> Self {
-		Self {
-			umin: Self::lerp(self.umin, self.umax, uv[0]),
-			vmin: Self::lerp(self.vmin, self.vmax, uv[1]),
-			umax: Self::lerp(self.umin, self.umax, uv[2]),
-			vmax: Self::lerp(self.vmin, self.vmax, uv[3]),
-		}
+	/// A context with the +X neighbour of the block being the given model.
+	fn context_with_east(neighbour: &BlockModel) -> BakeryContext {
+		let blocks = blocks::Blocks::new().to_ref();
+		let air = blocks.get_block_by_name_unchecked("air").get_default_state();
+		
+		let mut context = BakeryContext::new(air);
+		let occlusion = StaticBlockBakery::bake_occlusion(neighbour);
+		context.set_occlusion_masks(
+			occlusion[Face::NegativeX.uid()], FACE_MASK_EMPTY, FACE_MASK_EMPTY,
+			FACE_MASK_EMPTY, FACE_MASK_EMPTY, FACE_MASK_EMPTY,
+			FACE_MASK_EMPTY
+		);
+		context
+	}
+	
+	#[test]
+	fn face_masks() {
+		assert_eq!(face_mask([0.0, 0.0, 1.0, 1.0], true), FACE_MASK_FULL);
+		assert_eq!(face_mask([0.0, 0.0, 1.0, 1.0], false), FACE_MASK_FULL);
+		assert_eq!(face_mask([0.5, 0.5, 0.5, 0.5], false), FACE_MASK_EMPTY);
+		
+		// The lower half of the rows.
+		assert_eq!(face_mask([0.0, 0.0, 1.0, 0.5], true), 0x0000_0000_FFFF_FFFF);
+		
+		// Partially covered cells count for the area a face needs covered, but not for the area it covers.
+		assert_eq!(face_mask([0.0, 0.0, 1.0, 0.1], false), 0x0000_0000_0000_00FF);
+		assert_eq!(face_mask([0.0, 0.0, 1.0, 0.1], true), FACE_MASK_EMPTY);
+	}
+	
+	#[test]
+	fn occlusion_of_cube_and_slab() {
+		let cube = StaticBlockBakery::bake_occlusion(&model(CUBE));
+		assert!(cube[1..7].iter().all(|mask| *mask == FACE_MASK_FULL));
+		assert_eq!(cube[Face::EveryDir.uid()], FACE_MASK_EMPTY);
+		
+		// The slab covers the lower half of its sides, and nothing at its top.
+		let slab = StaticBlockBakery::bake_occlusion(&model(SLAB));
+		assert_eq!(slab[Face::PositiveX.uid()], face_mask([0.0, 0.0, 0.5, 1.0], true));
+		assert_eq!(slab[Face::PositiveZ.uid()], face_mask([0.0, 0.0, 1.0, 0.5], true));
+		assert_eq!(slab[Face::NegativeY.uid()], FACE_MASK_FULL);
+		assert_eq!(slab[Face::PositiveY.uid()], FACE_MASK_EMPTY);
+	}
+	
+	#[test]
+	fn cube_next_to_cube_is_culled() {
+		let cube = model(CUBE);
+		
+		let normals = render(&cube, &context_with_east(&cube));
+		assert_eq!(normals.len(), 5);
+		assert!(!normals.contains(&(1.0, 0.0, 0.0)));
+	}
+	
+	#[test]
+	fn cube_next_to_slab_keeps_partial_face() {
+		let cube = model(CUBE);
+		let slab = model(SLAB);
+		
+		// The slab only covers the lower half of the side of the cube.
+		let normals = render(&cube, &context_with_east(&slab));
+		assert_eq!(normals.len(), 6);
+		assert!(normals.contains(&(1.0, 0.0, 0.0)));
+		
+		// The side of the slab is covered by the cube, or by another slab.
+		let normals = render(&slab, &context_with_east(&cube));
+		assert_eq!(normals.len(), 5);
+		assert!(!normals.contains(&(1.0, 0.0, 0.0)));
+		
+		let normals = render(&slab, &context_with_east(&slab));
+		assert!(!normals.contains(&(1.0, 0.0, 0.0)));
+	}
+	
+	#[test]
+	fn inside_faces_are_flipped() {
+		let glass = model("[[elements]]\ninside = true\nfaces = { east = { texture = \"a\" }, up = { texture = \"a\", cull = false } }");
+		
+		let normals = render(&glass, &context_with_east(&model("")));
+		assert_eq!(normals, vec![
+			(1.0, 0.0, 0.0), (-1.0, 0.0, 0.0),
+			(0.0, 1.0, 0.0), (0.0, -1.0, 0.0),
+		]);
+		
+		// The back-faces are culled together with their fronts.
+		let normals = render(&glass, &context_with_east(&model(CUBE)));
+		assert_eq!(normals, vec![(0.0, 1.0, 0.0), (0.0, -1.0, 0.0)]);
+	}
+	
+	#[test]
+	fn inside_only_faces() {
+		let cage = model("[[elements]]\ninside = true\noutside = false\nfaces = { east = { texture = \"a\" } }");
+		
+		assert_eq!(render(&cage, &context_with_east(&model(""))), vec![(-1.0, 0.0, 0.0)]);
+		
+		// Without outside faces, the element hides nothing.
+		let occlusion = StaticBlockBakery::bake_occlusion(&cage);
+		assert!(occlusion.iter().all(|mask| *mask == FACE_MASK_EMPTY));
+	}
+	
+	fn condition(offset: (i8, i8, i8), matches: BlockModelMatch, negate: bool) -> BlockModelCondition {
+		BlockModelCondition { offset, matches, negate }
+	}
+	
+	/// A model of a single, never culled face on the given side.
+	fn face(side: &str) -> BlockModel {
+		model(&format!("[[elements]]\nfaces.{} = {{ texture = \"a\", cull = false }}", side))
 	}
 	
-	fn lerp(v0: f32, v1: f32, x: f32) -> f32 {
-		(1.0 - x) * v0 + x * v1
+	#[test]
+	fn connected_variants_and_parts() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let air = blocks.get_block_by_name_unchecked("air").get_default_state();
+		let adm = blocks.get_block_by_name_unchecked("adm");
+		let fence = blocks.get_block_by_name_unchecked("adm_fence").get_default_state();
+		
+		// The base is the top face; the first matching variant replaces it, and every matching part adds a face.
+		let mut connected = face("up");
+		connected.variants = vec![
+			BlockModelPart { when: smallvec![condition((0, 0, 1), BlockModelMatch::Block("unknown".to_string()), false)], model: face("south") },
+			BlockModelPart { when: smallvec![condition((1, 0, 0), BlockModelMatch::Same, false)], model: face("east") },
+			BlockModelPart { when: smallvec![condition((1, 0, 0), BlockModelMatch::Solid, false)], model: face("west") },
+		];
+		connected.parts = vec![
+			BlockModelPart { when: smallvec![condition((0, 0, 1), BlockModelMatch::Block("unknown".to_string()), true)], model: face("down") },
+			BlockModelPart {
+				when: smallvec![
+					condition((-1, 1, 0), BlockModelMatch::Block("adm_fence".to_string()), false),
+					condition((0, -1, 0), BlockModelMatch::Solid, true),
+				],
+				model: face("north"),
+			},
+		];
+		
+		let textures = BlockTextureArray::assemble(&[BlockTextureSprite::missingno()])
+			.unwrap_or_else(|err| panic!("{}", err));
+		let baked = StaticBlockBakery::bake_model(&blocks, adm.as_ref(), &connected, &textures);
+		
+		let block = adm.get_default_state();
+		let render = |context: &BakeryContext| {
+			let mut normals = vec![];
+			baked.build(context, &block, &mut |face| normals.push((face.a.nx, face.a.ny, face.a.nz)));
+			normals
+		};
+		
+		let up = (0.0, 1.0, 0.0);
+		let down = (0.0, -1.0, 0.0);
+		let east = (1.0, 0.0, 0.0);
+		let west = (-1.0, 0.0, 0.0);
+		let north = (0.0, 0.0, -1.0);
+		
+		// Conditions on unknown blocks never match, so the negated part is always added.
+		let mut context = BakeryContext::new(air);
+		assert_eq!(render(&context), vec![up, down]);
+		
+		// The same block to the east selects the first variant that matches, not the solid one after it.
+		context.set_neighbour(1, 0, 0, block);
+		assert_eq!(render(&context), vec![east, down]);
+		
+		context.set_neighbour(1, 0, 0, fence);
+		assert_eq!(render(&context), vec![west, down]);
+		
+		// All conditions of a part must hold.
+		context.set_neighbour(-1, 1, 0, fence);
+		assert_eq!(render(&context), vec![west, down, north]);
+		
+		context.set_neighbour(0, -1, 0, block);
+		assert_eq!(render(&context), vec![west, down]);
+	}
+	
+	#[test]
+	fn fence_connects_to_solid_neighbours() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let bakery = StaticBlockBakery::from_includes(&blocks);
+		
+		let air = blocks.get_block_by_name_unchecked("air").get_default_state();
+		let adm = blocks.get_block_by_name_unchecked("adm").get_default_state();
+		let fence = blocks.get_block_by_name_unchecked("adm_fence").get_default_state();
+		
+		let count = |context: &BakeryContext| {
+			let mut faces = 0;
+			bakery.render_block(context, &fence, &mut |_| faces += 1);
+			faces
+		};
+		
+		// The post alone, since blocks above do not connect; each bar adds four faces.
+		let mut context = BakeryContext::new(air);
+		context.set_neighbour(0, 1, 0, adm);
+		assert_eq!(count(&context), 6);
+		
+		context.set_neighbour(1, 0, 0, adm);
+		assert_eq!(count(&context), 10);
+		
+		context.set_neighbour(0, 0, -1, fence);
+		assert_eq!(count(&context), 14);
+		
+		// Diagonal neighbours do not connect.
+		context.set_neighbour(-1, 0, 1, adm);
+		assert_eq!(count(&context), 14);
+		
+		assert!(!bakery.is_opaque(&fence));
+	}
+	
+	#[test]
+	fn embedded_blocks_have_textures() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let bakery = StaticBlockBakery::from_includes(&blocks);
+		
+		let models = {
+			let mut res = resources::new();
+			res.register_provider_by_type(resources::IncludeProvider::new(BLOCK_MODEL_FILES.to_vec()), resources::PRIORITY_INCLUDES);
+			StaticBlockBakery::load_models(&res, &blocks)
+		};
+		
+		assert!(!models.textures.is_empty());
+		for texture in models.textures.iter() {
+			assert_ne!(bakery.get_textures().get_layer(texture), 0, "texture '{}' is missing", texture);
+		}
 	}
 }
@@ -1,71 +1,53 @@
 use super::resources::ResourceLocation;
-use super::resources::ResourceProvider;
-use super::resources::ResourceError;
 use super::resources::Resources;
 use crate::render::*;
+use super::BlockTextureArray;
+use super::insert_chunk_vertex_glsl;
 
 pub const BLOCKS_MATERIAL_FILES: [(&str, &[u8]); 3] = [
+	("core/materials/blocks.toml", include_bytes!("blocks.toml")),
 	("core/shaders/blocks.vert", include_bytes!("blocks.vert")),
 	("core/shaders/blocks.frag", include_bytes!("blocks.frag")),
-	("core/textures/blocks.png", include_bytes!("blocks.png")),
 ];
 
 pub struct BlocksMaterial {
-	pub shader: ProgramObject,
-	pub atlas: TextureObject,
+	pub shader: Material,
+	pub textures: TextureObject,
 	pub uniform_matrix: UniformLocation,
-	pub uniform_atlas: UniformLocation,
 	pub uniform_sun: UniformLocation,
+	pub uniform_cutoff: UniformLocation,
 }
 
 impl BlocksMaterial {
-	pub fn new(gl: &gl::Gl, res: &Resources) -> Result<Self, BlocksMaterialError> {
-		debug!("Loading blocks texture...");
+	pub fn new(gl: &gl::Gl, res: &Resources, block_textures: &BlockTextureArray) -> Result<Self, MaterialError> {
+		debug!("Uploading blocks texture array...");
 		
-		let atlas_loc = ResourceLocation::from_str("core/textures/blocks.png");
-		let atlas = TextureObjectBuilder::new()
-			.wrapping(gl::CLAMP_TO_EDGE)
+		// Every texture has a layer of its own, so it can be repeated across merged faces.
+		let textures = TextureObjectBuilder::new()
+			.name("Blocks Textures".to_string())
+			.wrapping(gl::REPEAT)
 			.anisotropy(true)
 			.filter(gl::NEAREST_MIPMAP_LINEAR, gl::NEAREST)
-			.build_from_res(gl, &res, &atlas_loc)
-			.map_err(|err| BlocksMaterialError::Texture(err))?;
-		
-		
-		debug!("Loading blocks shader...");
-		
-		let shader_vert = ResourceLocation::from("core/shaders/blocks.vert");
-		let shader_frag = ResourceLocation::from("core/shaders/blocks.frag");
-		
-		let shader_vert = res.res_as_cstring(&shader_vert)
-			.map_err(|err| BlocksMaterialError::Resource(err))?;
-		
-		let shader_frag = res.res_as_cstring(&shader_frag)
-			.map_err(|err| BlocksMaterialError::Resource(err))?;
-		
-		let shader_vert = ShaderObject::new_vertex_shader(gl, &shader_vert)
-			.map_err(|err| BlocksMaterialError::Shader(err))?;
-		
-		let shader_frag = ShaderObject::new_fragment_shader(gl, &shader_frag)
-			.map_err(|err| BlocksMaterialError::Shader(err))?;
-		
-		let shader = ProgramObject::new(gl, "Blocks", &smallvec![shader_vert, shader_frag])
-			.map_err(|err| BlocksMaterialError::Shader(err))?;
-		
-		// TODO: Fix error handling
-		let uniform_matrix = shader.get_uniform_location("transform").unwrap();
-		let uniform_atlas = shader.get_uniform_location("atlas").unwrap();
-		let uniform_sun = shader.get_uniform_location("sun").unwrap();
-		
-		Ok(Self {shader, atlas,
-			uniform_matrix,
-			uniform_atlas,
-			uniform_sun,
+			.build_array_from_rgba(gl, block_textures.size, block_textures.size, &block_textures.layers);
+		
+		
+		debug!("Loading blocks material...");
+		
+		// The unpacking of the vertex format is generated from its description.
+		let shader = Material::load_with(gl, res, &ResourceLocation::parse("core:materials/blocks.toml")?, &mut |kind, source| {
+			if kind == gl::VERTEX_SHADER {
+				insert_chunk_vertex_glsl(&source)
+			} else {
+				source
+			}
+		})?;
+		
+		Ok(Self {
+			uniform_matrix: shader.uniform("transform"),
+			uniform_sun: shader.uniform("sun"),
+			uniform_cutoff: shader.uniform("cutoff"),
+			shader,
+			textures,
 		})
 	}
 }
-
-pub enum BlocksMaterialError {
-	Resource(ResourceError),
-	Texture(TextureError),
-	Shader(String),
-}
\ No newline at end of file
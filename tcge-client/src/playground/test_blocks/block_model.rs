@@ -3,12 +3,40 @@ use smallvec::SmallVec;
 
 #[derive(Clone, Debug)]
 pub struct BlockModel {
-	pub parent: Option<String>, // TODO: Let models inherit from each other...
-	// pub inherit_textures: bool,
-	// pub inherit_elements: bool,
+	/// The name of the model this one inherited from, if any.
+	pub parent: Option<String>,
 	
+	/// The fully resolved texture names, indexed by the faces of the elements.
 	pub textures: SmallVec<[String; 1]>,
 	pub elements: SmallVec<[BlockModelElement; 1]>,
+	
+	/// The render pass the faces of this model are drawn in.
+	pub layer: BlockRenderLayer,
+	
+	/// The name of the tint provider that colours the tinted faces, if any.
+	pub tint: Option<String>,
+	
+	/// Alternative models, of which the first one whose conditions match is used instead of this one.
+	pub variants: Vec<BlockModelPart>,
+	
+	/// Additional models, each of which is added if its conditions match.
+	pub parts: Vec<BlockModelPart>,
+}
+
+impl BlockModel {
+	/// Returns `true` if the model depends on the neighbours of the block.
+	pub fn is_connected(&self) -> bool {
+		!self.variants.is_empty() || !self.parts.is_empty()
+	}
+	
+	/// Collects the names of all textures used by this model, its variants and its parts.
+	pub fn collect_textures(&self, out: &mut Vec<String>) {
+		out.extend(self.textures.iter().cloned());
+		
+		for part in self.variants.iter().chain(self.parts.iter()) {
+			part.model.collect_textures(out);
+		}
+	}
 }
 
 impl Default for BlockModel {
@@ -17,66 +45,268 @@ impl Default for BlockModel {
 		Self {
 			parent: None,
 			textures: smallvec!("missingno".to_string()),
-			elements: smallvec!(BlockModelElement::default())
+			elements: smallvec!(BlockModelElement::default()),
+			layer: BlockRenderLayer::Opaque,
+			tint: None,
+			variants: vec![],
+			parts: vec![],
 		}
 	}
 }
 
+/// The number of render layers.
+pub const BLOCK_RENDER_LAYERS: usize = 3;
+
+/// The render passes blocks are drawn in, in order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockRenderLayer {
+	/// Fully opaque faces; drawn first, in any order.
+	Opaque = 0,
+	
+	/// Faces with fully transparent holes, whose pixels are discarded below an alpha threshold.
+	Cutout = 1,
+	
+	/// Partially transparent faces; blended over everything else, sorted far to near.
+	Translucent = 2,
+}
+
+impl BlockRenderLayer {
+	pub const ALL: [BlockRenderLayer; BLOCK_RENDER_LAYERS] = [
+		BlockRenderLayer::Opaque,
+		BlockRenderLayer::Cutout,
+		BlockRenderLayer::Translucent,
+	];
+	
+	#[inline]
+	pub fn index(&self) -> usize {
+		*self as usize
+	}
+}
+
+/// A model that is only used if all of its conditions on the neighbouring blocks hold.
+#[derive(Clone, Debug)]
+pub struct BlockModelPart {
+	pub when: SmallVec<[BlockModelCondition; 4]>,
+	pub model: BlockModel,
+}
+
+/// A condition on one of the 26 neighbours of a block.
+#[derive(Clone, Debug)]
+pub struct BlockModelCondition {
+	/// The position of the neighbour, relative to the block; each component is in `-1..=1`.
+	pub offset: (i8, i8, i8),
+	
+	/// What the neighbour must be.
+	pub matches: BlockModelMatch,
+	
+	/// Should the condition be inverted?
+	pub negate: bool,
+}
+
+/// The kinds of blocks a `BlockModelCondition` can test for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockModelMatch {
+	/// The same type of block as the block itself.
+	Same,
+	
+	/// Any block except air.
+	Solid,
+	
+	/// The block with the given name.
+	Block(String),
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct BlockModelElement {
 	pub bounds: [f32; 6], // min/max (xyz)
 	pub faces: [BlockModelElementFace; 6],
 	
-	// TODO: Implement this here and in the bakery.
-	// pub tform: BlockModelTransform,
+	/// An optional rotation of the element.
+	pub tform: Option<BlockModelTransform>,
 	
 	/// Should geometry for the inside of this element be generated?
-	pub inside: bool, // TODO: Make use of this flag.
+	pub inside: bool,
 	
 	/// Should geometry for the outside of this element be generated?
-	pub outside: bool, // TODO: Make use of this flag.
+	pub outside: bool,
 }
 
 impl Default for BlockModelElement {
 	fn default() -> Self {
-		let null_face = BlockModelElementFace {
-			uv: [0.0, 0.0, 1.0, 1.0],
-			texture: Some(0),
-			side: Face::EveryDir,
-			cull: true,
-		};
-		
 		Self {
 			bounds: [0.0, 0.0, 0.0, 1.0, 1.0, 1.0],
 			faces: [
-				null_face.with_side(Face::PositiveX),
-				null_face.with_side(Face::NegativeX),
-				null_face.with_side(Face::PositiveY),
-				null_face.with_side(Face::NegativeY),
-				null_face.with_side(Face::PositiveZ),
-				null_face.with_side(Face::NegativeZ),
+				BlockModelElementFace::full(Face::PositiveX),
+				BlockModelElementFace::full(Face::NegativeX),
+				BlockModelElementFace::full(Face::PositiveY),
+				BlockModelElementFace::full(Face::NegativeY),
+				BlockModelElementFace::full(Face::PositiveZ),
+				BlockModelElementFace::full(Face::NegativeZ),
 			],
+			tform: None,
 			inside: false,
 			outside: true,
 		}
 	}
 }
 
+/// An axis of rotation for model elements.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockModelAxis {
+	X, Y, Z
+}
+
+/// The rotation of a model element around a pivot, on a single axis.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockModelTransform {
+	/// The pivot point of the rotation.
+	pub origin: [f32; 3],
+	
+	/// The axis to rotate around.
+	pub axis: BlockModelAxis,
+	
+	/// The angle of the rotation in degrees, counter-clockwise when looking down the axis; a multiple of 22.5 within `-45..=45`.
+	pub angle: f32,
+	
+	/// Should the element be scaled up across the other two axes, so that a rotated full face still spans the block?
+	pub rescale: bool,
+}
+
+impl BlockModelTransform {
+	/// The step in degrees that the angle of a rotation must be a multiple of.
+	pub const ANGLE_STEP: f32 = 22.5;
+	
+	/// The largest angle in degrees (in either direction) of a rotation; beyond it, rescaling would blow the element up.
+	pub const ANGLE_MAX: f32 = 45.0;
+	
+	/// Applies the transform to a point.
+	pub fn apply_point(&self, point: [f32; 3]) -> [f32; 3] {
+		let o = self.origin;
+		let p = [point[0] - o[0], point[1] - o[1], point[2] - o[2]];
+		let p = self.rotate(p);
+		
+		let p = if self.rescale {
+			let scale = 1.0 / self.angle.to_radians().cos().abs();
+			match self.axis {
+				BlockModelAxis::X => [p[0], p[1] * scale, p[2] * scale],
+				BlockModelAxis::Y => [p[0] * scale, p[1], p[2] * scale],
+				BlockModelAxis::Z => [p[0] * scale, p[1] * scale, p[2]],
+			}
+		} else {
+			p
+		};
+		
+		[p[0] + o[0], p[1] + o[1], p[2] + o[2]]
+	}
+	
+	/// Applies the rotation (but not the rescaling) to a direction.
+	pub fn apply_normal(&self, normal: [f32; 3]) -> [f32; 3] {
+		self.rotate(normal)
+	}
+	
+	fn rotate(&self, p: [f32; 3]) -> [f32; 3] {
+		let (sin, cos) = self.angle.to_radians().sin_cos();
+		match self.axis {
+			BlockModelAxis::X => [p[0], p[1] * cos - p[2] * sin, p[1] * sin + p[2] * cos],
+			BlockModelAxis::Y => [p[0] * cos + p[2] * sin, p[1], p[2] * cos - p[0] * sin],
+			BlockModelAxis::Z => [p[0] * cos - p[1] * sin, p[0] * sin + p[1] * cos, p[2]],
+		}
+	}
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct BlockModelElementFace {
 	pub uv: [f32; 4],
-	// pub uv_spin: u8 // TODO: Implement UV rotation...
+	
+	/// The rotation of the texture on the face, in quarter turns (0 to 3).
+	pub uv_spin: u8,
 	pub texture: Option<u8>,
 	
 	pub side: Face,
-	pub cull: bool,
-	// tint: ...?
+	
+	/// The side of the block which, if occluded, causes this face to be culled.
+	pub cull: Option<Face>,
+	
+	/// The index passed to the tint provider of the model; untinted if `None`.
+	pub tint: Option<u8>,
 }
 
 impl BlockModelElementFace {
-	pub fn with_side(self, side: Face) -> Self {
-		let mut new = self.clone();
-		new.side = side;
-		new
+	/// Creates a face covering the whole given side, using the first texture and culled by that side.
+	pub fn full(side: Face) -> Self {
+		Self {
+			uv: [0.0, 0.0, 1.0, 1.0],
+			uv_spin: 0,
+			texture: Some(0),
+			side,
+			cull: Some(side),
+			tint: None,
+		}
+	}
+	
+	/// Creates a face without a texture, i.e. one that is not rendered.
+	pub fn none(side: Face) -> Self {
+		Self {
+			uv: [0.0, 0.0, 1.0, 1.0],
+			uv_spin: 0,
+			texture: None,
+			side,
+			cull: None,
+			tint: None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn transform(axis: BlockModelAxis, angle: f32, rescale: bool) -> BlockModelTransform {
+		BlockModelTransform {
+			origin: [0.5, 0.5, 0.5],
+			axis,
+			angle,
+			rescale,
+		}
+	}
+	
+	fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+		for (a, e) in actual.iter().zip(expected.iter()) {
+			assert!((a - e).abs() < 1e-5, "{:?} is not {:?}", actual, expected);
+		}
+	}
+	
+	#[test]
+	fn rotates_around_origin() {
+		// Rotating by 45° around Y moves +X towards -Z.
+		let half = std::f32::consts::FRAC_1_SQRT_2 / 2.0;
+		assert_near(transform(BlockModelAxis::Y, 45.0, false).apply_point([1.0, 0.0, 0.5]), [0.5 + half, 0.0, 0.5 - half]);
+		assert_near(transform(BlockModelAxis::Z, 45.0, false).apply_point([1.0, 0.5, 0.0]), [0.5 + half, 0.5 + half, 0.0]);
+		assert_near(transform(BlockModelAxis::X, -45.0, false).apply_point([0.0, 1.0, 0.5]), [0.0, 0.5 + half, 0.5 - half]);
+		
+		// The origin itself does not move.
+		assert_near(transform(BlockModelAxis::Y, 22.5, false).apply_point([0.5, 0.5, 0.5]), [0.5, 0.5, 0.5]);
+		
+		// Normals are rotated, but not moved or rescaled.
+		assert_near(transform(BlockModelAxis::Y, 45.0, true).apply_normal([1.0, 0.0, 0.0]), [half * 2.0, 0.0, -half * 2.0]);
+	}
+	
+	#[test]
+	fn rescale_keeps_spanning_the_block() {
+		// Rotated by 45° and rescaled, the corner of the block lies on the axis through the middle of the side.
+		assert_near(transform(BlockModelAxis::Y, 45.0, true).apply_point([1.0, 0.25, 1.0]), [1.5, 0.25, 0.5]);
+		
+		// The other axes are scaled by 1 / cos(angle); the rotation axis is not.
+		let scale = 1.0 / 22.5f32.to_radians().cos();
+		let rotated = transform(BlockModelAxis::Z, 22.5, false).apply_point([1.0, 0.0, 0.25]);
+		let rescaled = transform(BlockModelAxis::Z, 22.5, true).apply_point([1.0, 0.0, 0.25]);
+		assert_near(rescaled, [
+			0.5 + (rotated[0] - 0.5) * scale,
+			0.5 + (rotated[1] - 0.5) * scale,
+			0.25,
+		]);
+		
+		// Without rotation, nothing is scaled.
+		assert_near(transform(BlockModelAxis::X, 0.0, true).apply_point([1.0, 0.0, 0.25]), [1.0, 0.0, 0.25]);
 	}
 }
@@ -0,0 +1,931 @@
+//! Loading and resolving of block models from resource files.
+//!
+//! A block model is a TOML file located at `core:models/<name>.toml`, where the name of the model
+//! for a block is `block/<block-name>`. A model file may contain:
+//!
+//! - `parent`: The name of a model to inherit from.
+//! - `[textures]`: Texture variables; each value is either a texture name or a `#variable` reference.
+//! - `[[elements]]`: The boxes making up the model, each with `from`, `to`, `inside`, `outside` and `faces`.
+//!
+//! The faces of an element are named `east` (+X), `west` (-X), `up` (+Y), `down` (-Y), `south` (+Z) and `north` (-Z).
+//! Each face has a `texture` (usually a `#variable`), an optional `uv` rectangle, an optional `rotation`
+//! of the texture in multiples of 90 degrees, an optional `cull` side, which is either a face name
+//! or a boolean (`true` meaning the side of the face itself), and an optional `tint` index.
+//!
+//! An element may be rotated with a `rotation` table, holding the pivot `origin`, the `axis` (`x`, `y` or `z`),
+//! the `angle` in multiples of 22.5 degrees from -45 to 45, and whether to `rescale` the element to keep spanning the block.
+//!
+//! - `layer`: The render pass of the model; one of `opaque` (the default), `cutout` or `translucent`.
+//! - `tint`: The name of the tint provider that colours the faces with a `tint` index (e.g. `grass`).
+//!
+//! Children override the texture variables of their parents one by one, and replace their elements as a whole.
+//!
+//! Models can react to the 26 blocks surrounding them, through two lists of conditional models:
+//!
+//! - `[[variants]]`: The first variant whose conditions hold is rendered instead of the model itself.
+//! - `[[parts]]`: Every part whose conditions hold is rendered in addition to the model (or its variant).
+//!
+//! Each entry has a `when` table of conditions, all of which must hold, and is otherwise an anonymous model:
+//! It may override `textures`, the `layer` and the `tint`, define `elements` and name a `model` to inherit from. Without a `model`, it
+//! inherits from the model containing it. Like elements, the variants and parts of the closest model that
+//! defines any are used, and they cannot be nested.
+//!
+//! The keys of `when` name a neighbour by joining up to three face names with `_` (e.g. `east` or `up_north`).
+//! The values are `same` (the same type of block), `other` (any other), `solid` (anything but air), `air`,
+//! or the name of a block. A value starting with `!` inverts the condition.
+
+use super::*;
+use super::resources::ResourceLocation;
+use super::resources::ResourceLocationError;
+use super::resources::ResourceProvider;
+use super::resources::ResourceError;
+use super::resources::Resources;
+use blocks::Face;
+use smallvec::SmallVec;
+
+pub const BLOCK_MODEL_FILES: [(&str, &[u8]); 7] = [
+	("core/models/block/cube.toml", include_bytes!("models/block/cube.toml")),
+	("core/models/block/cube_all.toml", include_bytes!("models/block/cube_all.toml")),
+	("core/models/block/cross.toml", include_bytes!("models/block/cross.toml")),
+	("core/models/block/fence.toml", include_bytes!("models/block/fence.toml")),
+	("core/models/block/adm.toml", include_bytes!("models/block/adm.toml")),
+	("core/models/block/adm_fence.toml", include_bytes!("models/block/adm_fence.toml")),
+	("core/models/block/adm_cross.toml", include_bytes!("models/block/adm_cross.toml")),
+];
+
+/// Errors that may occur when loading or resolving a block model.
+pub enum BlockModelError {
+	/// The file of a model could not be read.
+	Resource(String, ResourceError),
+	
+	/// The file of a model is not a valid model definition.
+	Parse(String, String),
+	
+	/// The parent of a model does not exist.
+	MissingParent(String, String),
+	
+	/// The parents of a model form a cycle.
+	Cycle(Vec<String>),
+	
+	/// A texture variable is used but never defined, or refers back to itself.
+	MissingTexture(String, String),
+}
+
+impl std::fmt::Display for BlockModelError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			BlockModelError::Resource(model, ResourceError::NotFound) => write!(f, "Could not find block model '{}'", model),
+			BlockModelError::Resource(model, ResourceError::Io(err)) => write!(f, "Could not read block model '{}': {}", model, err),
+			BlockModelError::Resource(model, _) => write!(f, "Could not read block model '{}'", model),
+			BlockModelError::Parse(model, error) => write!(f, "Could not parse block model '{}': {}", model, error),
+			BlockModelError::MissingParent(model, parent) => write!(f, "Block model '{}' has a missing parent '{}'", model, parent),
+			BlockModelError::Cycle(chain) => write!(f, "Block model parents form a cycle: {}", chain.join(" -> ")),
+			BlockModelError::MissingTexture(model, variable) => write!(f, "Block model '{}' uses an unresolvable texture variable '#{}'", model, variable),
+		}
+	}
+}
+
+/// A block model as written in its file, before inheritance is applied.
+#[derive(Clone, Debug)]
+pub struct BlockModelFile {
+	pub parent: Option<String>,
+	pub textures: FxHashMap<String, String>,
+	pub elements: Option<Vec<BlockModelFileElement>>,
+	pub layer: Option<BlockRenderLayer>,
+	pub tint: Option<String>,
+	pub variants: Option<Vec<BlockModelFilePart>>,
+	pub parts: Option<Vec<BlockModelFilePart>>,
+}
+
+/// A conditional model within a model file; its `file` has the named `model` as its parent, if any.
+#[derive(Clone, Debug)]
+pub struct BlockModelFilePart {
+	pub when: SmallVec<[BlockModelCondition; 4]>,
+	pub file: BlockModelFile,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockModelFileElement {
+	pub bounds: [f32; 6],
+	pub faces: [Option<BlockModelFileFace>; 6],
+	pub tform: Option<BlockModelTransform>,
+	pub inside: bool,
+	pub outside: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockModelFileFace {
+	pub uv: [f32; 4],
+	pub uv_spin: u8,
+	pub texture: String,
+	pub cull: Option<Face>,
+	pub tint: Option<u8>,
+}
+
+/// Loads block model files from resources, caching them, and resolves them into `BlockModel`s.
+pub struct BlockModelLoader {
+	files: FxHashMap<String, BlockModelFile>,
+}
+
+impl BlockModelLoader {
+	pub fn new() -> Self {
+		Self {
+			files: FxHashMap::default(),
+		}
+	}
+	
+	/// Returns the resource location of the model with the given name.
+	pub fn get_location(name: &str) -> Result<ResourceLocation, ResourceLocationError> {
+		ResourceLocation::parse(&format!("core:models/{}.toml", name))
+	}
+	
+	/// Loads the file of the given model, unless it is already cached.
+	fn load_file(&mut self, res: &Resources, name: &str) -> Result<&BlockModelFile, BlockModelError> {
+		if !self.files.contains_key(name) {
+			let location = Self::get_location(name)
+				.map_err(|err| BlockModelError::Parse(name.to_string(), err.to_string()))?;
+			
+			let source = res.res_as_string(&location)
+				.map_err(|err| BlockModelError::Resource(name.to_string(), err))?;
+			
+			let file = parse_model(&source)
+				.map_err(|err| BlockModelError::Parse(name.to_string(), err))?;
+			
+			self.files.insert(name.to_string(), file);
+		}
+		
+		Ok(&self.files[name])
+	}
+	
+	/// Loads the given model and all of its parents, and resolves them into a single model.
+	pub fn resolve(&mut self, res: &Resources, name: &str) -> Result<BlockModel, BlockModelError> {
+		let chain = self.load_chain(res, name)?;
+		
+		// --- Variants and parts: The closest model that defines any wins.
+		let variants = chain.iter()
+			.filter_map(|model| self.files[model].variants.clone())
+			.next()
+			.unwrap_or_default();
+		
+		let parts = chain.iter()
+			.filter_map(|model| self.files[model].parts.clone())
+			.next()
+			.unwrap_or_default();
+		
+		// --- Load the chains of the models the variants and parts inherit from.
+		let mut variant_chains = Vec::with_capacity(variants.len());
+		for part in variants.iter() {
+			variant_chains.push(self.load_part_chain(res, part, &chain)?);
+		}
+		
+		let mut part_chains = Vec::with_capacity(parts.len());
+		for part in parts.iter() {
+			part_chains.push(self.load_part_chain(res, part, &chain)?);
+		}
+		
+		// --- Resolve everything.
+		let mut model = resolve_model(name, &self.get_files(&chain))?;
+		
+		for (part, part_chain) in variants.iter().zip(variant_chains.iter()) {
+			model.variants.push(self.resolve_part(name, part, part_chain)?);
+		}
+		
+		for (part, part_chain) in parts.iter().zip(part_chains.iter()) {
+			model.parts.push(self.resolve_part(name, part, part_chain)?);
+		}
+		
+		Ok(model)
+	}
+	
+	/// Returns the chain of models a variant or part inherits from.
+	fn load_part_chain(&mut self, res: &Resources, part: &BlockModelFilePart, chain: &[String]) -> Result<Vec<String>, BlockModelError> {
+		match &part.file.parent {
+			Some(parent) => self.load_chain(res, parent),
+			None => Ok(chain.to_vec()),
+		}
+	}
+	
+	/// Resolves a variant or part as an anonymous child of the given chain.
+	fn resolve_part(&self, name: &str, part: &BlockModelFilePart, chain: &[String]) -> Result<BlockModelPart, BlockModelError> {
+		let mut files = vec![&part.file];
+		files.extend(self.get_files(chain));
+		
+		Ok(BlockModelPart {
+			when: part.when.clone(),
+			model: resolve_model(name, &files)?,
+		})
+	}
+	
+	fn get_files(&self, chain: &[String]) -> Vec<&BlockModelFile> {
+		chain.iter()
+			.map(|model| &self.files[model])
+			.collect()
+	}
+	
+	/// Loads the given model and all of its parents, returning their names child first.
+	fn load_chain(&mut self, res: &Resources, name: &str) -> Result<Vec<String>, BlockModelError> {
+		let mut chain: Vec<String> = vec![];
+		let mut current = Some(name.to_string());
+		
+		while let Some(model) = current {
+			if chain.contains(&model) {
+				chain.push(model);
+				return Err(BlockModelError::Cycle(chain));
+			}
+			
+			let file = match self.load_file(res, &model) {
+				Ok(file) => file,
+				Err(BlockModelError::Resource(_, _)) if !chain.is_empty() => {
+					return Err(BlockModelError::MissingParent(chain.last().unwrap().clone(), model));
+				},
+				Err(err) => return Err(err),
+			};
+			
+			current = file.parent.clone();
+			chain.push(model);
+		}
+		
+		Ok(chain)
+	}
+}
+
+/// Resolves a chain of model files (child first) into a single model.
+///
+/// The variants and parts of the files are ignored; they are resolved by the `BlockModelLoader`.
+pub fn resolve_model(name: &str, chain: &[&BlockModelFile]) -> Result<BlockModel, BlockModelError> {
+	// --- Texture variables: Children override their parents.
+	let mut variables: FxHashMap<&str, &str> = FxHashMap::default();
+	for file in chain.iter().rev() {
+		for (key, value) in file.textures.iter() {
+			variables.insert(key, value);
+		}
+	}
+	
+	// --- Elements: The closest model that defines any wins.
+	let elements = chain.iter()
+		.filter_map(|file| file.elements.as_ref())
+		.next()
+		.map(|elements| elements.as_slice())
+		.unwrap_or(&[]);
+	
+	// --- Render layer: The closest model that defines one wins.
+	let layer = chain.iter()
+		.filter_map(|file| file.layer)
+		.next()
+		.unwrap_or(BlockRenderLayer::Opaque);
+	
+	// --- Tint provider: The closest model that names one wins.
+	let tint = chain.iter()
+		.filter_map(|file| file.tint.clone())
+		.next();
+	
+	let mut textures: SmallVec<[String; 1]> = smallvec![];
+	let mut model_elements: SmallVec<[BlockModelElement; 1]> = smallvec![];
+	
+	for element in elements.iter() {
+		let mut faces = [
+			BlockModelElementFace::none(Face::PositiveX),
+			BlockModelElementFace::none(Face::NegativeX),
+			BlockModelElementFace::none(Face::PositiveY),
+			BlockModelElementFace::none(Face::NegativeY),
+			BlockModelElementFace::none(Face::PositiveZ),
+			BlockModelElementFace::none(Face::NegativeZ),
+		];
+		
+		for (face, file_face) in faces.iter_mut().zip(element.faces.iter()) {
+			let file_face = match file_face {
+				Some(file_face) => file_face,
+				None => continue,
+			};
+			
+			let texture = resolve_texture(name, &variables, &file_face.texture)?;
+			
+			let index = match textures.iter().position(|t| t == texture) {
+				Some(index) => index,
+				None => {
+					textures.push(texture.to_string());
+					textures.len() - 1
+				}
+			};
+			
+			face.uv = file_face.uv;
+			face.uv_spin = file_face.uv_spin;
+			face.texture = Some(index as u8);
+			face.cull = file_face.cull;
+			face.tint = file_face.tint;
+		}
+		
+		model_elements.push(BlockModelElement {
+			bounds: element.bounds,
+			faces,
+			tform: element.tform,
+			inside: element.inside,
+			outside: element.outside,
+		});
+	}
+	
+	Ok(BlockModel {
+		parent: chain.first().and_then(|file| file.parent.clone()),
+		textures,
+		elements: model_elements,
+		layer,
+		tint,
+		variants: vec![],
+		parts: vec![],
+	})
+}
+
+/// Follows texture variable references until a texture name is found.
+fn resolve_texture<'a>(name: &str, variables: &FxHashMap<&str, &'a str>, texture: &'a str) -> Result<&'a str, BlockModelError> {
+	let mut texture = texture;
+	
+	// Every step must hit a different variable, so anything longer than this is a cycle.
+	for _ in 0..=variables.len() {
+		if !texture.starts_with('#') {
+			return Ok(texture);
+		}
+		
+		texture = match variables.get(&texture[1..]) {
+			Some(value) => *value,
+			None => return Err(BlockModelError::MissingTexture(name.to_string(), texture[1..].to_string())),
+		};
+	}
+	
+	Err(BlockModelError::MissingTexture(name.to_string(), texture[1..].to_string()))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// Parses the source of a model file.
+pub fn parse_model(source: &str) -> Result<BlockModelFile, String> {
+	let root = match toml::from_str(source) {
+		Ok(toml::Value::Table(root)) => root,
+		Ok(_) => return Err("Root is not a table".to_string()),
+		Err(e) => return Err(e.to_string()),
+	};
+	
+	let parent = match root.get("parent") {
+		Some(toml::Value::String(parent)) => Some(parent.clone()),
+		Some(_) => return Err("'parent' must be a string".to_string()),
+		None => None,
+	};
+	
+	let textures = parse_textures(root.get("textures"))?;
+	let elements = parse_elements(root.get("elements"))?;
+	let layer = parse_layer(root.get("layer"))?;
+	let tint = parse_tint(root.get("tint"))?;
+	let variants = parse_parts(root.get("variants"), "variants")?;
+	let parts = parse_parts(root.get("parts"), "parts")?;
+	
+	Ok(BlockModelFile {
+		parent,
+		textures,
+		elements,
+		layer,
+		tint,
+		variants,
+		parts,
+	})
+}
+
+fn parse_textures(value: Option<&toml::Value>) -> Result<FxHashMap<String, String>, String> {
+	let mut textures = FxHashMap::default();
+	match value {
+		Some(toml::Value::Table(table)) => for (key, value) in table.iter() {
+			match value {
+				toml::Value::String(value) => textures.insert(key.clone(), value.clone()),
+				_ => return Err(format!("Texture '{}' must be a string", key)),
+			};
+		},
+		Some(_) => return Err("'textures' must be a table".to_string()),
+		None => (),
+	};
+	Ok(textures)
+}
+
+fn parse_layer(value: Option<&toml::Value>) -> Result<Option<BlockRenderLayer>, String> {
+	match value {
+		Some(toml::Value::String(layer)) => match layer.as_str() {
+			"opaque" => Ok(Some(BlockRenderLayer::Opaque)),
+			"cutout" => Ok(Some(BlockRenderLayer::Cutout)),
+			"translucent" => Ok(Some(BlockRenderLayer::Translucent)),
+			_ => Err(format!("Unknown render layer '{}'", layer)),
+		},
+		Some(_) => Err("'layer' must be a string".to_string()),
+		None => Ok(None),
+	}
+}
+
+fn parse_tint(value: Option<&toml::Value>) -> Result<Option<String>, String> {
+	match value {
+		Some(toml::Value::String(tint)) => Ok(Some(tint.clone())),
+		Some(_) => Err("'tint' must be the name of a tint provider".to_string()),
+		None => Ok(None),
+	}
+}
+
+fn parse_elements(value: Option<&toml::Value>) -> Result<Option<Vec<BlockModelFileElement>>, String> {
+	match value {
+		Some(toml::Value::Array(array)) => Ok(Some(array.iter()
+			.map(parse_element)
+			.collect::<Result<Vec<_>, _>>()?)),
+		Some(_) => Err("'elements' must be an array of tables".to_string()),
+		None => Ok(None),
+	}
+}
+
+fn parse_parts(value: Option<&toml::Value>, key: &str) -> Result<Option<Vec<BlockModelFilePart>>, String> {
+	match value {
+		Some(toml::Value::Array(array)) => Ok(Some(array.iter()
+			.map(parse_part)
+			.collect::<Result<Vec<_>, _>>()?)),
+		Some(_) => Err(format!("'{}' must be an array of tables", key)),
+		None => Ok(None),
+	}
+}
+
+fn parse_part(value: &toml::Value) -> Result<BlockModelFilePart, String> {
+	let table = match value {
+		toml::Value::Table(table) => table,
+		_ => return Err("Variants and parts must be tables".to_string()),
+	};
+	
+	if table.contains_key("variants") || table.contains_key("parts") {
+		return Err("Variants and parts cannot be nested".to_string());
+	}
+	
+	let parent = match table.get("model") {
+		Some(toml::Value::String(model)) => Some(model.clone()),
+		Some(_) => return Err("'model' must be a string".to_string()),
+		None => None,
+	};
+	
+	let mut when: SmallVec<[BlockModelCondition; 4]> = smallvec![];
+	match table.get("when") {
+		Some(toml::Value::Table(conditions)) => for (key, value) in conditions.iter() {
+			match value {
+				toml::Value::String(value) => when.push(parse_condition(key, value)?),
+				_ => return Err(format!("Condition '{}' must be a string", key)),
+			}
+		},
+		Some(_) => return Err("'when' must be a table".to_string()),
+		None => (),
+	}
+	
+	Ok(BlockModelFilePart {
+		when,
+		file: BlockModelFile {
+			parent,
+			textures: parse_textures(table.get("textures"))?,
+			elements: parse_elements(table.get("elements"))?,
+			layer: parse_layer(table.get("layer"))?,
+			tint: parse_tint(table.get("tint"))?,
+			variants: None,
+			parts: None,
+		},
+	})
+}
+
+/// Parses a condition, given the direction of the neighbour (e.g. `up_east`) and what it must be.
+fn parse_condition(direction: &str, value: &str) -> Result<BlockModelCondition, String> {
+	let mut offset = (0i8, 0i8, 0i8);
+	
+	for name in direction.split('_') {
+		let (nx, ny, nz) = parse_face_name(name)?.normal();
+		let (x, y, z) = (nx as i8, ny as i8, nz as i8);
+		
+		if (x != 0 && offset.0 != 0) || (y != 0 && offset.1 != 0) || (z != 0 && offset.2 != 0) {
+			return Err(format!("Direction '{}' uses an axis twice", direction));
+		}
+		
+		offset = (offset.0 + x, offset.1 + y, offset.2 + z);
+	}
+	
+	let (negate, value) = match value.starts_with('!') {
+		true => (true, &value[1..]),
+		false => (false, value),
+	};
+	
+	let (matches, invert) = match value {
+		"same" => (BlockModelMatch::Same, false),
+		"other" => (BlockModelMatch::Same, true),
+		"solid" => (BlockModelMatch::Solid, false),
+		"air" => (BlockModelMatch::Solid, true),
+		"" => return Err(format!("Condition '{}' is empty", direction)),
+		name => (BlockModelMatch::Block(name.to_string()), false),
+	};
+	
+	Ok(BlockModelCondition {
+		offset,
+		matches,
+		negate: negate != invert,
+	})
+}
+
+fn parse_element(value: &toml::Value) -> Result<BlockModelFileElement, String> {
+	let table = match value {
+		toml::Value::Table(table) => table,
+		_ => return Err("Element must be a table".to_string()),
+	};
+	
+	let from = parse_floats::<[f32; 3]>(table.get("from"), "from")?.unwrap_or([0.0, 0.0, 0.0]);
+	let to = parse_floats::<[f32; 3]>(table.get("to"), "to")?.unwrap_or([1.0, 1.0, 1.0]);
+	
+	let inside = parse_bool(table.get("inside"), "inside")?.unwrap_or(false);
+	let outside = parse_bool(table.get("outside"), "outside")?.unwrap_or(true);
+	
+	let tform = match table.get("rotation") {
+		Some(value) => Some(parse_transform(value)?),
+		None => None,
+	};
+	
+	let mut faces: [Option<BlockModelFileFace>; 6] = Default::default();
+	
+	match table.get("faces") {
+		Some(toml::Value::Table(face_table)) => for (key, value) in face_table.iter() {
+			let side = parse_face_name(key)?;
+			faces[side.uid() - 1] = Some(parse_face(side, value)?);
+		},
+		Some(_) => return Err("'faces' must be a table".to_string()),
+		None => (),
+	}
+	
+	Ok(BlockModelFileElement {
+		bounds: [from[0], from[1], from[2], to[0], to[1], to[2]],
+		faces,
+		tform,
+		inside,
+		outside,
+	})
+}
+
+fn parse_face(side: Face, value: &toml::Value) -> Result<BlockModelFileFace, String> {
+	let table = match value {
+		toml::Value::Table(table) => table,
+		_ => return Err("Face must be a table".to_string()),
+	};
+	
+	let texture = match table.get("texture") {
+		Some(toml::Value::String(texture)) => texture.clone(),
+		_ => return Err("Face must have a 'texture' string".to_string()),
+	};
+	
+	let uv = parse_floats::<[f32; 4]>(table.get("uv"), "uv")?.unwrap_or([0.0, 0.0, 1.0, 1.0]);
+	
+	let uv_spin = match table.get("rotation") {
+		Some(toml::Value::Integer(degrees)) if degrees % 90 == 0 => (degrees / 90).rem_euclid(4) as u8,
+		Some(_) => return Err("Face 'rotation' must be a multiple of 90".to_string()),
+		None => 0,
+	};
+	
+	let cull = match table.get("cull") {
+		Some(toml::Value::Boolean(true)) | None => Some(side),
+		Some(toml::Value::Boolean(false)) => None,
+		Some(toml::Value::String(name)) => Some(parse_face_name(name)?),
+		Some(_) => return Err("'cull' must be a boolean or a face name".to_string()),
+	};
+	
+	let tint = match table.get("tint") {
+		Some(toml::Value::Integer(index)) if *index >= 0 && *index <= 255 => Some(*index as u8),
+		Some(_) => return Err("Face 'tint' must be an index between 0 and 255".to_string()),
+		None => None,
+	};
+	
+	Ok(BlockModelFileFace {
+		uv,
+		uv_spin,
+		texture,
+		cull,
+		tint,
+	})
+}
+
+fn parse_transform(value: &toml::Value) -> Result<BlockModelTransform, String> {
+	let table = match value {
+		toml::Value::Table(table) => table,
+		_ => return Err("Element 'rotation' must be a table".to_string()),
+	};
+	
+	let origin = parse_floats::<[f32; 3]>(table.get("origin"), "origin")?.unwrap_or([0.5, 0.5, 0.5]);
+	
+	let axis = match table.get("axis") {
+		Some(toml::Value::String(axis)) => match axis.as_str() {
+			"x" => BlockModelAxis::X,
+			"y" => BlockModelAxis::Y,
+			"z" => BlockModelAxis::Z,
+			_ => return Err(format!("Unknown axis '{}'", axis)),
+		},
+		_ => return Err("Element 'rotation' must have an 'axis' of x, y or z".to_string()),
+	};
+	
+	let angle = match table.get("angle") {
+		Some(toml::Value::Float(angle)) => *angle as f32,
+		Some(toml::Value::Integer(angle)) => *angle as f32,
+		_ => return Err("Element 'rotation' must have a numeric 'angle'".to_string()),
+	};
+	
+	if angle % BlockModelTransform::ANGLE_STEP != 0.0 {
+		return Err(format!("Rotation angle {} is not a multiple of {}", angle, BlockModelTransform::ANGLE_STEP));
+	}
+	
+	if angle.abs() > BlockModelTransform::ANGLE_MAX {
+		return Err(format!("Rotation angle {} is not between -{max} and {max}", angle, max = BlockModelTransform::ANGLE_MAX));
+	}
+	
+	let rescale = parse_bool(table.get("rescale"), "rescale")?.unwrap_or(false);
+	
+	Ok(BlockModelTransform {
+		origin,
+		axis,
+		angle,
+		rescale,
+	})
+}
+
+fn parse_face_name(name: &str) -> Result<Face, String> {
+	match name {
+		"east" => Ok(Face::PositiveX),
+		"west" => Ok(Face::NegativeX),
+		"up" => Ok(Face::PositiveY),
+		"down" => Ok(Face::NegativeY),
+		"south" => Ok(Face::PositiveZ),
+		"north" => Ok(Face::NegativeZ),
+		_ => Err(format!("Unknown face '{}'", name)),
+	}
+}
+
+fn parse_bool(value: Option<&toml::Value>, key: &str) -> Result<Option<bool>, String> {
+	match value {
+		Some(toml::Value::Boolean(value)) => Ok(Some(*value)),
+		Some(_) => Err(format!("'{}' must be a boolean", key)),
+		None => Ok(None),
+	}
+}
+
+fn parse_floats<A: Default + AsMut<[f32]>>(value: Option<&toml::Value>, key: &str) -> Result<Option<A>, String> {
+	let array = match value {
+		Some(toml::Value::Array(array)) => array,
+		Some(_) => return Err(format!("'{}' must be an array of numbers", key)),
+		None => return Ok(None),
+	};
+	
+	let mut out = A::default();
+	
+	if array.len() != out.as_mut().len() {
+		return Err(format!("'{}' must have {} elements", key, out.as_mut().len()));
+	}
+	
+	for (slot, value) in out.as_mut().iter_mut().zip(array.iter()) {
+		*slot = match value {
+			toml::Value::Float(value) => *value as f32,
+			toml::Value::Integer(value) => *value as f32,
+			_ => return Err(format!("'{}' must be an array of numbers", key)),
+		};
+	}
+	
+	Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::resources::{MemoryStorage, ResourceStorage};
+	
+	/// Resources holding the given model files, by model name.
+	fn resources(models: &[(&str, &str)]) -> Resources {
+		let storage = MemoryStorage::new();
+		for (name, source) in models.iter() {
+			let location = BlockModelLoader::get_location(name).ok().unwrap();
+			storage.write_buffer(&location, source.as_bytes()).ok().unwrap();
+		}
+		
+		let mut res = resources::new();
+		res.register_provider_by_type(storage, resources::PRIORITY_FILESYSTEM + 1);
+		res
+	}
+	
+	fn resolve(res: &Resources, name: &str) -> BlockModel {
+		match BlockModelLoader::new().resolve(res, name) {
+			Ok(model) => model,
+			Err(err) => panic!("{}", err),
+		}
+	}
+	
+	const GRANDPARENT: &str = r##"
+		[textures]
+		side = "stone"
+		top = "#side"
+		
+		[[elements]]
+		from = [0, 0, 0]
+		to = [1, 0.5, 1]
+		faces.east = { texture = "#side" }
+		faces.up = { texture = "#top" }
+	"##;
+	
+	const PARENT: &str = r##"
+		parent = "test/grandparent"
+		textures.top = "grass_top"
+	"##;
+	
+	const CHILD: &str = r##"
+		parent = "test/parent"
+		textures.side = "dirt"
+	"##;
+	
+	#[test]
+	fn parents_are_inherited() {
+		let res = resources(&[
+			("test/grandparent", GRANDPARENT),
+			("test/parent", PARENT),
+			("test/child", CHILD),
+		]);
+		
+		let grandparent = resolve(&res, "test/grandparent");
+		assert_eq!(grandparent.parent, None);
+		assert_eq!(grandparent.textures.to_vec(), vec!["stone"]);
+		
+		let parent = resolve(&res, "test/parent");
+		assert_eq!(parent.parent.as_deref(), Some("test/grandparent"));
+		assert_eq!(parent.textures.to_vec(), vec!["stone", "grass_top"]);
+		
+		// The child overrides `side`, and keeps the `top` of its parent.
+		let child = resolve(&res, "test/child");
+		assert_eq!(child.parent.as_deref(), Some("test/parent"));
+		assert_eq!(child.textures.to_vec(), vec!["dirt", "grass_top"]);
+		
+		// The elements come from the grandparent.
+		assert_eq!(child.elements.len(), 1);
+		assert_eq!(child.elements[0].bounds, [0.0, 0.0, 0.0, 1.0, 0.5, 1.0]);
+		assert_eq!(child.elements[0].faces[0].texture, Some(0));
+		assert_eq!(child.elements[0].faces[2].texture, Some(1));
+		assert_eq!(child.elements[0].faces[1].texture, None);
+	}
+	
+	#[test]
+	fn variables_refer_to_overridden_variables() {
+		// Without the override of its parent, `top` refers to `side`.
+		let res = resources(&[
+			("test/grandparent", GRANDPARENT),
+			("test/child", "parent = \"test/grandparent\"\ntextures.side = \"dirt\""),
+		]);
+		
+		assert_eq!(resolve(&res, "test/child").textures.to_vec(), vec!["dirt"]);
+	}
+	
+	#[test]
+	fn cycle_is_reported() {
+		let res = resources(&[
+			("test/a", "parent = \"test/b\""),
+			("test/b", "parent = \"test/c\""),
+			("test/c", "parent = \"test/a\""),
+		]);
+		
+		match BlockModelLoader::new().resolve(&res, "test/a") {
+			Err(BlockModelError::Cycle(chain)) => assert_eq!(chain, vec!["test/a", "test/b", "test/c", "test/a"]),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+		
+		// A model being its own parent is a cycle too.
+		let res = resources(&[("test/self", "parent = \"test/self\"")]);
+		match BlockModelLoader::new().resolve(&res, "test/self") {
+			Err(BlockModelError::Cycle(chain)) => assert_eq!(chain, vec!["test/self", "test/self"]),
+			_ => panic!("Expected a cycle"),
+		}
+	}
+	
+	#[test]
+	fn missing_parent_is_reported() {
+		let res = resources(&[
+			("test/child", "parent = \"test/orphan\""),
+			("test/orphan", "parent = \"test/nowhere\""),
+		]);
+		
+		match BlockModelLoader::new().resolve(&res, "test/child") {
+			Err(BlockModelError::MissingParent(model, parent)) => {
+				assert_eq!(model, "test/orphan");
+				assert_eq!(parent, "test/nowhere");
+			},
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+	}
+	
+	#[test]
+	fn missing_model_is_reported() {
+		let res = resources(&[]);
+		
+		match BlockModelLoader::new().resolve(&res, "test/nothing") {
+			Err(BlockModelError::Resource(model, ResourceError::NotFound)) => assert_eq!(model, "test/nothing"),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+	}
+	
+	#[test]
+	fn invalid_model_is_reported() {
+		let res = resources(&[
+			("test/child", "parent = \"test/broken\""),
+			("test/broken", "elements = 5"),
+		]);
+		
+		match BlockModelLoader::new().resolve(&res, "test/child") {
+			Err(BlockModelError::Parse(model, _)) => assert_eq!(model, "test/broken"),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+	}
+	
+	#[test]
+	fn rotation_angles() {
+		let rotation = |angle: &str| parse_model(&format!("[[elements]]\nrotation = {{ axis = \"y\", angle = {}, rescale = true }}", angle))
+			.map(|file| file.elements.unwrap()[0].tform.unwrap().angle);
+		
+		for angle in ["-45", "-22.5", "0", "22.5", "45", "45.0"].iter() {
+			assert_eq!(rotation(angle), Ok(angle.parse::<f32>().unwrap()));
+		}
+		
+		// Not a multiple of the step, or beyond 45° (where rescaling would divide by almost zero).
+		for angle in ["10", "67.5", "-67.5", "90", "-90", "180", "360"].iter() {
+			assert!(rotation(angle).is_err(), "angle {} was accepted", angle);
+		}
+	}
+	
+	#[test]
+	fn conditions_are_parsed() {
+		let file = parse_model(r#"
+			[[parts]]
+			when = { up_east = "same", north = "!air", down_south_west = "adm", east = "!other" }
+		"#).unwrap_or_else(|err| panic!("{}", err));
+		
+		let parts = file.parts.unwrap();
+		assert_eq!(parts.len(), 1);
+		
+		let mut when: Vec<_> = parts[0].when.iter()
+			.map(|condition| (condition.offset, condition.matches.clone(), condition.negate))
+			.collect();
+		when.sort_by_key(|(offset, _, _)| *offset);
+		
+		assert_eq!(when, vec![
+			((-1, -1, 1), BlockModelMatch::Block("adm".to_string()), false),
+			((0, 0, -1), BlockModelMatch::Solid, false),
+			((1, 0, 0), BlockModelMatch::Same, false),
+			((1, 1, 0), BlockModelMatch::Same, false),
+		]);
+	}
+	
+	#[test]
+	fn invalid_conditions_are_rejected() {
+		for when in ["east_west = \"same\"", "up_up = \"same\"", "inside = \"same\"", "east = \"\"", "east = 1"].iter() {
+			let source = format!("[[parts]]\nwhen = {{ {} }}", when);
+			assert!(parse_model(&source).is_err(), "condition '{}' was accepted", when);
+		}
+		
+		assert!(parse_model("[[parts]]\n[[parts.parts]]").is_err(), "nested parts were accepted");
+	}
+	
+	#[test]
+	fn fence_parts_are_resolved() {
+		let mut res = resources::new();
+		res.register_provider_by_type(resources::IncludeProvider::new(BLOCK_MODEL_FILES.to_vec()), resources::PRIORITY_INCLUDES);
+		
+		let fence = resolve(&res, "block/adm_fence");
+		assert!(fence.is_connected());
+		assert!(fence.variants.is_empty());
+		assert_eq!(fence.textures.to_vec(), vec!["adm"]);
+		
+		// Each bar inherits the texture variables of the fence.
+		let offsets: Vec<(i8, i8, i8)> = fence.parts.iter()
+			.map(|part| {
+				assert_eq!(part.when.len(), 1);
+				assert_eq!(part.when[0].matches, BlockModelMatch::Solid);
+				assert_eq!(part.model.textures.to_vec(), vec!["adm"]);
+				part.when[0].offset
+			})
+			.collect();
+		
+		assert_eq!(offsets, vec![(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1)]);
+	}
+	
+	#[test]
+	fn missing_texture_is_reported() {
+		let res = resources(&[
+			("test/loop", "textures.a = \"#b\"\ntextures.b = \"#a\"\n[[elements]]\nfaces.up = { texture = \"#a\" }"),
+			("test/undefined", "[[elements]]\nfaces.up = { texture = \"#nothing\" }"),
+		]);
+		
+		match BlockModelLoader::new().resolve(&res, "test/loop") {
+			Err(BlockModelError::MissingTexture(model, _)) => assert_eq!(model, "test/loop"),
+			_ => panic!("Expected a missing texture"),
+		}
+		
+		match BlockModelLoader::new().resolve(&res, "test/undefined") {
+			Err(BlockModelError::MissingTexture(model, variable)) => {
+				assert_eq!(model, "test/undefined");
+				assert_eq!(variable, "nothing");
+			},
+			_ => panic!("Expected a missing texture"),
+		}
+	}
+}
@@ -0,0 +1,390 @@
+use super::*;
+use super::resources::ResourceLocation;
+use super::resources::ResourceLocationError;
+use super::resources::ResourceProvider;
+use super::resources::Resources;
+use image::GenericImageView;
+
+pub const BLOCK_TEXTURE_FILES: [(&str, &[u8]); 1] = [
+	("core/textures/block/adm.png", include_bytes!("textures/block/adm.png")),
+];
+
+/// The name of the texture that replaces all missing or broken textures; always the first layer.
+pub const MISSINGNO: &str = "missingno";
+
+/// The largest width and height of a single texture layer.
+pub const TEXTURE_MAX_SIZE: u32 = 512;
+
+/// The largest number of texture layers; the minimum `GL_MAX_ARRAY_TEXTURE_LAYERS` of OpenGL 3.3.
+pub const TEXTURE_MAX_LAYERS: usize = 256;
+
+/// Errors that may occur when building the block textures.
+pub enum BlockTexturesError {
+	/// A texture is larger than `TEXTURE_MAX_SIZE`.
+	TooLarge(String, u32, u32),
+	
+	/// There are more textures than `TEXTURE_MAX_LAYERS`.
+	TooManyLayers(usize),
+}
+
+impl std::fmt::Display for BlockTexturesError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			BlockTexturesError::TooLarge(name, width, height) => write!(f, "Block texture '{}' is too large: {}x{} > {}x{}", name, width, height, TEXTURE_MAX_SIZE, TEXTURE_MAX_SIZE),
+			BlockTexturesError::TooManyLayers(count) => write!(f, "Too many block textures: {} > {}", count, TEXTURE_MAX_LAYERS),
+		}
+	}
+}
+
+/// A single RGBA image, before being placed into its layer.
+pub struct BlockTextureSprite {
+	pub name: String,
+	pub width: u32,
+	pub height: u32,
+	
+	/// The pixels of the image; for animated textures, those of the first frame shown.
+	pub pixels: Vec<u8>,
+	
+	/// The pixels of every frame and the timing, if the texture is animated.
+	pub animation: Option<(Vec<Vec<u8>>, BlockAnimationTiming)>,
+}
+
+impl BlockTextureSprite {
+	/// Generates the magenta-and-black checkerboard that stands in for missing textures.
+	pub fn missingno() -> Self {
+		let size = 16;
+		let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+		
+		for y in 0..size {
+			for x in 0..size {
+				if (x < size / 2) == (y < size / 2) {
+					pixels.extend_from_slice(&[255, 0, 255, 255]);
+				} else {
+					pixels.extend_from_slice(&[0, 0, 0, 255]);
+				}
+			}
+		}
+		
+		Self {
+			name: MISSINGNO.to_string(),
+			width: size,
+			height: size,
+			pixels,
+			animation: None,
+		}
+	}
+	
+	/// Returns the resource location of the block texture with the given name.
+	pub fn get_location(name: &str) -> Result<ResourceLocation, ResourceLocationError> {
+		ResourceLocation::parse(&format!("core:textures/block/{}.png", name))
+	}
+	
+	/// Loads the block texture with the given name, if it exists and can be decoded.
+	pub fn load(res: &Resources, name: &str) -> Option<Self> {
+		let location = match Self::get_location(name) {
+			Ok(location) => location,
+			Err(err) => {
+				warn!("{}; using '{}' instead.", err, MISSINGNO);
+				return None
+			}
+		};
+		
+		let buffer = match res.res_as_buffer(&location) {
+			Ok(buffer) => buffer,
+			Err(_) => {
+				warn!("Block texture '{}' is missing; using '{}' instead.", name, MISSINGNO);
+				return None
+			}
+		};
+		
+		let image = match image::load_from_memory(&buffer) {
+			Ok(image) => image,
+			Err(err) => {
+				warn!("Block texture '{}' could not be decoded ({}); using '{}' instead.", name, err, MISSINGNO);
+				return None
+			}
+		};
+		
+		let (width, height) = image.dimensions();
+		
+		let mut sprite = Self {
+			name: name.to_string(),
+			width,
+			height,
+			pixels: image.to_rgba().into_raw(),
+			animation: None,
+		};
+		
+		let animated = BlockAnimationDef::load(res, name)
+			.and_then(|def| match def {
+				Some(def) => sprite.animate(&def),
+				None => Ok(()),
+			});
+		
+		if let Err(err) = animated {
+			warn!("Block texture '{}' has an invalid animation ({}); it will not move.", name, err);
+		}
+		
+		Some(sprite)
+	}
+	
+	/// Splits the sprite, a vertical strip of square frames, into the frames of the given animation.
+	pub fn animate(&mut self, def: &BlockAnimationDef) -> Result<(), String> {
+		if self.height % self.width != 0 {
+			return Err(format!("{}x{} is not a strip of square frames", self.width, self.height));
+		}
+		
+		let frame_count = (self.height / self.width) as usize;
+		let sequence = def.sequence(frame_count)?;
+		
+		let frame_bytes = (self.width * self.width * 4) as usize;
+		let frames: Vec<Vec<u8>> = self.pixels.chunks(frame_bytes)
+			.map(|frame| frame.to_vec())
+			.collect();
+		
+		self.height = self.width;
+		self.pixels = frames[sequence[0].index].clone();
+		self.animation = Some((frames, BlockAnimationTiming::new(sequence, def.interpolate)));
+		Ok(())
+	}
+	
+	/// Scales the sprite to a square of the given size, picking the nearest pixel.
+	pub fn resample(&self, size: u32) -> Vec<u8> {
+		resample_rgba(&self.pixels, self.width, self.height, size)
+	}
+}
+
+/// Scales an RGBA image to a square of the given size, picking the nearest pixel.
+pub fn resample_rgba(pixels: &[u8], width: u32, height: u32, size: u32) -> Vec<u8> {
+	if width == size && height == size {
+		return pixels.to_vec();
+	}
+	
+	let mut out = Vec::with_capacity((size * size * 4) as usize);
+	
+	for y in 0..size {
+		for x in 0..size {
+			let sx = (x * width / size) as usize;
+			let sy = (y * height / size) as usize;
+			let src = (sy * width as usize + sx) * 4;
+			out.extend_from_slice(&pixels[src..src+4]);
+		}
+	}
+	
+	out
+}
+
+/// All block textures, as the layers of an array texture.
+///
+/// Every layer is a square of the same, power-of-two size; smaller textures are scaled up to it.
+/// Unlike the sub-rectangles of an atlas, layers cannot bleed into each other and can be repeated.
+pub struct BlockTextureArray {
+	/// The width and height of every layer.
+	pub size: u32,
+	
+	/// The pixels of every layer, tightly packed RGBA, with rows ordered top to bottom.
+	pub layers: Vec<Vec<u8>>,
+	
+	/// The layer of each texture.
+	pub indices: FxHashMap<String, u32>,
+	
+	/// The animated layers.
+	pub animations: Vec<BlockTextureAnimation>,
+}
+
+impl BlockTextureArray {
+	/// Loads the given textures from the resources and places each into its own layer.
+	///
+	/// Textures that cannot be loaded are replaced by `missingno`, which is always the first layer.
+	pub fn build(res: &Resources, textures: &[String]) -> Result<Self, BlockTexturesError> {
+		Self::build_with_progress(res, textures, &|_| ())
+	}
+	
+	/// Like `build`, reporting the number of textures loaded so far to `progress` as it goes.
+	pub fn build_with_progress(res: &Resources, textures: &[String], progress: &dyn Fn(usize)) -> Result<Self, BlockTexturesError> {
+		let mut sprites = vec![BlockTextureSprite::missingno()];
+		let mut aliases: Vec<&str> = vec![];
+		
+		for (index, name) in textures.iter().enumerate() {
+			progress(index);
+			
+			if name == MISSINGNO || sprites.iter().any(|sprite| &sprite.name == name) {
+				continue;
+			}
+			
+			match BlockTextureSprite::load(res, name) {
+				Some(sprite) => sprites.push(sprite),
+				None => aliases.push(name),
+			}
+		}
+		
+		progress(textures.len());
+		
+		let mut array = Self::assemble(&sprites)?;
+		
+		for alias in aliases {
+			array.indices.insert(alias.to_string(), 0);
+		}
+		
+		debug!("Placed {} block textures ({} animated) into {}x{} layers.", sprites.len(), array.animations.len(), array.size, array.size);
+		Ok(array)
+	}
+	
+	/// Scales the given sprites to a common size and assigns them their layers.
+	pub fn assemble(sprites: &[BlockTextureSprite]) -> Result<Self, BlockTexturesError> {
+		if sprites.len() > TEXTURE_MAX_LAYERS {
+			return Err(BlockTexturesError::TooManyLayers(sprites.len()));
+		}
+		
+		let mut size = 1;
+		for sprite in sprites.iter() {
+			if sprite.width > TEXTURE_MAX_SIZE || sprite.height > TEXTURE_MAX_SIZE {
+				return Err(BlockTexturesError::TooLarge(sprite.name.clone(), sprite.width, sprite.height));
+			}
+			
+			size = std::cmp::max(size, std::cmp::max(sprite.width, sprite.height).next_power_of_two());
+		}
+		
+		let names: Vec<&str> = sprites.iter().map(|sprite| sprite.name.as_str()).collect();
+		let indices = assign_layers(&names);
+		
+		let animations = sprites.iter()
+			.filter_map(|sprite| sprite.animation.as_ref().map(|(frames, timing)| BlockTextureAnimation {
+				layer: indices[&sprite.name],
+				frames: frames.iter()
+					.map(|frame| resample_rgba(frame, sprite.width, sprite.height, size))
+					.collect(),
+				timing: timing.clone(),
+			}))
+			.collect();
+		
+		Ok(Self {
+			size,
+			layers: sprites.iter().map(|sprite| sprite.resample(size)).collect(),
+			indices,
+			animations,
+		})
+	}
+	
+	/// Returns the layer of the given texture, or that of `missingno` if there is none.
+	pub fn get_layer(&self, name: &str) -> u32 {
+		match self.indices.get(name) {
+			Some(layer) => *layer,
+			None => 0,
+		}
+	}
+}
+
+/// Assigns each texture the index of its layer, in the given order; duplicates share the first layer.
+pub fn assign_layers(names: &[&str]) -> FxHashMap<String, u32> {
+	let mut indices = FxHashMap::default();
+	
+	for name in names.iter() {
+		if !indices.contains_key(*name) {
+			let layer = indices.len() as u32;
+			indices.insert(name.to_string(), layer);
+		}
+	}
+	
+	indices
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn sprite(name: &str, width: u32, height: u32) -> BlockTextureSprite {
+		BlockTextureSprite {
+			name: name.to_string(),
+			width,
+			height,
+			pixels: vec![255; (width * height * 4) as usize],
+			animation: None,
+		}
+	}
+	
+	#[test]
+	fn missingno_is_the_first_layer() {
+		let res = resources::new();
+		let textures = vec!["missing".to_string(), MISSINGNO.to_string()];
+		
+		let array = match BlockTextureArray::build(&res, &textures) {
+			Ok(array) => array,
+			Err(err) => panic!("{}", err),
+		};
+		
+		assert_eq!(array.layers.len(), 1);
+		assert_eq!(array.get_layer(MISSINGNO), 0);
+		assert_eq!(array.get_layer("missing"), 0);
+		assert_eq!(array.get_layer("never-requested"), 0);
+	}
+	
+	#[test]
+	fn duplicates_share_a_layer() {
+		let layers = assign_layers(&["a", "b", "a", "c", "b"]);
+		
+		assert_eq!(layers.len(), 3);
+		assert_eq!(layers["a"], 0);
+		assert_eq!(layers["b"], 1);
+		assert_eq!(layers["c"], 2);
+	}
+	
+	#[test]
+	fn size_is_the_next_power_of_two() {
+		let sprites = vec![BlockTextureSprite::missingno(), sprite("wide", 20, 8), sprite("small", 4, 4)];
+		
+		let array = match BlockTextureArray::assemble(&sprites) {
+			Ok(array) => array,
+			Err(err) => panic!("{}", err),
+		};
+		
+		assert_eq!(array.size, 32);
+		assert_eq!(array.layers.len(), 3);
+		for layer in array.layers.iter() {
+			assert_eq!(layer.len(), 32 * 32 * 4);
+		}
+	}
+	
+	#[test]
+	fn too_large() {
+		let sprites = vec![BlockTextureSprite::missingno(), sprite("huge", TEXTURE_MAX_SIZE + 1, 16)];
+		
+		match BlockTextureArray::assemble(&sprites) {
+			Err(BlockTexturesError::TooLarge(name, width, height)) => {
+				assert_eq!(name, "huge");
+				assert_eq!((width, height), (TEXTURE_MAX_SIZE + 1, 16));
+			},
+			Err(err) => panic!("unexpected error: {}", err),
+			Ok(_) => panic!("a texture larger than the maximum was accepted"),
+		}
+	}
+	
+	#[test]
+	fn too_many_layers() {
+		let sprites: Vec<BlockTextureSprite> = (0..TEXTURE_MAX_LAYERS + 1)
+			.map(|index| sprite(&format!("texture-{}", index), 1, 1))
+			.collect();
+		
+		match BlockTextureArray::assemble(&sprites) {
+			Err(BlockTexturesError::TooManyLayers(count)) => assert_eq!(count, TEXTURE_MAX_LAYERS + 1),
+			Err(err) => panic!("unexpected error: {}", err),
+			Ok(_) => panic!("more textures than layers were accepted"),
+		}
+	}
+	
+	#[test]
+	fn resample_non_square() {
+		// Two pixels side by side: Red and blue.
+		let pixels = [255, 0, 0, 255, 0, 0, 255, 255];
+		let out = resample_rgba(&pixels, 2, 1, 4);
+		
+		assert_eq!(out.len(), 4 * 4 * 4);
+		for y in 0..4 {
+			for x in 0..4 {
+				let pixel = &out[(y * 4 + x) * 4..(y * 4 + x) * 4 + 4];
+				let expected = if x < 2 { &pixels[0..4] } else { &pixels[4..8] };
+				assert_eq!(pixel, expected, "pixel {} {}", x, y);
+			}
+		}
+	}
+}
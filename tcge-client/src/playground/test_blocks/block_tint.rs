@@ -0,0 +1,272 @@
+//! Tint colours for block faces.
+//!
+//! A block model names a tint provider with its `tint` key, and each face that should be tinted
+//! has a `tint` index. When a chunk is meshed, the provider computes the colour of every tinted face
+//! from the position and state of the block, which is then baked into the vertices.
+//! The tint index only selects which of the tinted layers of the block a face belongs to.
+
+use super::*;
+use std::rc::Rc;
+
+/// An RGB colour, multiplied with the texture of a face.
+pub type BlockTint = [u8; 3];
+
+/// The colour of faces that are not tinted.
+pub const TINT_NONE: BlockTint = [255, 255, 255];
+
+/// Computes the colour of the tinted faces of a block.
+///
+/// Implementations must be pure functions of their arguments, so that meshing stays deterministic.
+pub trait BlockTintProvider {
+	fn get_tint(&self, pos: &BlockCoord, state: &BlockState, index: u8) -> BlockTint;
+}
+
+/// The same colour everywhere.
+pub struct ConstantTint(pub BlockTint);
+
+impl BlockTintProvider for ConstantTint {
+	fn get_tint(&self, _pos: &BlockCoord, _state: &BlockState, _index: u8) -> BlockTint {
+		self.0
+	}
+}
+
+/// The number of bits of a dye property in the data of a block state.
+pub const DYE_BITS: u32 = 4;
+
+/// Returns the dye of the given tinted layer of a block state.
+///
+/// Every tinted layer has its own dye property: Layer `i` is dyed by bits `4i..4i+4` of the state data,
+/// so a block can have up to four independently dyed layers.
+pub fn get_dye(state: &BlockState, index: u8) -> Option<usize> {
+	let shift = index as u32 * DYE_BITS;
+	if shift >= blocks::BlockStateData::BITS {
+		return None
+	}
+	
+	Some(((state.data >> shift) & ((1 << DYE_BITS) - 1)) as usize)
+}
+
+/// A colour for each dye, picked by the dye property of the tinted layer.
+pub struct DyeTint(pub Vec<BlockTint>);
+
+impl BlockTintProvider for DyeTint {
+	fn get_tint(&self, _pos: &BlockCoord, state: &BlockState, index: u8) -> BlockTint {
+		match get_dye(state, index) {
+			Some(dye) => self.0.get(dye).cloned().unwrap_or(TINT_NONE),
+			None => TINT_NONE,
+		}
+	}
+}
+
+/// A colour that changes smoothly across the world, like the colour of grass across biomes.
+///
+/// Temperature and humidity are taken from two independent noise maps over the horizontal plane;
+/// the colour blends from `dry` to `lush` by humidity, and then towards `cold` as it gets colder.
+pub struct BiomeTint {
+	pub dry: BlockTint,
+	pub lush: BlockTint,
+	pub cold: BlockTint,
+	
+	/// The size (in blocks) of the features of the noise maps.
+	pub scale: f32,
+	pub seed: u32,
+}
+
+impl BiomeTint {
+	/// Returns the temperature and humidity at the given position, each in `0..=1`.
+	pub fn get_climate(&self, x: BlockDim, z: BlockDim) -> (f32, f32) {
+		let x = x as f32 / self.scale;
+		let z = z as f32 / self.scale;
+		(
+			value_noise(x, z, self.seed),
+			value_noise(x, z, self.seed ^ 0x9E37_79B9),
+		)
+	}
+}
+
+impl BlockTintProvider for BiomeTint {
+	fn get_tint(&self, pos: &BlockCoord, _state: &BlockState, _index: u8) -> BlockTint {
+		let (temperature, humidity) = self.get_climate(pos.x, pos.z);
+		let color = lerp_tint(&self.dry, &self.lush, humidity);
+		lerp_tint(&color, &self.cold, 1.0 - temperature)
+	}
+}
+
+fn lerp_tint(a: &BlockTint, b: &BlockTint, x: f32) -> BlockTint {
+	let lerp = |a: u8, b: u8| ((1.0 - x) * a as f32 + x * b as f32).round() as u8;
+	[lerp(a[0], b[0]), lerp(a[1], b[1]), lerp(a[2], b[2])]
+}
+
+/// Smoothly interpolated noise in `0..=1`, with one random value per integer grid point.
+pub fn value_noise(x: f32, z: f32, seed: u32) -> f32 {
+	let (x0, z0) = (x.floor(), z.floor());
+	let (fx, fz) = (x - x0, z - z0);
+	let (x0, z0) = (x0 as i32, z0 as i32);
+	
+	// Smoothstep, so there are no visible creases along the grid.
+	let (sx, sz) = (fx * fx * (3.0 - 2.0 * fx), fz * fz * (3.0 - 2.0 * fz));
+	
+	let v00 = hash_noise(x0, z0, seed);
+	let v10 = hash_noise(x0 + 1, z0, seed);
+	let v01 = hash_noise(x0, z0 + 1, seed);
+	let v11 = hash_noise(x0 + 1, z0 + 1, seed);
+	
+	let a = v00 + (v10 - v00) * sx;
+	let b = v01 + (v11 - v01) * sx;
+	a + (b - a) * sz
+}
+
+/// A random value in `0..=1` for the given grid point.
+fn hash_noise(x: i32, z: i32, seed: u32) -> f32 {
+	let mut h = seed
+		^ (x as u32).wrapping_mul(0x27D4_EB2D)
+		^ (z as u32).wrapping_mul(0x1656_67B1);
+	h ^= h >> 15;
+	h = h.wrapping_mul(0x85EB_CA6B);
+	h ^= h >> 13;
+	h = h.wrapping_mul(0xC2B2_AE35);
+	h ^= h >> 16;
+	(h & 0xFFFF) as f32 / 65535.0
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// The tint providers block models can refer to, by name.
+pub struct BlockTintProviders {
+	providers: FxHashMap<String, Rc<dyn BlockTintProvider>>,
+}
+
+impl BlockTintProviders {
+	/// Creates the registry with the built-in providers.
+	pub fn new() -> Self {
+		let mut providers = Self {
+			providers: FxHashMap::default(),
+		};
+		
+		providers.register("grass", Rc::new(BiomeTint {
+			dry: [191, 183, 85],
+			lush: [71, 205, 51],
+			cold: [128, 180, 151],
+			scale: 256.0,
+			seed: 1,
+		}));
+		
+		providers.register("foliage", Rc::new(BiomeTint {
+			dry: [174, 164, 42],
+			lush: [48, 187, 11],
+			cold: [96, 161, 123],
+			scale: 256.0,
+			seed: 1,
+		}));
+		
+		providers.register("water", Rc::new(ConstantTint([63, 118, 228])));
+		
+		providers.register("dye", Rc::new(DyeTint(vec![
+			[249, 255, 254], [249, 128, 29], [199, 78, 189], [58, 179, 218],
+			[254, 216, 61], [128, 199, 31], [243, 139, 170], [71, 79, 82],
+			[157, 157, 151], [22, 156, 156], [137, 50, 184], [60, 68, 170],
+			[131, 84, 50], [94, 124, 22], [176, 46, 38], [29, 29, 33],
+		])));
+		
+		providers
+	}
+	
+	/// Registers a provider, replacing any previous one of the same name.
+	pub fn register(&mut self, name: &str, provider: Rc<dyn BlockTintProvider>) {
+		self.providers.insert(name.to_string(), provider);
+	}
+	
+	pub fn get(&self, name: &str) -> Option<Rc<dyn BlockTintProvider>> {
+		self.providers.get(name).cloned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn state(data: blocks::BlockStateData) -> BlockState {
+		blocks::Blocks::new()
+			.get_block_by_name_unchecked("adm")
+			.get_default_state()
+			.with_data(data)
+	}
+	
+	#[test]
+	fn value_noise_is_deterministic() {
+		for i in 0..100 {
+			let (x, z) = (i as f32 * 0.37 - 10.0, i as f32 * -1.13 + 4.0);
+			assert_eq!(value_noise(x, z, 7), value_noise(x, z, 7));
+		}
+		
+		// Other seeds give other maps.
+		let differs = (0..100).any(|i| value_noise(i as f32 * 0.5, 0.25, 7) != value_noise(i as f32 * 0.5, 0.25, 8));
+		assert!(differs);
+	}
+	
+	#[test]
+	fn value_noise_is_in_range() {
+		for x in -50..50 {
+			for z in -50..50 {
+				let value = value_noise(x as f32 * 0.31, z as f32 * 0.17, 1);
+				assert!(value >= 0.0 && value <= 1.0, "noise at {} {} is {}", x, z, value);
+			}
+		}
+		
+		// On the grid points, the noise is exactly the hashed value.
+		assert_eq!(value_noise(3.0, -2.0, 5), hash_noise(3, -2, 5));
+	}
+	
+	#[test]
+	fn biome_tint_is_deterministic_and_between_its_colours() {
+		let tint = BiomeTint {
+			dry: [200, 100, 0],
+			lush: [0, 200, 100],
+			cold: [100, 0, 200],
+			scale: 16.0,
+			seed: 3,
+		};
+		
+		for x in -20..20 {
+			let pos = BlockCoord::new(x * 7, 64, x * -5);
+			let color = tint.get_tint(&pos, &state(0), 0);
+			
+			assert_eq!(color, tint.get_tint(&pos, &state(0), 0));
+			
+			// Neither the height, nor the state nor the index matter.
+			assert_eq!(color, tint.get_tint(&BlockCoord::new(x * 7, 0, x * -5), &state(5), 1));
+			
+			for channel in 0..3 {
+				let min = std::cmp::min(tint.dry[channel], std::cmp::min(tint.lush[channel], tint.cold[channel]));
+				let max = std::cmp::max(tint.dry[channel], std::cmp::max(tint.lush[channel], tint.cold[channel]));
+				assert!(color[channel] >= min && color[channel] <= max);
+			}
+		}
+	}
+	
+	#[test]
+	fn dye_comes_from_the_state() {
+		let palette: Vec<BlockTint> = (0..16).map(|dye| [dye * 10, 0, 0]).collect();
+		let tint = DyeTint(palette);
+		let pos = BlockCoord::new(0, 0, 0);
+		
+		// The same face (tint index 0) changes colour with the dye of the state.
+		assert_eq!(tint.get_tint(&pos, &state(0), 0), [0, 0, 0]);
+		assert_eq!(tint.get_tint(&pos, &state(3), 0), [30, 0, 0]);
+		assert_eq!(tint.get_tint(&pos, &state(15), 0), [150, 0, 0]);
+		
+		// Every tinted layer reads its own dye.
+		let two_layers = state(0x52);
+		assert_eq!(get_dye(&two_layers, 0), Some(2));
+		assert_eq!(get_dye(&two_layers, 1), Some(5));
+		assert_eq!(tint.get_tint(&pos, &two_layers, 1), [50, 0, 0]);
+		
+		// There is no dye beyond the bits of the state.
+		assert_eq!(get_dye(&two_layers, 4), None);
+		assert_eq!(tint.get_tint(&pos, &two_layers, 4), TINT_NONE);
+		
+		// Dyes without a colour are left untinted.
+		let short = DyeTint(vec![[1, 2, 3]]);
+		assert_eq!(short.get_tint(&pos, &state(1), 0), TINT_NONE);
+	}
+}
@@ -1,18 +1,27 @@
 #version 330 core
 
-uniform sampler2D atlas;
+uniform sampler2DArray textures;
 uniform vec3 sun = vec3(0.707, 0.707, 0.707);
+uniform float cutoff = 0.0;
 
 in vec3 position;
 in vec2 texcoord;
+flat in uint layer;
 in vec3 normal;
 in float ao_term;
+in vec3 tint;
 
 out vec4 Color;
 
 void main() {
     float lighting = max(dot(normal, sun), 0.0);
     
-    Color = texture2D(atlas, texcoord) * vec4(1.0 - ao_term, 1.0 - ao_term, 1.0 - ao_term, 1.0);
-    Color.rgb *= 0.25 + lighting;
+    Color = texture(textures, vec3(texcoord, float(layer))) * vec4(1.0 - ao_term, 1.0 - ao_term, 1.0 - ao_term, 1.0);
+    
+    // Cutout faces discard their transparent pixels.
+    if (Color.a < cutoff) {
+        discard;
+    }
+    
+    Color.rgb *= tint * (0.25 + lighting);
 }
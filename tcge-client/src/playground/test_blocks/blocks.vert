@@ -1,21 +1,23 @@
 #version 330 core
 
-uniform mat4 transform;
+// The vertex words, the `ChunkOffset`, the `chunk_vertex_*()` functions unpacking the words, and `chunk_vertex_normal()` and `chunk_vertex_texcoord()` are inserted here when the shader is loaded.
 
-layout (location = 0) in vec3 Position;
-layout (location = 1) in vec2 TexCoord;
-layout (location = 2) in vec3 Normal;
-layout (location = 3) in float AO_Term;
+uniform mat4 transform;
 
 out vec3 position;
 out vec2 texcoord;
+flat out uint layer;
 out vec3 normal;
 out float ao_term;
+out vec3 tint;
 
 void main() {
-    gl_Position = transform * vec4(Position, 1.0);
+    vec3 Position = vec3(chunk_vertex_x(), chunk_vertex_y(), chunk_vertex_z());
+    gl_Position = transform * vec4(Position + ChunkOffset, 1.0);
     position = Position;
-    texcoord = TexCoord;
-    normal   = Normal;
-    ao_term  = AO_Term;
+    texcoord = chunk_vertex_texcoord();
+    layer    = uint(chunk_vertex_layer());
+    normal   = chunk_vertex_normal();
+    ao_term  = chunk_vertex_ao();
+    tint     = vec3(chunk_vertex_red(), chunk_vertex_green(), chunk_vertex_blue()) * chunk_vertex_light();
 }
@@ -0,0 +1,354 @@
+//! Shared vertex buffers for chunk meshes.
+//!
+//! Instead of a buffer and vertex array per chunk, the vertices of all chunk meshes are suballocated from a few large pages.
+//! The draws of a render pass are queued and then issued with a single `glMultiDrawElementsIndirect` per page;
+//! the origin of each chunk is passed as an instanced attribute, selected by the `baseInstance` of its draw command.
+//!
+//! If multi-draw-indirect is not available (OpenGL 4.3), the same commands are drawn one by one,
+//! with the origin set as a constant vertex attribute.
+
+use super::*;
+use crate::render::{BufferObject, BufferObjectRef, BufferAllocator, BufferAllocationId, BufferRange};
+
+/// The number of vertices in a page; 20 MiB with the current vertex format.
+///
+/// Meshes larger than this get a page of their own.
+pub const CHUNK_BUFFER_PAGE_VERTICES: usize = 1 << 20;
+
+/// Where the vertices of a chunk mesh are stored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkBufferSlot {
+	pub page: usize,
+	pub id: BufferAllocationId,
+}
+
+/// The parameters of a single indexed draw, laid out as `glMultiDrawElementsIndirect` reads them.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct ChunkDrawCommand {
+	pub count: u32,
+	pub instance_count: u32,
+	pub first_index: u32,
+	pub base_vertex: i32,
+	pub base_instance: u32,
+}
+
+/// A run of consecutive draw commands that use the same page.
+#[derive(Copy, Clone, Debug)]
+struct ChunkDrawBatch {
+	page: usize,
+	start: usize,
+	count: usize,
+}
+
+struct ChunkBufferPage {
+	vertices: BufferObject,
+	descriptor: gl::types::GLuint,
+	allocator: BufferAllocator,
+}
+
+pub struct ChunkBufferPool {
+	gl: gl::Gl,
+	pages: Vec<ChunkBufferPage>,
+	quad_index: BufferObjectRef,
+	multi_draw: bool,
+	
+	/// The chunk origins of the queued draws, one per chunk.
+	offsets_buffer: BufferObject,
+	commands_buffer: BufferObject,
+	
+	// Per Pass
+	offsets: Vec<[f32; 3]>,
+	commands: Vec<ChunkDrawCommand>,
+	batches: Vec<ChunkDrawBatch>,
+}
+
+impl ChunkBufferPool {
+	pub fn new(gl: &gl::Gl, quad_index: BufferObjectRef) -> Self {
+		let multi_draw = Self::supports_multi_draw(gl);
+		
+		if !multi_draw {
+			info!("Multi-draw-indirect is not available; chunks are drawn one by one.");
+		}
+		
+		let offsets_buffer = BufferObject::buffer_data_empty::<[f32; 3]>(gl, gl::ARRAY_BUFFER, gl::STREAM_DRAW, 0);
+		let commands_buffer = BufferObject::buffer_data_empty::<ChunkDrawCommand>(gl, gl::DRAW_INDIRECT_BUFFER, gl::STREAM_DRAW, 0);
+		
+		gl.label_object(gl::BUFFER, offsets_buffer.id, "Chunk Offsets");
+		gl.label_object(gl::BUFFER, commands_buffer.id, "Chunk Draw Commands");
+		
+		Self {
+			gl: gl.clone(),
+			pages: vec![],
+			quad_index,
+			multi_draw,
+			offsets_buffer,
+			commands_buffer,
+			offsets: vec![],
+			commands: vec![],
+			batches: vec![],
+		}
+	}
+	
+	/// Returns `true` if the context can draw with `glMultiDrawElementsIndirect` and instanced attributes.
+	fn supports_multi_draw(gl: &gl::Gl) -> bool {
+		let (mut major, mut minor) = (0, 0);
+		unsafe {
+			gl.GetIntegerv(gl::MAJOR_VERSION, &mut major);
+			gl.GetIntegerv(gl::MINOR_VERSION, &mut minor);
+		}
+		
+		(major, minor) >= (4, 3)
+			&& gl.MultiDrawElementsIndirect.is_loaded()
+			&& gl.VertexAttribDivisor.is_loaded()
+	}
+	
+	/// The quad index buffer all pages draw with.
+	pub fn get_quad_index(&self) -> &BufferObjectRef {
+		&self.quad_index
+	}
+	
+	/// Returns the number of pages, and the number of used and total vertices across all pages.
+	pub fn get_usage(&self) -> (usize, usize, usize) {
+		let used = self.pages.iter().map(|page| page.allocator.used()).sum();
+		let capacity = self.pages.iter().map(|page| page.allocator.capacity()).sum();
+		(self.pages.len(), used, capacity)
+	}
+	
+	/// Returns the fragmentation of the most fragmented page.
+	pub fn get_fragmentation(&self) -> f32 {
+		self.pages.iter()
+			.map(|page| page.allocator.fragmentation())
+			.fold(0.0, f32::max)
+	}
+	
+	/// Allocates room for the given (non-zero) number of vertices.
+	///
+	/// If no page has a large enough free range, a page with enough free space in total is defragmented first;
+	/// failing that, a new page is added.
+	pub fn allocate(&mut self, vertices: usize) -> ChunkBufferSlot {
+		for (page, entry) in self.pages.iter_mut().enumerate() {
+			if let Some(id) = entry.allocator.allocate(vertices) {
+				return ChunkBufferSlot { page, id }
+			}
+		}
+		
+		if let Some(page) = self.pages.iter().position(|entry| entry.allocator.available() >= vertices) {
+			self.defragment(page);
+			
+			if let Some(id) = self.pages[page].allocator.allocate(vertices) {
+				return ChunkBufferSlot { page, id }
+			}
+		}
+		
+		let page = self.add_page(std::cmp::max(CHUNK_BUFFER_PAGE_VERTICES, vertices));
+		let id = self.pages[page].allocator.allocate(vertices)
+			.expect("A new page always fits the allocation it was made for.");
+		
+		ChunkBufferSlot { page, id }
+	}
+	
+	/// Frees the vertices of the given slot.
+	pub fn free(&mut self, slot: ChunkBufferSlot) {
+		if self.pages[slot.page].allocator.free(slot.id).is_none() {
+			warn!("Chunk buffer slot {:?} was freed twice.", slot);
+		}
+	}
+	
+	/// Returns the range of vertices of the given slot, within its page.
+	pub fn get(&self, slot: ChunkBufferSlot) -> BufferRange {
+		self.pages[slot.page].allocator.get(slot.id)
+			.expect("Chunk buffer slot is not allocated.")
+	}
+	
+	/// Uploads vertices into the given slot, starting at the given vertex within the slot.
+	pub fn upload(&self, slot: ChunkBufferSlot, offset: usize, vertices: &[ChunkMeshVertex]) {
+		let range = self.get(slot);
+		assert!(offset + vertices.len() <= range.size, "Cannot upload {} vertices into a slot of {}.", vertices.len(), range.size);
+		self.pages[slot.page].vertices.buffer_sub_data(&self.gl, range.offset + offset, vertices);
+	}
+	
+	fn add_page(&mut self, capacity: usize) -> usize {
+		let index = self.pages.len();
+		debug!("Adding chunk buffer page {} with room for {} vertices.", index, capacity);
+		
+		let vertices = BufferObject::buffer_data_empty::<ChunkMeshVertex>(&self.gl, gl::ARRAY_BUFFER, gl::DYNAMIC_DRAW, capacity);
+		let descriptor = self.create_descriptor(&vertices);
+		
+		self.gl.label_object(gl::BUFFER, vertices.id, &format!("Chunk Page {} Geometry", index));
+		self.gl.label_object(gl::VERTEX_ARRAY, descriptor, &format!("Chunk Page {} Descriptor", index));
+		
+		self.pages.push(ChunkBufferPage {
+			vertices,
+			descriptor,
+			allocator: BufferAllocator::new(capacity),
+		});
+		
+		index
+	}
+	
+	fn create_descriptor(&self, vertices: &BufferObject) -> gl::types::GLuint {
+		let mut vao: gl::types::GLuint = 0;
+		unsafe {
+			self.gl.GenVertexArrays(1, &mut vao);
+			self.gl.BindVertexArray(vao);
+			
+			self.gl.BindBuffer(gl::ARRAY_BUFFER, vertices.id);
+			ChunkMeshVertex::setup_attributes(&self.gl);
+			
+			self.gl.BindBuffer(self.quad_index.target, self.quad_index.id);
+			
+			// Without multi-draw, the attribute stays disabled and is set per draw instead.
+			if self.multi_draw {
+				self.gl.BindBuffer(gl::ARRAY_BUFFER, self.offsets_buffer.id);
+				self.gl.EnableVertexAttribArray(CHUNK_OFFSET_LOCATION);
+				self.gl.VertexAttribPointer(
+					CHUNK_OFFSET_LOCATION, // attribute location
+					3, // sub-element count
+					gl::FLOAT, // sub-element type
+					gl::FALSE, // sub-element normalization
+					0,
+					0 as *const gl::types::GLvoid
+				);
+				self.gl.VertexAttribDivisor(CHUNK_OFFSET_LOCATION, 1);
+			}
+			
+			self.gl.BindVertexArray(0);
+			self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+		}
+		
+		vao
+	}
+	
+	/// Compacts the allocations of a page, moving the vertices within its buffer.
+	fn defragment(&mut self, page: usize) {
+		let entry = &mut self.pages[page];
+		let moves = entry.allocator.defragment();
+		
+		debug!("Defragmenting chunk buffer page {}: Moving {} meshes.", page, moves.len());
+		
+		let vertex_size = std::mem::size_of::<ChunkMeshVertex>();
+		let largest = moves.iter().map(|copy| copy.size).max().unwrap_or(0);
+		
+		// Copies within one buffer must not overlap, so overlapping moves go through a scratch buffer.
+		let scratch = BufferObject::buffer_data_empty::<ChunkMeshVertex>(&self.gl, gl::COPY_WRITE_BUFFER, gl::STREAM_COPY, largest);
+		
+		unsafe {
+			self.gl.BindBuffer(gl::COPY_READ_BUFFER, entry.vertices.id);
+			self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, entry.vertices.id);
+			
+			for copy in moves.iter() {
+				let from = (copy.from * vertex_size) as gl::types::GLintptr;
+				let to = (copy.to * vertex_size) as gl::types::GLintptr;
+				let bytes = (copy.size * vertex_size) as gl::types::GLsizeiptr;
+				
+				if copy.to + copy.size <= copy.from {
+					self.gl.CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, from, to, bytes);
+					continue;
+				}
+				
+				self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, scratch.id);
+				self.gl.CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, from, 0, bytes);
+				self.gl.BindBuffer(gl::COPY_READ_BUFFER, scratch.id);
+				self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, entry.vertices.id);
+				self.gl.CopyBufferSubData(gl::COPY_READ_BUFFER, gl::COPY_WRITE_BUFFER, 0, to, bytes);
+				self.gl.BindBuffer(gl::COPY_READ_BUFFER, entry.vertices.id);
+			}
+			
+			self.gl.BindBuffer(gl::COPY_READ_BUFFER, 0);
+			self.gl.BindBuffer(gl::COPY_WRITE_BUFFER, 0);
+			self.gl.DeleteBuffers(1, [scratch.id].as_ptr());
+		}
+	}
+	
+	/// Queues the draw of the given ranges of a slot, for a chunk at the given camera-relative offset.
+	pub fn push_draw(&mut self, slot: ChunkBufferSlot, ranges: &ChunkMeshRanges, offset: &cgmath::Vector3<f32>) {
+		let base = self.get(slot).offset as i32;
+		let instance = self.offsets.len() as u32;
+		self.offsets.push([offset.x, offset.y, offset.z]);
+		
+		for range in ranges.iter() {
+			self.commands.push(ChunkDrawCommand {
+				count: range.count as u32,
+				instance_count: 1,
+				first_index: 0,
+				base_vertex: base + range.base_vertex,
+				base_instance: instance,
+			});
+			
+			match self.batches.last_mut() {
+				Some(batch) if batch.page == slot.page => batch.count += 1,
+				_ => self.batches.push(ChunkDrawBatch {
+					page: slot.page,
+					start: self.commands.len() - 1,
+					count: 1,
+				}),
+			}
+		}
+	}
+	
+	/// Draws all queued draws in the order they were queued in, emptying the queue.
+	pub fn flush_draws(&mut self) {
+		if self.commands.is_empty() {
+			return
+		}
+		
+		if self.multi_draw {
+			self.offsets_buffer.buffer_data_replace(&self.gl, gl::STREAM_DRAW, &self.offsets);
+			self.commands_buffer.buffer_data_replace(&self.gl, gl::STREAM_DRAW, &self.commands);
+			
+			unsafe {
+				self.gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.commands_buffer.id);
+				
+				for batch in self.batches.iter() {
+					self.gl.BindVertexArray(self.pages[batch.page].descriptor);
+					self.gl.MultiDrawElementsIndirect(
+						gl::TRIANGLES,
+						gl::UNSIGNED_SHORT,
+						(batch.start * std::mem::size_of::<ChunkDrawCommand>()) as *const gl::types::GLvoid,
+						batch.count as gl::types::GLsizei,
+						0
+					);
+				}
+				
+				self.gl.BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+			}
+		} else {
+			unsafe {
+				for batch in self.batches.iter() {
+					self.gl.BindVertexArray(self.pages[batch.page].descriptor);
+					
+					for command in self.commands[batch.start..batch.start + batch.count].iter() {
+						let offset = self.offsets[command.base_instance as usize];
+						self.gl.VertexAttrib3f(CHUNK_OFFSET_LOCATION, offset[0], offset[1], offset[2]);
+						self.gl.DrawElementsBaseVertex(
+							gl::TRIANGLES,
+							command.count as gl::types::GLsizei,
+							gl::UNSIGNED_SHORT,
+							0 as *const gl::types::GLvoid,
+							command.base_vertex
+						);
+					}
+				}
+			}
+		}
+		
+		self.offsets.clear();
+		self.commands.clear();
+		self.batches.clear();
+	}
+}
+
+impl Drop for ChunkBufferPool {
+	fn drop(&mut self) {
+		unsafe {
+			for page in self.pages.iter() {
+				self.gl.DeleteBuffers(1, [page.vertices.id].as_ptr());
+				self.gl.DeleteVertexArrays(1, [page.descriptor].as_ptr());
+			}
+			
+			self.gl.DeleteBuffers(1, [self.offsets_buffer.id].as_ptr());
+			self.gl.DeleteBuffers(1, [self.commands_buffer.id].as_ptr());
+		}
+	}
+}
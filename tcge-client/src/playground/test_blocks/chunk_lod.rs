@@ -0,0 +1,497 @@
+use super::*;
+use blocks::Face;
+
+/// Distance (in blocks) from the camera beyond which each coarser level of detail is used.
+pub const LOD_DISTANCES: [f32; 3] = [96.0, 192.0, 384.0];
+
+/// How far (in blocks) the camera must move past a LOD distance before the level actually switches.
+///
+/// Without this, chunks right at a threshold would be remeshed over and over while the camera moves around.
+pub const LOD_HYSTERESIS: f32 = 16.0;
+
+/// The level of detail a chunk is meshed at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChunkLod {
+	/// One voxel per block.
+	Full = 0,
+	
+	/// One voxel per 2³ blocks.
+	Half = 1,
+	
+	/// One voxel per 4³ blocks.
+	Quarter = 2,
+	
+	/// One voxel per 8³ blocks.
+	Eighth = 3,
+}
+
+/// How the blocks within a single LOD voxel are combined.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LodSampling {
+	/// The voxel is solid if at least half of its blocks are; it takes the most common solid block.
+	Majority,
+	
+	/// The voxel is solid if any of its blocks are; it takes the top-most solid block.
+	///
+	/// This keeps thin surfaces (like a single layer of ground) from vanishing at coarse levels.
+	Surface,
+}
+
+impl ChunkLod {
+	/// The edge length of a single voxel, in blocks.
+	pub fn scale(&self) -> usize {
+		1 << (*self as usize)
+	}
+	
+	/// The number of voxels along each axis of a chunk.
+	pub fn size(&self) -> usize {
+		CHUNK_SIZE / self.scale()
+	}
+	
+	/// The sampling method that works best for this level.
+	pub fn sampling(&self) -> LodSampling {
+		match self {
+			ChunkLod::Full => LodSampling::Majority,
+			ChunkLod::Half => LodSampling::Majority,
+			ChunkLod::Quarter => LodSampling::Surface,
+			ChunkLod::Eighth => LodSampling::Surface,
+		}
+	}
+	
+	/// Returns the level of detail for the given distance from the camera, without any hysteresis.
+	pub fn for_distance(distance: f32) -> ChunkLod {
+		if distance < LOD_DISTANCES[0] {
+			ChunkLod::Full
+		} else if distance < LOD_DISTANCES[1] {
+			ChunkLod::Half
+		} else if distance < LOD_DISTANCES[2] {
+			ChunkLod::Quarter
+		} else {
+			ChunkLod::Eighth
+		}
+	}
+	
+	/// Returns the level of detail a chunk at the given distance should switch to, given its current level.
+	///
+	/// The level only changes once the distance is more than `LOD_HYSTERESIS` past a threshold.
+	pub fn select(current: ChunkLod, distance: f32) -> ChunkLod {
+		// The finest level allowed is that of a slightly closer chunk, the coarsest that of a slightly farther one.
+		let finest = Self::for_distance(distance - LOD_HYSTERESIS);
+		let coarsest = Self::for_distance(distance + LOD_HYSTERESIS);
+		
+		if current < finest {
+			finest
+		} else if current > coarsest {
+			coarsest
+		} else {
+			current
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A downsampled chunk, with a border of one voxel on every side.
+///
+/// The border voxels are sampled from the single layer of neighbouring blocks in a `ChunkWithEdge`;
+/// they are used to generate skirts, not for face culling.
+pub struct LodVolume {
+	pub lod: ChunkLod,
+	pub air: BlockState,
+	
+	/// The voxels, indexed `[y][z][x]` with an offset of +1 for the border.
+	pub cells: Vec<BlockState>,
+}
+
+impl LodVolume {
+	/// The number of voxels along each axis, including the border.
+	#[inline]
+	pub fn stride(&self) -> usize {
+		self.lod.size() + 2
+	}
+	
+	/// Returns the voxel at the given position; `-1` and `size` address the border.
+	#[inline]
+	pub fn get(&self, x: isize, y: isize, z: isize) -> BlockState {
+		let stride = self.stride() as isize;
+		self.cells[(((y+1) * stride + (z+1)) * stride + (x+1)) as usize]
+	}
+	
+	#[inline]
+	fn set(&mut self, x: isize, y: isize, z: isize, state: BlockState) {
+		let stride = self.stride() as isize;
+		self.cells[(((y+1) * stride + (z+1)) * stride + (x+1)) as usize] = state;
+	}
+	
+	/// Returns `true` if the given position lies inside the chunk, i.e. not in the border.
+	#[inline]
+	pub fn is_inner(&self, x: isize, y: isize, z: isize) -> bool {
+		let size = self.lod.size() as isize;
+		x >= 0 && y >= 0 && z >= 0 && x < size && y < size && z < size
+	}
+}
+
+/// Picks a single block from a list of samples, according to the sampling method.
+///
+/// The samples must be ordered bottom to top.
+fn sample_blocks(samples: &[BlockState], air: BlockState, sampling: LodSampling) -> BlockState {
+	match sampling {
+		LodSampling::Majority => {
+			let mut counts: smallvec::SmallVec<[(BlockState, usize); 4]> = smallvec![];
+			let mut solid = 0;
+			
+			for sample in samples.iter().filter(|sample| **sample != air) {
+				solid += 1;
+				match counts.iter_mut().find(|(state, _)| state == sample) {
+					Some((_, count)) => *count += 1,
+					None => counts.push((*sample, 1)),
+				}
+			}
+			
+			if solid * 2 < samples.len() {
+				return air
+			}
+			
+			counts.iter()
+				.max_by_key(|(_, count)| *count)
+				.map(|(state, _)| *state)
+				.unwrap_or(air)
+		},
+		LodSampling::Surface => {
+			samples.iter()
+				.rev()
+				.find(|sample| **sample != air)
+				.cloned()
+				.unwrap_or(air)
+		}
+	}
+}
+
+/// Downsamples the given chunk to the given level of detail.
+pub fn downsample_chunk(block_data: &ChunkWithEdge, air: BlockState, lod: ChunkLod, sampling: LodSampling) -> LodVolume {
+	let scale = lod.scale();
+	let size = lod.size();
+	let stride = size + 2;
+	
+	let mut volume = LodVolume {
+		lod,
+		air,
+		cells: vec![air; stride * stride * stride],
+	};
+	
+	let mut samples: Vec<BlockState> = Vec::with_capacity(scale * scale * scale);
+	
+	// --- Inner voxels: Combine all the blocks of each voxel.
+	for cy in 0..size {
+		for cz in 0..size {
+			for cx in 0..size {
+				samples.clear();
+				
+				for y in cy*scale..(cy+1)*scale {
+					for z in cz*scale..(cz+1)*scale {
+						for x in cx*scale..(cx+1)*scale {
+							samples.push(block_data[y+1][z+1][x+1]);
+						}
+					}
+				}
+				
+				let state = sample_blocks(&samples, air, sampling);
+				volume.set(cx as isize, cy as isize, cz as isize, state);
+			}
+		}
+	}
+	
+	// --- Border voxels: Combine the patch of edge blocks in front of each voxel on the chunk faces.
+	// The edge is only one block thick, so majority sampling would throw away almost everything.
+	let edge = CHUNK_SIZE + 1;
+	let border = size as isize;
+	
+	for a in 0..size {
+		for b in 0..size {
+			let mut patches: [Vec<BlockState>; 6] = Default::default();
+			
+			for i in a*scale+1..(a+1)*scale+1 {
+				for j in b*scale+1..(b+1)*scale+1 {
+					// (a, b) is (y, z) for the X faces, (z, x) for the Y faces and (y, x) for the Z faces.
+					patches[0].push(block_data[i][j][edge]);
+					patches[1].push(block_data[i][j][0]);
+					patches[2].push(block_data[edge][i][j]);
+					patches[3].push(block_data[0][i][j]);
+					patches[4].push(block_data[i][edge][j]);
+					patches[5].push(block_data[i][0][j]);
+				}
+			}
+			
+			let mut sampled = [air; 6];
+			for (face, patch) in patches.iter().enumerate() {
+				sampled[face] = sample_blocks(patch, air, LodSampling::Surface);
+			}
+			
+			let (a, b) = (a as isize, b as isize);
+			volume.set(border, a, b, sampled[0]);
+			volume.set(-1, a, b, sampled[1]);
+			volume.set(b, border, a, sampled[2]);
+			volume.set(b, -1, a, sampled[3]);
+			volume.set(b, a, border, sampled[4]);
+			volume.set(b, a, -1, sampled[5]);
+		}
+	}
+	
+	volume
+}
+
+/// Generates the mesh for a downsampled chunk.
+///
+/// Every solid voxel is rendered as a block scaled up to the size of the voxel.
+/// Faces on the border of the chunk are never culled, since the neighbouring chunk may be at another level of detail.
+///
+/// To cover the cracks between levels, a skirt is generated wherever an empty voxel on the border of the chunk
+/// faces a solid patch of the neighbouring chunk: The face of the neighbour pointing into this chunk.
+pub fn mesh_lod_volume(
+	volume: &LodVolume,
+	chunk_pos: &ChunkCoord,
+	static_bakery: &StaticBlockBakery,
+	vertices: &mut [Vec<ChunkMeshVertex>; BLOCK_RENDER_LAYERS]
+) {
+	let size = volume.lod.size() as isize;
+	let scale = volume.lod.scale() as f32;
+	let air = volume.air;
+	
+	// The neighbours of a voxel are left as air, so connected models always render unconnected.
+	let mut context = BakeryContext::new(air);
+	
+	let (origin_x, origin_y, origin_z) = chunk_pos.to_block_coord_tuple();
+	
+	let mut emit = |context: &BakeryContext, state: &BlockState, x: isize, y: isize, z: isize| {
+		let offset = (x as f32 * scale, y as f32 * scale, z as f32 * scale);
+		
+		// Voxels are tinted like the block in their lower corner.
+		let step = volume.lod.scale() as BlockDim;
+		let pos = BlockCoord::new(
+			origin_x + x as BlockDim * step,
+			origin_y + y as BlockDim * step,
+			origin_z + z as BlockDim * step
+		);
+		
+		static_bakery.render_block(context, state, &mut |face| {
+			let tint = static_bakery.get_tint(state, &pos, face);
+			let vertices = &mut vertices[face.layer.index()];
+			let mut quad = [face.a, face.b, face.c, face.d];
+			for vertex in quad.iter_mut() {
+				vertex.x *= scale;
+				vertex.y *= scale;
+				vertex.z *= scale;
+			}
+			
+			// Repeat the texture across the voxel, keeping the texel density of full-detail blocks.
+			vertices.extend_from_slice(&ChunkMeshVertex::new_quad(&quad, 0.0, &offset, &tint, volume.lod as u32));
+		});
+	};
+	
+	// --- Solid voxels inside the chunk.
+	let is_solid = |x: isize, y: isize, z: isize, state: &BlockState| {
+		if !volume.is_inner(x, y, z) {
+			return false
+		}
+		
+		// Like full blocks, non-opaque voxels only hide voxels of the same type.
+		let neighbour = volume.get(x, y, z);
+		neighbour != air && (static_bakery.is_opaque(&neighbour) || neighbour == *state)
+	};
+	
+	for y in 0..size {
+		for z in 0..size {
+			for x in 0..size {
+				let state = volume.get(x, y, z);
+				
+				if state == air {
+					continue;
+				}
+				
+				context.set_occlusion(
+					is_solid(x+1, y, z, &state),
+					is_solid(x, y+1, z, &state),
+					is_solid(x, y, z+1, &state),
+					is_solid(x-1, y, z, &state),
+					is_solid(x, y-1, z, &state),
+					is_solid(x, y, z-1, &state),
+					true
+				);
+				
+				emit(&context, &state, x, y, z);
+			}
+		}
+	}
+	
+	// --- Skirts: Neighbouring voxels on the border, seen through empty voxels of this chunk.
+	for a in 0..size {
+		for b in 0..size {
+			let skirts = [
+				((size, a, b), (size-1, a, b), Face::NegativeX),
+				((-1, a, b), (0, a, b), Face::PositiveX),
+				((b, size, a), (b, size-1, a), Face::NegativeY),
+				((b, -1, a), (b, 0, a), Face::PositiveY),
+				((b, a, size), (b, a, size-1), Face::NegativeZ),
+				((b, a, -1), (b, a, 0), Face::PositiveZ),
+			];
+			
+			for ((x, y, z), (ix, iy, iz), face) in skirts.iter().cloned() {
+				let state = volume.get(x, y, z);
+				
+				if state == air || volume.get(ix, iy, iz) != air {
+					continue;
+				}
+				
+				// Only the face pointing into this chunk is wanted.
+				context.occlusion = [FACE_MASK_FULL; 8];
+				context.occlusion[face.uid()] = FACE_MASK_EMPTY;
+				
+				emit(&context, &state, x, y, z);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn states(blocks: &BlocksRef) -> (BlockState, BlockState) {
+		(
+			blocks.get_block_by_name_unchecked("air").get_default_state(),
+			blocks.get_block_by_name_unchecked("adm").get_default_state(),
+		)
+	}
+	
+	fn filled(state: BlockState) -> ChunkWithEdge {
+		Box::new([[[state; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2])
+	}
+	
+	#[test]
+	fn select_switches_past_the_hysteresis() {
+		let threshold = LOD_DISTANCES[0];
+		
+		// Moving away: Full detail is kept until the distance is past the threshold by the hysteresis.
+		assert_eq!(ChunkLod::select(ChunkLod::Full, threshold + LOD_HYSTERESIS - 0.5), ChunkLod::Full);
+		assert_eq!(ChunkLod::select(ChunkLod::Full, threshold + LOD_HYSTERESIS + 0.5), ChunkLod::Half);
+		
+		// Moving closer: The coarser level is kept until the distance is short of the threshold by the hysteresis.
+		assert_eq!(ChunkLod::select(ChunkLod::Half, threshold - LOD_HYSTERESIS + 0.5), ChunkLod::Half);
+		assert_eq!(ChunkLod::select(ChunkLod::Half, threshold - LOD_HYSTERESIS - 0.5), ChunkLod::Full);
+		
+		// Far off, several levels are skipped at once.
+		assert_eq!(ChunkLod::select(ChunkLod::Full, 1000.0), ChunkLod::Eighth);
+		assert_eq!(ChunkLod::select(ChunkLod::Eighth, 0.0), ChunkLod::Full);
+	}
+	
+	#[test]
+	fn select_does_not_flap_at_a_threshold() {
+		let threshold = LOD_DISTANCES[1];
+		
+		for start in [ChunkLod::Half, ChunkLod::Quarter].iter() {
+			let mut lod = *start;
+			for step in 0..100 {
+				let wobble = if step % 2 == 0 { -LOD_HYSTERESIS } else { LOD_HYSTERESIS } * 0.9;
+				lod = ChunkLod::select(lod, threshold + wobble);
+				assert_eq!(lod, *start);
+			}
+		}
+	}
+	
+	#[test]
+	fn downsample_majority() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let (air, solid) = states(&blocks);
+		
+		// The lower half is solid, and one single block sticks out above it.
+		let mut block_data = filled(air);
+		for y in 0..CHUNK_SIZE / 2 {
+			for z in 0..CHUNK_SIZE {
+				for x in 0..CHUNK_SIZE {
+					block_data[y + 1][z + 1][x + 1] = solid;
+				}
+			}
+		}
+		block_data[CHUNK_SIZE / 2 + 1][1][1] = solid;
+		
+		let volume = downsample_chunk(&block_data, air, ChunkLod::Half, LodSampling::Majority);
+		let size = ChunkLod::Half.size() as isize;
+		
+		for y in 0..size {
+			for z in 0..size {
+				for x in 0..size {
+					let expected = if y < size / 2 { solid } else { air };
+					assert!(volume.get(x, y, z) == expected, "voxel {} {} {}", x, y, z);
+				}
+			}
+		}
+	}
+	
+	#[test]
+	fn downsample_surface_keeps_thin_layers() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let (air, solid) = states(&blocks);
+		
+		// A single layer of ground, and a single block in the neighbouring chunk beyond +X.
+		let mut block_data = filled(air);
+		for z in 0..CHUNK_SIZE {
+			for x in 0..CHUNK_SIZE {
+				block_data[1][z + 1][x + 1] = solid;
+			}
+		}
+		block_data[CHUNK_SIZE][CHUNK_SIZE][CHUNK_SIZE + 1] = solid;
+		
+		let majority = downsample_chunk(&block_data, air, ChunkLod::Quarter, LodSampling::Majority);
+		assert!(majority.get(0, 0, 0) == air);
+		
+		let surface = downsample_chunk(&block_data, air, ChunkLod::Quarter, LodSampling::Surface);
+		let size = ChunkLod::Quarter.size() as isize;
+		assert!(surface.get(0, 0, 0) == solid);
+		assert!(surface.get(size - 1, 0, size - 1) == solid);
+		assert!(surface.get(0, 1, 0) == air);
+		
+		// The border takes the neighbouring block, in front of the voxel it belongs to.
+		assert!(surface.get(size, size - 1, size - 1) == solid);
+		assert!(surface.get(size, 0, 0) == air);
+	}
+	
+	#[test]
+	fn skirts_face_into_empty_chunks() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let (air, solid) = states(&blocks);
+		let bakery = StaticBlockBakery::from_includes(&blocks);
+		
+		// An empty chunk, next to a solid chunk beyond +X.
+		let mut block_data = filled(air);
+		for y in 0..CHUNK_SIZE + 2 {
+			for z in 0..CHUNK_SIZE + 2 {
+				block_data[y][z][CHUNK_SIZE + 1] = solid;
+			}
+		}
+		
+		let lod = ChunkLod::Half;
+		let volume = downsample_chunk(&block_data, air, lod, lod.sampling());
+		
+		let mut vertices: [Vec<ChunkMeshVertex>; BLOCK_RENDER_LAYERS] = Default::default();
+		mesh_lod_volume(&volume, &ChunkCoord::new_from_chunk(0, 0, 0), &bakery, &mut vertices);
+		
+		// One skirt quad per border voxel, all of them on the +X face of the chunk.
+		let all: Vec<&ChunkMeshVertex> = vertices.iter().flat_map(|layer| layer.iter()).collect();
+		assert_eq!(all.len(), lod.size() * lod.size() * 4);
+		
+		for vertex in all {
+			assert_eq!(vertex.get_position().0, CHUNK_SIZE as f32);
+		}
+		
+		// Nothing is in front of a full chunk.
+		let block_data = filled(solid);
+		let volume = downsample_chunk(&block_data, air, lod, lod.sampling());
+		
+		let mut vertices: [Vec<ChunkMeshVertex>; BLOCK_RENDER_LAYERS] = Default::default();
+		mesh_lod_volume(&volume, &ChunkCoord::new_from_chunk(0, 0, 0), &bakery, &mut vertices);
+		
+		// Only the border faces of the chunk itself remain: Six sides of `size²` voxel faces each.
+		let quads: usize = vertices.iter().map(|layer| layer.len() / 4).sum();
+		assert_eq!(quads, 6 * lod.size() * lod.size());
+	}
+}
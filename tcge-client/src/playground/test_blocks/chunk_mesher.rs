@@ -1,6 +1,5 @@
 use super::*;
-use crate::render;
-use half::f16;
+use blocks::Face;
 
 /// The graphical state of a chunk.
 pub enum ChunkMeshState {
@@ -11,117 +10,160 @@ pub enum ChunkMeshState {
 	Meshed(ChunkMesh),
 }
 
-pub type ChunkMeshRaw = (gl::types::GLuint, gl::types::GLsizei);
+pub type ChunkMeshRaw = (ChunkBufferSlot, ChunkMeshRanges, ChunkCoord);
+
+/// The largest number of quads a 16-bit quad index buffer can address.
+pub const QUAD_INDEX_LIMIT: usize = (std::u16::MAX as usize + 1) / 4;
+
+/// How far (in blocks) the camera must move before the translucent quads of a chunk are sorted again.
+pub const TRANSLUCENT_RESORT_DISTANCE: f32 = 1.0;
+
+/// A range of quads within a chunk mesh, drawable with a single 16-bit quad index buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChunkMeshRange {
+	/// The first vertex of the range; added to every index by OpenGL.
+	pub base_vertex: gl::types::GLint,
+	
+	/// The number of indices to draw.
+	pub count: gl::types::GLsizei,
+}
+
+pub type ChunkMeshRanges = smallvec::SmallVec<[ChunkMeshRange; 1]>;
+
+/// Splits the given number of quads into ranges of at most `limit` quads each.
+///
+/// Every range starts at a multiple of `limit` quads, so the same quad index buffer can be used for all of them.
+pub fn split_quad_ranges(quads: usize, limit: usize) -> ChunkMeshRanges {
+	let mut ranges = ChunkMeshRanges::new();
+	let mut start = 0;
+	
+	while start < quads {
+		let length = std::cmp::min(quads - start, limit);
+		ranges.push(ChunkMeshRange {
+			base_vertex: (start * 4) as gl::types::GLint,
+			count: (length * 6) as gl::types::GLsizei,
+		});
+		start += length;
+	}
+	
+	ranges
+}
 
 /// The graphical representation of a chunk.
-/// Really just a bag of OpenGL Object-Handles.
+///
+/// The vertices of all render layers share one slot of the `ChunkBufferPool`, one layer after another;
+/// the slot must be freed when the mesh is replaced.
+/// The translucent quads are also kept on the CPU, so they can be sorted as the camera moves.
 pub struct ChunkMesh {
-	gl: gl::Gl,
-	descriptor: gl::types::GLuint,
-	vertices: render::BufferObject,
-	count: gl::types::GLsizei,
+	pos: ChunkCoord,
+	slot: ChunkBufferSlot,
+	ranges: [ChunkMeshRanges; BLOCK_RENDER_LAYERS],
+	
+	/// The translucent vertices, in the order they are currently uploaded in.
+	translucent: Vec<ChunkMeshVertex>,
+	
+	/// The first translucent vertex within the slot.
+	translucent_base: usize,
+	
+	/// The chunk-local camera position the translucent quads were last sorted for.
+	sorted_for: Option<cgmath::Vector3<f32>>,
 }
 
 impl ChunkMesh {
-	pub fn new(gl: &gl::Gl, descriptor: gl::types::GLuint, vertices: render::BufferObject, count: i32) -> Self {
+	pub fn new(
+		pos: &ChunkCoord,
+		slot: ChunkBufferSlot,
+		ranges: [ChunkMeshRanges; BLOCK_RENDER_LAYERS],
+		translucent: Vec<ChunkMeshVertex>,
+		translucent_base: usize
+	) -> Self {
 		Self {
-			gl: gl.clone(),
-			descriptor,
-			vertices,
-			count
+			pos: pos.clone(),
+			slot,
+			ranges,
+			translucent,
+			translucent_base,
+			sorted_for: None,
 		}
 	}
 	
-	pub fn draw(&self) {
-		unsafe {
-			self.gl.BindVertexArray(self.descriptor);
-			self.gl.DrawElements(
-				gl::TRIANGLES,
-				self.count,
-				gl::UNSIGNED_SHORT,
-				0 as *const gl::types::GLvoid
-			);
-		}
+	pub fn draw_later(&self, layer: BlockRenderLayer) -> ChunkMeshRaw {
+		(self.slot, self.ranges[layer.index()].clone(), self.pos)
 	}
 	
-	pub fn draw_later(&self) -> ChunkMeshRaw {
-		(self.descriptor, self.count)
+	/// Returns the slot of the chunk buffer pool the vertices are stored in.
+	pub fn get_slot(&self) -> ChunkBufferSlot {
+		self.slot
 	}
-}
-
-pub fn draw_chunk(gl: &gl::Gl, chunk_mesh_raw: ChunkMeshRaw) {
-	unsafe {
-		let (descriptor, count) = chunk_mesh_raw;
-		gl.BindVertexArray(descriptor);
-		gl.DrawElements(
-			gl::TRIANGLES,
-			count,
-			gl::UNSIGNED_SHORT,
-			0 as *const gl::types::GLvoid
-		);
+	
+	/// Returns `true` if the given layer has any quads.
+	pub fn has_layer(&self, layer: BlockRenderLayer) -> bool {
+		!self.ranges[layer.index()].is_empty()
 	}
-}
-
-impl Drop for ChunkMesh {
-	fn drop(&mut self) {
-		unsafe {
-			self.gl.DeleteBuffers(1, [self.vertices.id].as_ptr());
-			self.gl.DeleteVertexArrays(1, [self.descriptor].as_ptr());
+	
+	/// Returns `true` if the translucent quads should be sorted for the given chunk-local camera position.
+	pub fn needs_sort(&self, camera: &cgmath::Vector3<f32>) -> bool {
+		use cgmath::InnerSpace;
+		
+		if self.translucent.is_empty() {
+			return false
+		}
+		
+		match self.sorted_for {
+			Some(sorted_for) => (camera - sorted_for).magnitude() > TRANSLUCENT_RESORT_DISTANCE,
+			None => true
 		}
 	}
-}
-
-#[derive(Copy, Clone, Debug)]
-#[repr(C, packed)]
-pub struct ChunkMeshVertex {
-	// Geometry
-	pub x: half::f16,
-	pub y: half::f16,
-	pub z: half::f16,
-	
-	// Texture
-	pub u: half::f16,
-	pub v: half::f16,
-	
-	// Normal
-	pub nx: i8,
-	pub ny: i8,
-	pub nz: i8,
-	
-	// AO
-	pub ao: i8,
-}
-
-impl ChunkMeshVertex {
-	// TODO: Use the `From`-trait here instead...
-	pub fn new_from(other: &BakedBlockMeshVertex, ao: f32, offset: &(f32, f32, f32)) -> Self{
-		Self {
-			x: f16::from_f32(other.x + offset.0),
-			y: f16::from_f32(other.y + offset.1),
-			z: f16::from_f32(other.z + offset.2),
-			u: f16::from_f32(other.u),
-			v: f16::from_f32(other.v),
-			nx: (other.nx * 127.0) as i8,
-			ny: (other.ny * 127.0) as i8,
-			nz: (other.nz * 127.0) as i8,
-			ao: (ao * 127.0) as i8
+	
+	/// Sorts the translucent quads far to near, as seen from the given chunk-local camera position, and uploads them.
+	pub fn sort_translucent(&mut self, buffers: &ChunkBufferPool, camera: &cgmath::Vector3<f32>) {
+		let distance = |quad: &[ChunkMeshVertex]| {
+			let mut sum = 0.0;
+			for vertex in quad.iter() {
+				let (x, y, z) = vertex.get_position();
+				let (dx, dy, dz) = (x - camera.x, y - camera.y, z - camera.z);
+				sum += dx * dx + dy * dy + dz * dz;
+			}
+			sum
+		};
+		
+		let mut quads: Vec<(f32, usize)> = self.translucent.chunks(4)
+			.enumerate()
+			.map(|(index, quad)| (distance(quad), index))
+			.collect();
+		
+		quads.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+		
+		let mut sorted = Vec::with_capacity(self.translucent.len());
+		for (_, index) in quads.iter() {
+			sorted.extend_from_slice(&self.translucent[index*4..index*4+4]);
 		}
+		
+		buffers.upload(self.slot, self.translucent_base, &sorted);
+		self.translucent = sorted;
+		self.sorted_for = Some(*camera);
 	}
 }
 
 pub struct MesherThreadState {
-	pub vertices: Vec<ChunkMeshVertex>
+	/// The vertices of each render layer.
+	pub vertices: [Vec<ChunkMeshVertex>; BLOCK_RENDER_LAYERS],
+	pub visibility: ChunkVisibility,
 }
 
 impl MesherThreadState {
 	pub fn new() -> MesherThreadState {
 		MesherThreadState {
-			vertices: vec![]
+			vertices: Default::default(),
+			visibility: ChunkVisibility::all(),
 		}
 	}
 	
 	pub fn reset(&mut self) {
-		self.vertices.clear();
+		for vertices in self.vertices.iter_mut() {
+			vertices.clear();
+		}
+		self.visibility = ChunkVisibility::all();
 	}
 }
 
@@ -145,8 +187,6 @@ pub fn mesh_chunk(
 		.get_block_by_name_unchecked("air")
 		.get_default_state();
 	
-	let (cx, cy, cz) = chunk.pos.to_block_coord_tuple();
-	
 	// --- Local function for fetching blocks quickly...
 	let get_block = |
 		local_x: BlockDim,
@@ -162,7 +202,8 @@ pub fn mesh_chunk(
 		})
 	};
 	
-	let mut context = BakeryContext::new();
+	let mut context = BakeryContext::new(air);
+	let (origin_x, origin_y, origin_z) = chunk.pos.to_block_coord_tuple();
 	
 	let prep_time = common::current_time_nanos_precise() - prep_time;
 	// let mut starts = (start, start);
@@ -188,35 +229,46 @@ pub fn mesh_chunk(
 				
 				non_empty += 1;
 				
-				context.set_occlusion(
-					get_block(x+1, y, z) != air,
-					get_block(x, y+1, z) != air,
-					get_block(x, y, z+1) != air,
-					get_block(x-1, y, z) != air,
-					get_block(x, y-1, z) != air,
-					get_block(x, y, z-1) != air,
-					true
+				// Each neighbour hides the area covered by its side facing this block.
+				context.set_occlusion_masks(
+					static_bakery.get_occlusion(&get_block(x+1, y, z), Face::NegativeX, &block),
+					static_bakery.get_occlusion(&get_block(x, y+1, z), Face::NegativeY, &block),
+					static_bakery.get_occlusion(&get_block(x, y, z+1), Face::NegativeZ, &block),
+					static_bakery.get_occlusion(&get_block(x-1, y, z), Face::PositiveX, &block),
+					static_bakery.get_occlusion(&get_block(x, y-1, z), Face::PositiveY, &block),
+					static_bakery.get_occlusion(&get_block(x, y, z-1), Face::PositiveZ, &block),
+					FACE_MASK_EMPTY
 				);
 				
+				for dy in -1..=1 {
+					for dz in -1..=1 {
+						for dx in -1..=1 {
+							context.set_neighbour(dx, dy, dz, get_block(x + dx as BlockDim, y + dy as BlockDim, z + dz as BlockDim));
+						}
+					}
+				}
+				
 				// length.0 += common::current_time_nanos_precise() - starts.0;
 				
 				// starts.1 = common::current_time_nanos_precise();
-				let cbx = x + cx;
-				let cby = y + cy;
-				let cbz = z + cz;
-				let offset = (cbx as f32, cby as f32, cbz as f32);
+				let offset = (x as f32, y as f32, z as f32);
+				
+				let pos = BlockCoord::new(origin_x + x, origin_y + y, origin_z + z);
 				
 				static_bakery.render_block(&context, &block, &mut |face| {
-					vertices.push(ChunkMeshVertex::new_from(&face.a, 0.0, &offset));
-					vertices.push(ChunkMeshVertex::new_from(&face.b, 0.0, &offset));
-					vertices.push(ChunkMeshVertex::new_from(&face.c, 0.0, &offset));
-					vertices.push(ChunkMeshVertex::new_from(&face.d, 0.0, &offset));
+					let tint = static_bakery.get_tint(&block, &pos, face);
+					let vertices = &mut vertices[face.layer.index()];
+					let quad = [face.a, face.b, face.c, face.d];
+					vertices.extend_from_slice(&ChunkMeshVertex::new_quad(&quad, 0.0, &offset, &tint, 0));
 				});
 				// length.1 += common::current_time_nanos_precise() - starts.1;
 			}
 		}
 	}
 	
+	// --- Determine which faces of the chunk can be seen through each other.
+	mesher.visibility = compute_visibility(block_data, |block| static_bakery.is_opaque(&block));
+	
 	let duration = common::current_time_nanos_precise() - start;
 	if duration > 100 {
 		trace!("Took {} ({:.0}% pre, {:.0}% occ, {:.0}% cpy) to mesh chunk {} ({} solids)",
@@ -232,87 +284,95 @@ pub fn mesh_chunk(
 	// return upload(gl, chunk, &vertices, &qindex);
 }
 
-pub fn upload(gl: &gl::Gl, chunk_pos: &ChunkCoord, mesh_data: &Vec<ChunkMeshVertex>, quad_index: &render::BufferObjectRef) -> ChunkMeshState {
-	// Don't upload empty meshes.
-	if mesh_data.len() == 0 {
-		return ChunkMeshState::Empty
+/// Meshes the given chunk at the given level of detail.
+///
+/// The visibility of the chunk is always computed from the full-detail blocks.
+pub fn mesh_chunk_lod(
+	mesher: &mut MesherThreadState,
+	blocks: BlocksRef,
+	static_bakery: &StaticBlockBakery,
+	chunk: &Chunk,
+	block_data: &ChunkWithEdge,
+	lod: ChunkLod
+) {
+	if lod == ChunkLod::Full {
+		return mesh_chunk(mesher, blocks, static_bakery, chunk, block_data);
 	}
 	
-	let vertex_count = mesh_data.len() / 4 * 6;
+	let start = common::current_time_nanos_precise();
+	
+	mesher.reset();
 	
-	let vertex_buffer = render::BufferObject::buffer_data(gl, gl::ARRAY_BUFFER, gl::STATIC_DRAW, mesh_data);
+	let air = blocks
+		.get_block_by_name_unchecked("air")
+		.get_default_state();
 	
-	let mut vao: gl::types::GLuint = 0;
-	unsafe {
-		gl.GenVertexArrays(1, &mut vao);
-		gl.BindVertexArray(vao);
-		gl.BindBuffer(gl::ARRAY_BUFFER, vertex_buffer.id);
-		
-		// Bind the index buffer
-		gl.BindBuffer(quad_index.target, quad_index.id);
-		
-		let stride = (5 * std::mem::size_of::<f16>()) as gl::types::GLsizei + 3 + 1;
-		
-		gl.EnableVertexAttribArray(0);
-		gl.VertexAttribPointer(
-			0, // attribute location
-			3, // sub-element count
-			gl::HALF_FLOAT, // sub-element type
-			gl::FALSE, // sub-element normalization
-			stride,
-			(0 * std::mem::size_of::<f16>()) as *const gl::types::GLvoid
+	let volume = downsample_chunk(block_data, air, lod, lod.sampling());
+	mesh_lod_volume(&volume, &chunk.pos, static_bakery, &mut mesher.vertices);
+	
+	mesher.visibility = compute_visibility(block_data, |block| static_bakery.is_opaque(&block));
+	
+	let duration = common::current_time_nanos_precise() - start;
+	if duration > 100 {
+		trace!("Took {} to mesh chunk {} at {:?}",
+			common::profiler::Nanosec::new(duration),
+			chunk.pos,
+			lod
 		);
+	}
+}
+
+/// Uploads the vertices of all render layers into a single slot of the chunk buffer pool, one layer after another.
+pub fn upload(buffers: &mut ChunkBufferPool, chunk_pos: &ChunkCoord, mesh_data: &[Vec<ChunkMeshVertex>; BLOCK_RENDER_LAYERS]) -> ChunkMeshState {
+	let total: usize = mesh_data.iter().map(|vertices| vertices.len()).sum();
+	
+	// Don't upload empty meshes.
+	if total == 0 {
+		return ChunkMeshState::Empty
+	}
+	
+	// Meshes with more quads than the index buffer can address are drawn in several ranges.
+	let quad_limit = buffers.get_quad_index().items / 6;
+	let mut ranges: [ChunkMeshRanges; BLOCK_RENDER_LAYERS] = Default::default();
+	let mut base = 0;
+	let mut translucent_base = 0;
+	
+	for layer in BlockRenderLayer::ALL.iter() {
+		let vertices = &mesh_data[layer.index()];
 		
-		gl.EnableVertexAttribArray(1);
-		gl.VertexAttribPointer(
-			1, // attribute location
-			2, // sub-element count
-			gl::HALF_FLOAT, // sub-element type
-			gl::FALSE, // sub-element normalization
-			stride,
-			(3 * std::mem::size_of::<f16>()) as *const gl::types::GLvoid
-		);
+		ranges[layer.index()] = split_quad_ranges(vertices.len() / 4, quad_limit);
+		for range in ranges[layer.index()].iter_mut() {
+			range.base_vertex += base as gl::types::GLint;
+		}
 		
-		gl.EnableVertexAttribArray(2);
-		gl.VertexAttribPointer(
-			2, // attribute location
-			3, // sub-element count
-			gl::BYTE, // sub-element type
-			gl::TRUE, // sub-element normalization
-			stride,
-			(5 * std::mem::size_of::<f16>()) as *const gl::types::GLvoid
-		);
+		if ranges[layer.index()].len() > 1 {
+			debug!("Chunk {} has {} {:?} quads; splitting into {} draw ranges.", chunk_pos, vertices.len() / 4, layer, ranges[layer.index()].len());
+		}
 		
-		gl.EnableVertexAttribArray(3);
-		gl.VertexAttribPointer(
-			3, // attribute location
-			1, // sub-element count
-			gl::BYTE, // sub-element type
-			gl::TRUE, // sub-element normalization
-			stride,
-			((5 * std::mem::size_of::<f16>()) + 3) as *const gl::types::GLvoid
-		);
+		if *layer == BlockRenderLayer::Translucent {
+			translucent_base = base;
+		}
 		
-		gl.BindVertexArray(0);
+		base += vertices.len();
 	}
 	
-	let label = format!("Chunk({}, {}, {})", chunk_pos.x, chunk_pos.y, chunk_pos.z);
+	let slot = buffers.allocate(total);
 	
-	gl.label_object(
-		gl::VERTEX_ARRAY, vao,
-		&format!("{} Descriptor", label)
-	);
+	let mut offset = 0;
+	for vertices in mesh_data.iter() {
+		buffers.upload(slot, offset, vertices);
+		offset += vertices.len();
+	}
 	
-	gl.label_object(
-		gl::BUFFER, vertex_buffer.id,
-		&format!("{} Geometry", label)
-	);
+	// Translucent quads are sorted again whenever the camera moves far enough.
+	let translucent = &mesh_data[BlockRenderLayer::Translucent.index()];
 	
 	ChunkMeshState::Meshed(ChunkMesh::new(
-		gl,
-		vao,
-		vertex_buffer,
-		vertex_count as i32
+		chunk_pos,
+		slot,
+		ranges,
+		translucent.clone(),
+		translucent_base
 	))
 }
 
@@ -326,3 +386,70 @@ fn lerp_trilinear(x: f32, y: f32, z: f32, corners: &[f32; 8]) -> f32 {
 	(1.0 - x) * y * z * corners[6] +
 			x * y * z * corners[7]
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn split_quad_ranges_covers_all_quads() {
+		assert!(split_quad_ranges(0, QUAD_INDEX_LIMIT).is_empty());
+		
+		let ranges = split_quad_ranges(QUAD_INDEX_LIMIT, QUAD_INDEX_LIMIT);
+		assert_eq!(ranges.len(), 1);
+		
+		let ranges = split_quad_ranges(QUAD_INDEX_LIMIT + 1, QUAD_INDEX_LIMIT);
+		assert_eq!(ranges.len(), 2);
+		assert_eq!(ranges[1].base_vertex, (QUAD_INDEX_LIMIT * 4) as gl::types::GLint);
+		assert_eq!(ranges[1].count, 6);
+	}
+	
+	#[test]
+	fn checkerboard_is_split_below_the_index_limit() {
+		let blocks = blocks::Blocks::new().to_ref();
+		let bakery = StaticBlockBakery::from_includes(&blocks);
+		
+		let air = blocks.get_block_by_name_unchecked("air").get_default_state();
+		let adm = blocks.get_block_by_name_unchecked("adm").get_default_state();
+		
+		// The worst case: No two solid blocks touch, so every block shows all six faces.
+		let mut block_data: ChunkWithEdge = Box::new([[[air; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]);
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				for x in 0..CHUNK_SIZE {
+					if (x + y + z) % 2 == 0 {
+						block_data[y + 1][z + 1][x + 1] = adm;
+					}
+				}
+			}
+		}
+		
+		let chunk = Chunk::new(&blocks, ChunkCoord::new_from_chunk(0, 0, 0), air);
+		let mut mesher = MesherThreadState::new();
+		mesh_chunk(&mut mesher, blocks.clone(), &bakery, &chunk, &block_data);
+		
+		let vertices = &mesher.vertices[bakery.get_layer(&adm).index()];
+		assert_eq!(vertices.len() % 4, 0);
+		
+		let quads = vertices.len() / 4;
+		assert_eq!(quads, CHUNK_VOLUME / 2 * 6);
+		assert!(quads > QUAD_INDEX_LIMIT);
+		
+		let ranges = split_quad_ranges(quads, QUAD_INDEX_LIMIT);
+		assert!(ranges.len() > 1);
+		
+		let mut next_vertex = 0;
+		let mut indices = 0;
+		for range in ranges.iter() {
+			let range_vertices = range.count as usize / 6 * 4;
+			assert!(range_vertices <= std::u16::MAX as usize + 1);
+			assert_eq!(range.base_vertex as usize, next_vertex);
+			
+			next_vertex += range_vertices;
+			indices += range.count as usize;
+		}
+		
+		assert_eq!(next_vertex, vertices.len());
+		assert_eq!(indices, quads * 6);
+	}
+}
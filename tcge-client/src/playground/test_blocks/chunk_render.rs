@@ -1,21 +1,60 @@
 use super::*;
-use crate::playground::test_blocks::BlocksMaterialError;
-use crate::render::{BufferObject, BufferObjectRef};
+use rustc_hash::FxHashSet;
+use crate::render::{BufferObject, Frustum, MaterialError};
+
+/// Per-frame statistics of the chunk renderer.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ChunkRenderStats {
+	/// Number of non-empty chunk meshes that passed frustum- and visibility-culling and were drawn.
+	pub visible: usize,
+	
+	/// Number of non-empty chunk meshes.
+	pub total: usize,
+	
+	/// Number of pages of the chunk buffer pool.
+	pub buffer_pages: usize,
+	
+	/// Number of vertices in use, and room for vertices in total, across all pages.
+	pub buffer_used: usize,
+	pub buffer_capacity: usize,
+	
+	/// Fragmentation of the most fragmented page, from 0 to 1.
+	pub buffer_fragmentation: f32,
+}
+
+/// The alpha below which pixels of cutout faces are discarded.
+pub const CUTOUT_ALPHA: f32 = 0.5;
+
+/// The graphical state of a single chunk, as tracked by the chunk renderer.
+struct ChunkRenderEntry {
+	/// The time the chunk was last meshed at.
+	time: u128,
+	mesh: ChunkMeshState,
+	visibility: ChunkVisibility,
+	
+	/// The level of detail the chunk was last meshed at.
+	lod: ChunkLod,
+}
 
 pub struct ChunkRenderManager {
 	// Static
 	gl: gl::Gl,
 	blocks: BlocksRef,
 	material: BlocksMaterial,
+	animator: BlockTextureAnimator,
 	bakery: StaticBlockBakeryRef,
-	quad_index: BufferObjectRef,
 	
 	// Dynamic
-	chunks: FxHashMap<ChunkCoord, (u128, ChunkMeshState)>,
+	buffers: ChunkBufferPool,
+	chunks: FxHashMap<ChunkCoord, ChunkRenderEntry>,
 	mesher: MesherThreadState,
 	
 	// Per Frame
-	calls: Vec<(gl::types::GLuint, gl::types::GLsizei)>,
+	calls: Vec<ChunkMeshRaw>,
+	visible: Vec<ChunkCoord>,
+	visited: FxHashSet<ChunkCoord>,
+	queue: std::collections::VecDeque<(ChunkCoord, Option<blocks::Face>, u8)>,
+	stats: ChunkRenderStats,
 }
 
 impl ChunkRenderManager {
@@ -24,10 +63,11 @@ impl ChunkRenderManager {
 		res: &resources::Resources,
 		blocks: &BlocksRef,
 		bakery: StaticBlockBakeryRef
-	) -> Result<Self, BlocksMaterialError> {
+	) -> Result<Self, MaterialError> {
 		
-		let material = BlocksMaterial::new(gl, res)?;
-		let quad_index = Self::generate_quad_indices(gl, 4096).to_ref();
+		let material = BlocksMaterial::new(gl, res, bakery.get_textures())?;
+		let animator = BlockTextureAnimator::new(bakery.get_textures().animations.clone());
+		let quad_index = Self::generate_quad_indices(gl, QUAD_INDEX_LIMIT).to_ref();
 		
 		gl.label_object(
 			gl::BUFFER,
@@ -35,20 +75,29 @@ impl ChunkRenderManager {
 			"Quads Index"
 		);
 		
+		let buffers = ChunkBufferPool::new(gl, quad_index);
+		
 		Ok(Self {
 			gl: gl.clone(),
 			blocks: blocks.clone(),
 			material,
+			animator,
 			bakery,
-			quad_index,
+			buffers,
 			chunks: FxHashMap::default(),
 			mesher: MesherThreadState::new(),
 			calls: vec![],
+			visible: vec![],
+			visited: FxHashSet::default(),
+			queue: std::collections::VecDeque::new(),
+			stats: ChunkRenderStats::default(),
 		})
 	}
 	
 	pub fn generate_quad_indices(gl: &gl::Gl, max: usize) -> BufferObject {
-		let mut indices: Vec<u16> = vec![];
+		assert!(max <= QUAD_INDEX_LIMIT, "Cannot address {} quads with 16-bit indices.", max);
+		
+		let mut indices: Vec<u16> = Vec::with_capacity(max * 6);
 		for i in 0..max {
 			// A: a b d
 			// B: b c d
@@ -68,91 +117,369 @@ impl ChunkRenderManager {
 		)
 	}
 	
-	pub fn render(&mut self, chunks: &ChunkStorage, transform: &cgmath::Matrix4<f32>) {
+	/// Rebuilds the block bakery and the material, if they read any of the `changed` resources; or always, if `None`.
+	///
+	/// A new bakery causes every chunk to be meshed again.
+	/// If anything fails to load, the old bakery and material are kept.
+	pub fn reload(&mut self, res: &resources::Resources, changed: Option<&[resources::ResourceLocation]>) {
+		let bakery_affected = changed.map_or(true, |changed| {
+			changed.iter().any(|location| StaticBlockBakery::is_affected_by(location))
+		});
+		
+		if bakery_affected {
+			info!("Reloading block models and textures...");
+			
+			let bakery = match StaticBlockBakery::new(res, &self.blocks) {
+				Ok(bakery) => bakery,
+				Err(_) => {
+					error!("Failed to reload the block bakery, keeping the old one.");
+					return;
+				}
+			};
+			
+			let material = match BlocksMaterial::new(&self.gl, res, bakery.get_textures()) {
+				Ok(material) => material,
+				Err(err) => {
+					error!("Failed to reload 'Blocks' material, keeping the old one: {}", err);
+					return;
+				}
+			};
+			
+			self.animator = BlockTextureAnimator::new(bakery.get_textures().animations.clone());
+			self.material = material;
+			self.bakery = std::rc::Rc::new(bakery);
+			
+			for entry in self.chunks.values_mut() {
+				entry.time = 0;
+			}
+			
+			return;
+		}
+		
+		if changed.map_or(false, |changed| self.material.shader.is_affected_by(changed)) {
+			info!("Reloading 'Blocks' material...");
+			
+			match BlocksMaterial::new(&self.gl, res, self.bakery.get_textures()) {
+				Ok(material) => self.material = material,
+				Err(err) => error!("Failed to reload 'Blocks' material, keeping the old one: {}", err),
+			}
+		}
+	}
+	
+	/// Advances the animated block textures by one tick.
+	pub fn tick(&mut self) {
+		self.animator.tick_and_upload(&self.material.textures);
+	}
+	
+	/// Meshes, uploads and draws the given chunks.
+	///
+	/// The `transform` must NOT contain the camera translation; instead, the `camera` position is
+	/// subtracted from each chunk origin on the CPU, so that vertex positions stay small on the GPU.
+	///
+	/// Chunks outside of the view-frustum (derived from the `transform`) are skipped,
+	/// as are chunks that cannot be seen from the camera through the visibility graph.
+	pub fn render(&mut self, chunks: &ChunkStorage, transform: &cgmath::Matrix4<f32>, camera: &cgmath::Vector3<f32>) {
 		self.gl.push_debug("Chunks");
 		
+		let frustum = Frustum::from_matrix(transform);
+		self.stats = ChunkRenderStats::default();
+		
 		use cgmath::InnerSpace;
 		let sun = cgmath::Vector3::new(0.9, 1.0, 0.7).normalize();
 		
-		self.material.shader.set_used();
-		self.material.shader.set_uniform_matrix4(self.material.uniform_matrix, transform);
-		self.material.shader.set_uniform_vector3(self.material.uniform_sun, &sun);
-		self.material.shader.set_uniform_sampler(self.material.uniform_atlas, 0);
-		self.material.atlas.set_used();
+		self.material.shader.bind();
+		self.material.shader.program.set_uniform_matrix4(self.material.uniform_matrix, transform);
+		self.material.shader.program.set_uniform_vector3(self.material.uniform_sun, &sun);
+		self.material.textures.set_used();
 		
 		self.gl.push_debug("Chunk-Uploads");
 		
 		let mut max_uploads_per_frame: usize = 2;
 		for (chunk_pos, chunk) in chunks.chunks.iter() {
 			
-			if self.chunks.contains_key(chunk_pos) {
-				let (time, mesh) = self.chunks.get_mut(chunk_pos).unwrap();
+			let distance = {
+				let half = (CHUNK_SIZE / 2) as f32;
+				(Self::get_chunk_offset(chunk_pos, camera) + cgmath::Vector3::new(half, half, half)).magnitude()
+			};
+			
+			if let Some(entry) = self.chunks.get_mut(chunk_pos) {
+				let lod = ChunkLod::select(entry.lod, distance);
 				
-				if chunk.last_update > *time && max_uploads_per_frame > 0 {
+				if (chunk.last_update > entry.time || lod != entry.lod) && max_uploads_per_frame > 0 {
 					max_uploads_per_frame -= 1;
 					
 					let block_data = chunks.get_chunk_with_edges(chunk_pos).unwrap();
 					
-					*time = chunk.last_update;
+					entry.time = std::cmp::max(entry.time, chunk.last_update);
+					entry.lod = lod;
 					
 					let profiler_tree = common::profiler::profiler().get_current();
 					
 					profiler_tree.enter_noguard("mesh-chunk");
-					mesh_chunk(
+					mesh_chunk_lod(
 						&mut self.mesher,
 						self.blocks.clone(),
 						&self.bakery,
 						&chunk,
-						&block_data
+						&block_data,
+						lod
 					);
 					
-					*mesh = upload(&self.gl, &chunk.pos, &self.mesher.vertices, &self.quad_index);
+					if let ChunkMeshState::Meshed(mesh) = &entry.mesh {
+						self.buffers.free(mesh.get_slot());
+					}
+					
+					entry.mesh = upload(&mut self.buffers, &chunk.pos, &self.mesher.vertices);
+					entry.visibility = self.mesher.visibility;
 					
 					profiler_tree.leave();
 				}
 				
-				if let ChunkMeshState::Meshed(mesh) = mesh {
-					self.calls.push(mesh.draw_later());
+				if let ChunkMeshState::Meshed(_) = entry.mesh {
+					self.stats.total += 1;
 				}
 			} else {
 				if max_uploads_per_frame > 0 {
 					max_uploads_per_frame -= 1;
 					
 					let block_data = chunks.get_chunk_with_edges(chunk_pos).unwrap();
+					let lod = ChunkLod::for_distance(distance);
 					
 					let profiler_tree = common::profiler::profiler().get_current();
 					profiler_tree.enter_noguard("mesh-chunk");
 					
-					mesh_chunk(
+					mesh_chunk_lod(
 						&mut self.mesher,
 						self.blocks.clone(),
 						&self.bakery,
 						&chunk,
-						&block_data
+						&block_data,
+						lod
 					);
 					
-					let mesh = upload(&self.gl, &chunk.pos, &self.mesher.vertices, &self.quad_index);
+					let mesh = upload(&mut self.buffers, &chunk.pos, &self.mesher.vertices);
 					
-					if let ChunkMeshState::Meshed(mesh) = &mesh {
-						self.calls.push(mesh.draw_later());
+					if let ChunkMeshState::Meshed(_) = &mesh {
+						self.stats.total += 1;
 					}
 					
 					profiler_tree.leave();
 					
-					self.chunks.insert(chunk_pos.clone(), (current_time_nanos(), mesh));
+					self.chunks.insert(chunk_pos.clone(), ChunkRenderEntry {
+						time: current_time_nanos(),
+						mesh,
+						visibility: self.mesher.visibility,
+						lod,
+					});
 				}
 			}
 		}
 		
 		self.gl.pop_debug();
 		
-		self.gl.clone().scope_debug("Chunk-Draws", &mut || {
-			// TODO: Optimize with https://www.reddit.com/r/opengl/comments/3m9u36/how_to_render_using_glmultidrawarraysindirect/
-			while let Some(chunk_mesh_raw) = self.calls.pop() {
-				draw_chunk(&self.gl, chunk_mesh_raw);
+		let profiler_tree = common::profiler::profiler().get_current();
+		profiler_tree.enter_noguard("cull-chunks");
+		self.collect_visible_chunks(chunks, &frustum, camera);
+		profiler_tree.leave();
+		
+		self.stats.visible = self.visible.iter()
+			.filter(|chunk_pos| match self.chunks.get(chunk_pos) {
+				Some(ChunkRenderEntry { mesh: ChunkMeshState::Meshed(_), .. }) => true,
+				_ => false
+			})
+			.count();
+		
+		let (pages, used, capacity) = self.buffers.get_usage();
+		self.stats.buffer_pages = pages;
+		self.stats.buffer_used = used;
+		self.stats.buffer_capacity = capacity;
+		self.stats.buffer_fragmentation = self.buffers.get_fragmentation();
+		
+		// --- Opaque and cutout layers: Drawn in any order.
+		for (layer, cutoff) in [(BlockRenderLayer::Opaque, 0.0), (BlockRenderLayer::Cutout, CUTOUT_ALPHA)].iter().cloned() {
+			for chunk_pos in self.visible.iter() {
+				if let Some(ChunkRenderEntry { mesh: ChunkMeshState::Meshed(mesh), .. }) = self.chunks.get(chunk_pos) {
+					if mesh.has_layer(layer) {
+						self.calls.push(mesh.draw_later(layer));
+					}
+				}
+			}
+			
+			self.material.shader.program.set_uniform_scalar(self.material.uniform_cutoff, cutoff);
+			self.gl.clone().scope_debug(&format!("Chunk-Draws ({:?})", layer), &mut || {
+				self.draw_calls(camera);
+			});
+		}
+		
+		// --- Translucent layer: Sorted far to near, blended over everything else.
+		// The visible chunks are ordered (roughly) near to far, so the nearest chunks get sorted first.
+		let mut max_sorts_per_frame: usize = 4;
+		for chunk_pos in self.visible.iter() {
+			if let Some(ChunkRenderEntry { mesh: ChunkMeshState::Meshed(mesh), .. }) = self.chunks.get_mut(chunk_pos) {
+				if !mesh.has_layer(BlockRenderLayer::Translucent) {
+					continue;
+				}
+				
+				let local_camera = -Self::get_chunk_offset(chunk_pos, camera);
+				if max_sorts_per_frame > 0 && mesh.needs_sort(&local_camera) {
+					max_sorts_per_frame -= 1;
+					mesh.sort_translucent(&self.buffers, &local_camera);
+				}
+				
+				self.calls.push(mesh.draw_later(BlockRenderLayer::Translucent));
 			}
+		}
+		
+		// Calls are drawn from the back of the list, so the farthest chunks go first (back to front).
+		let half = cgmath::Vector3::new((CHUNK_SIZE / 2) as f32, (CHUNK_SIZE / 2) as f32, (CHUNK_SIZE / 2) as f32);
+		self.calls.sort_by(|a, b| {
+			let a = (Self::get_chunk_offset(&a.2, camera) + half).magnitude2();
+			let b = (Self::get_chunk_offset(&b.2, camera) + half).magnitude2();
+			a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+		});
+		
+		unsafe {
+			self.gl.Enable(gl::BLEND);
+			self.gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			self.gl.DepthMask(gl::FALSE);
+		}
+		
+		self.material.shader.program.set_uniform_scalar(self.material.uniform_cutoff, 0.0);
+		self.gl.clone().scope_debug("Chunk-Draws (Translucent)", &mut || {
+			self.draw_calls(camera);
 		});
 		
+		unsafe {
+			self.gl.DepthMask(gl::TRUE);
+			self.gl.Disable(gl::BLEND);
+		}
+		
 		self.gl.pop_debug();
 	}
+	
+	/// Draws all queued calls, from the back of the list to the front, emptying it.
+	///
+	/// The calls are batched into as few indirect multi-draws as possible; usually just one.
+	fn draw_calls(&mut self, camera: &cgmath::Vector3<f32>) {
+		while let Some((slot, ranges, chunk_pos)) = self.calls.pop() {
+			let offset = Self::get_chunk_offset(&chunk_pos, camera);
+			self.buffers.push_draw(slot, &ranges, &offset);
+		}
+		
+		self.buffers.flush_draws();
+	}
+	
+	/// Collects all potentially visible chunks into `self.visible`,
+	/// by walking the visibility graph breadth-first, starting at the chunk containing the camera.
+	///
+	/// A chunk is only entered if it is inside the frustum, and if the face it is left through
+	/// can be seen from the face it was entered by. The walk never turns back towards the camera.
+	/// Chunks that do not exist or were not meshed yet are treated as fully transparent.
+	fn collect_visible_chunks(&mut self, chunks: &ChunkStorage, frustum: &Frustum, camera: &cgmath::Vector3<f32>) {
+		self.visible.clear();
+		self.visited.clear();
+		self.queue.clear();
+		
+		// --- Limit the walk to the volume of the loaded chunks, plus a layer of air around it.
+		let mut min = ChunkCoord::new_from_chunk(std::i32::MAX, std::i32::MAX, std::i32::MAX);
+		let mut max = ChunkCoord::new_from_chunk(std::i32::MIN, std::i32::MIN, std::i32::MIN);
+		
+		if chunks.chunks.is_empty() {
+			return;
+		}
+		
+		for chunk_pos in chunks.chunks.keys() {
+			min.set(min.x.min(chunk_pos.x), min.y.min(chunk_pos.y), min.z.min(chunk_pos.z));
+			max.set(max.x.max(chunk_pos.x), max.y.max(chunk_pos.y), max.z.max(chunk_pos.z));
+		}
+		
+		let min = min.sub(1, 1, 1);
+		let max = max.add(1, 1, 1);
+		
+		let in_bounds = |pos: &ChunkCoord| {
+			   pos.x >= min.x && pos.x <= max.x
+			&& pos.y >= min.y && pos.y <= max.y
+			&& pos.z >= min.z && pos.z <= max.z
+		};
+		
+		// --- Start at the camera; if it is outside the bounds, the space in between is empty anyway.
+		let start = ChunkCoord::new_from_block(&BlockCoord::new(
+			camera.x.floor() as BlockDim,
+			camera.y.floor() as BlockDim,
+			camera.z.floor() as BlockDim,
+		));
+		
+		let start = ChunkCoord::new_from_chunk(
+			start.x.max(min.x).min(max.x),
+			start.y.max(min.y).min(max.y),
+			start.z.max(min.z).min(max.z),
+		);
+		
+		self.visited.insert(start);
+		self.queue.push_back((start, None, 0));
+		
+		while let Some((chunk_pos, entered_by, directions)) = self.queue.pop_front() {
+			self.visible.push(chunk_pos);
+			
+			let visibility = match self.chunks.get(&chunk_pos) {
+				Some(entry) => entry.visibility,
+				None => ChunkVisibility::all(),
+			};
+			
+			for face in CHUNK_FACES.iter().cloned() {
+				// Never walk back towards the camera...
+				if directions & (1 << chunk_face_index(chunk_face_opposite(face))) != 0 {
+					continue;
+				}
+				
+				// ...nor through faces that cannot be seen from where we came from.
+				if let Some(entered_by) = entered_by {
+					if !visibility.connects(entered_by, face) {
+						continue;
+					}
+				}
+				
+				let neighbour = chunk_face_neighbour(&chunk_pos, face);
+				
+				if !in_bounds(&neighbour) || self.visited.contains(&neighbour) {
+					continue;
+				}
+				
+				if !Self::is_chunk_visible(frustum, &neighbour, camera) {
+					continue;
+				}
+				
+				self.visited.insert(neighbour);
+				self.queue.push_back((
+					neighbour,
+					Some(chunk_face_opposite(face)),
+					directions | (1 << chunk_face_index(face))
+				));
+			}
+		}
+	}
+	
+	/// Returns the statistics of the last rendered frame.
+	pub fn get_stats(&self) -> ChunkRenderStats {
+		self.stats
+	}
+	
+	/// Tests the bounds of the given chunk against a camera-relative view-frustum.
+	fn is_chunk_visible(frustum: &Frustum, chunk_pos: &ChunkCoord, camera: &cgmath::Vector3<f32>) -> bool {
+		let min = Self::get_chunk_offset(chunk_pos, camera);
+		let max = min + cgmath::Vector3::new(CHUNK_SIZE as f32, CHUNK_SIZE as f32, CHUNK_SIZE as f32);
+		frustum.intersects_aabb(&min, &max)
+	}
+	
+	/// Computes the origin of the given chunk relative to the camera.
+	///
+	/// The subtraction is done in double precision, so the result stays accurate far away from the world origin.
+	fn get_chunk_offset(chunk_pos: &ChunkCoord, camera: &cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
+		let (cx, cy, cz) = chunk_pos.to_block_coord_tuple();
+		cgmath::Vector3::new(
+			(cx as f64 - camera.x as f64) as f32,
+			(cy as f64 - camera.y as f64) as f32,
+			(cz as f64 - camera.z as f64) as f32,
+		)
+	}
 }
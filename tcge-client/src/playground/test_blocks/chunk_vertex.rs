@@ -0,0 +1,431 @@
+//! The packed vertex format of chunk meshes.
+//!
+//! A vertex is three 32-bit words, each holding several bitfields of fixed-point values.
+//! The layout is described once, in `ChunkVertexField::ALL`; packing and unpacking on the CPU,
+//! the vertex attribute setup and the GLSL functions that unpack the fields in the shader are all derived from it.
+//!
+//! | Word | Fields                                                   | Bits                    |
+//! |------|----------------------------------------------------------|-------------------------|
+//! | 0    | x, y, z, corner                                          | 10, 10, 10, 2           |
+//! | 1    | layer, face, ao, repeat, u, v, width, height             | 8, 3, 2, 2, 4, 4, 4, 4  |
+//! | 2    | red, green, blue, light                                  | 8, 8, 8, 4              |
+//!
+//! Instead of a normal, a vertex stores the index of the face it belongs to; the shader looks the normal up.
+//! Instead of texture coordinates, it stores the texture rectangle of its quad and the corner of it the vertex is at.
+
+use super::*;
+use blocks::Face;
+
+/// The number of 32-bit words in a vertex.
+pub const CHUNK_VERTEX_WORDS: usize = 3;
+
+/// The attribute location of the origin of the chunk being drawn, relative to the camera; right after the vertex words.
+pub const CHUNK_OFFSET_LOCATION: gl::types::GLuint = CHUNK_VERTEX_WORDS as gl::types::GLuint;
+
+/// A single fixed-point value within the words of a vertex.
+///
+/// A value `v` is stored as the unsigned integer `round((v - bias) / scale)`, clamped to the bits of the field.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChunkVertexField {
+	/// The name of the field in the shader, as in `chunk_vertex_<name>()`.
+	pub name: &'static str,
+	
+	pub word: usize,
+	pub shift: u32,
+	pub bits: u32,
+	
+	pub scale: f32,
+	pub bias: f32,
+}
+
+impl ChunkVertexField {
+	/// Chunk-local position: 1/16th of a block, from -16 to 48 blocks, so rotated elements may stick out of the chunk.
+	pub const X: Self = Self::new("x", 0, 0, 10, 1.0 / 16.0, -16.0);
+	pub const Y: Self = Self::new("y", 0, 10, 10, 1.0 / 16.0, -16.0);
+	pub const Z: Self = Self::new("z", 0, 20, 10, 1.0 / 16.0, -16.0);
+	
+	/// The corner of the texture rectangle: Bit 0 is set at its right edge, bit 1 at its bottom edge.
+	pub const CORNER: Self = Self::new("corner", 0, 30, 2, 1.0, 0.0);
+	
+	/// The layer of the block texture array.
+	pub const LAYER: Self = Self::new("layer", 1, 0, 8, 1.0, 0.0);
+	
+	/// The index of the face within `CHUNK_FACES`; rotated faces use the axis closest to their normal.
+	pub const FACE: Self = Self::new("face", 1, 8, 3, 1.0, 0.0);
+	
+	/// Ambient occlusion, in `0..=1`.
+	pub const AO: Self = Self::new("ao", 1, 11, 2, 1.0 / 3.0, 0.0);
+	
+	/// How often the texture repeats across the quad, as a power of two; for downsampled faces.
+	pub const REPEAT: Self = Self::new("repeat", 1, 13, 2, 1.0, 0.0);
+	
+	/// The texture rectangle of the quad: Its top-left corner and size, in 1/16ths of a texture.
+	pub const U: Self = Self::new("u", 1, 15, 4, 1.0 / 16.0, 0.0);
+	pub const V: Self = Self::new("v", 1, 19, 4, 1.0 / 16.0, 0.0);
+	pub const WIDTH: Self = Self::new("width", 1, 23, 4, 1.0 / 16.0, 1.0 / 16.0);
+	pub const HEIGHT: Self = Self::new("height", 1, 27, 4, 1.0 / 16.0, 1.0 / 16.0);
+	
+	/// The tint, multiplied with the texture.
+	pub const RED: Self = Self::new("red", 2, 0, 8, 1.0 / 255.0, 0.0);
+	pub const GREEN: Self = Self::new("green", 2, 8, 8, 1.0 / 255.0, 0.0);
+	pub const BLUE: Self = Self::new("blue", 2, 16, 8, 1.0 / 255.0, 0.0);
+	
+	/// Light, in `0..=1`.
+	pub const LIGHT: Self = Self::new("light", 2, 24, 4, 1.0 / 15.0, 0.0);
+	
+	/// Every field of the vertex format.
+	pub const ALL: [Self; 16] = [
+		Self::X, Self::Y, Self::Z, Self::CORNER,
+		Self::LAYER, Self::FACE, Self::AO, Self::REPEAT,
+		Self::U, Self::V, Self::WIDTH, Self::HEIGHT,
+		Self::RED, Self::GREEN, Self::BLUE, Self::LIGHT,
+	];
+	
+	const fn new(name: &'static str, word: usize, shift: u32, bits: u32, scale: f32, bias: f32) -> Self {
+		Self { name, word, shift, bits, scale, bias }
+	}
+	
+	/// The mask of the field, before shifting.
+	#[inline]
+	pub fn mask(&self) -> u32 {
+		((1u64 << self.bits) - 1) as u32
+	}
+	
+	/// Converts a value to the integer stored in the field.
+	#[inline]
+	pub fn encode(&self, value: f32) -> u32 {
+		let raw = ((value - self.bias) / self.scale).round();
+		raw.max(0.0).min(self.mask() as f32) as u32
+	}
+	
+	/// Converts the integer stored in the field back to a value.
+	#[inline]
+	pub fn decode(&self, raw: u32) -> f32 {
+		raw as f32 * self.scale + self.bias
+	}
+	
+	/// Returns the GLSL function that unpacks the field in the shader.
+	pub fn to_glsl(&self) -> String {
+		format!(
+			"float chunk_vertex_{}() {{ return float((ChunkVertexWord{} >> {}u) & {}u) * {:?} + {:?}; }}\n",
+			self.name, self.word, self.shift, self.mask(), self.scale, self.bias
+		)
+	}
+}
+
+/// Returns the index (within `CHUNK_FACES`) of the face whose normal is closest to the given one.
+pub fn chunk_face_from_normal(nx: f32, ny: f32, nz: f32) -> usize {
+	let face = if nx.abs() >= ny.abs() && nx.abs() >= nz.abs() {
+		if nx >= 0.0 { Face::PositiveX } else { Face::NegativeX }
+	} else if ny.abs() >= nz.abs() {
+		if ny >= 0.0 { Face::PositiveY } else { Face::NegativeY }
+	} else {
+		if nz >= 0.0 { Face::PositiveZ } else { Face::NegativeZ }
+	};
+	
+	chunk_face_index(face)
+}
+
+/// A single vertex of a chunk mesh, as uploaded to the GPU.
+///
+/// The position is relative to the origin of the chunk the vertex belongs to;
+/// the origin of the chunk is passed to the shader separately, per draw.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct ChunkMeshVertex {
+	pub words: [u32; CHUNK_VERTEX_WORDS],
+}
+
+impl ChunkMeshVertex {
+	/// Packs the four vertices of a quad, moved by `offset`.
+	///
+	/// The texture of the quad repeats `2^repeat` times across it; the texture coordinates
+	/// of the vertices must span a rectangle, i.e. be rotated by multiples of 90° at most.
+	pub fn new_quad(quad: &[BakedBlockMeshVertex; 4], ao: f32, offset: &(f32, f32, f32), tint: &BlockTint, repeat: u32) -> [Self; 4] {
+		let umin = quad.iter().map(|vertex| vertex.u).fold(std::f32::INFINITY, f32::min);
+		let umax = quad.iter().map(|vertex| vertex.u).fold(std::f32::NEG_INFINITY, f32::max);
+		let vmin = quad.iter().map(|vertex| vertex.v).fold(std::f32::INFINITY, f32::min);
+		let vmax = quad.iter().map(|vertex| vertex.v).fold(std::f32::NEG_INFINITY, f32::max);
+		
+		let face = chunk_face_from_normal(quad[0].nx, quad[0].ny, quad[0].nz);
+		
+		let mut vertices = [Self::default(); 4];
+		for (vertex, baked) in vertices.iter_mut().zip(quad.iter()) {
+			let right = baked.u - umin > (umax - umin) / 2.0;
+			let bottom = baked.v - vmin > (vmax - vmin) / 2.0;
+			
+			vertex.set(&ChunkVertexField::X, baked.x + offset.0);
+			vertex.set(&ChunkVertexField::Y, baked.y + offset.1);
+			vertex.set(&ChunkVertexField::Z, baked.z + offset.2);
+			vertex.set_raw(&ChunkVertexField::CORNER, right as u32 | (bottom as u32) << 1);
+			
+			vertex.set_raw(&ChunkVertexField::LAYER, baked.texture as u32);
+			vertex.set_raw(&ChunkVertexField::FACE, face as u32);
+			vertex.set(&ChunkVertexField::AO, ao);
+			vertex.set_raw(&ChunkVertexField::REPEAT, repeat);
+			vertex.set(&ChunkVertexField::U, umin);
+			vertex.set(&ChunkVertexField::V, vmin);
+			vertex.set(&ChunkVertexField::WIDTH, umax - umin);
+			vertex.set(&ChunkVertexField::HEIGHT, vmax - vmin);
+			
+			vertex.set_raw(&ChunkVertexField::RED, tint[0] as u32);
+			vertex.set_raw(&ChunkVertexField::GREEN, tint[1] as u32);
+			vertex.set_raw(&ChunkVertexField::BLUE, tint[2] as u32);
+			vertex.set(&ChunkVertexField::LIGHT, 1.0);
+		}
+		
+		vertices
+	}
+	
+	/// Packs a value into the given field, replacing what was there.
+	#[inline]
+	pub fn set(&mut self, field: &ChunkVertexField, value: f32) {
+		self.set_raw(field, field.encode(value))
+	}
+	
+	/// Unpacks the value of the given field.
+	#[inline]
+	pub fn get(&self, field: &ChunkVertexField) -> f32 {
+		field.decode(self.get_raw(field))
+	}
+	
+	/// Stores an integer into the given field, cutting off any bits that do not fit.
+	#[inline]
+	pub fn set_raw(&mut self, field: &ChunkVertexField, raw: u32) {
+		let mask = field.mask() << field.shift;
+		let word = &mut self.words[field.word];
+		*word = (*word & !mask) | ((raw << field.shift) & mask);
+	}
+	
+	#[inline]
+	pub fn get_raw(&self, field: &ChunkVertexField) -> u32 {
+		(self.words[field.word] >> field.shift) & field.mask()
+	}
+	
+	/// Returns the chunk-local position of the vertex.
+	pub fn get_position(&self) -> (f32, f32, f32) {
+		(
+			self.get(&ChunkVertexField::X),
+			self.get(&ChunkVertexField::Y),
+			self.get(&ChunkVertexField::Z),
+		)
+	}
+	
+	/// Returns the texture coordinates of the vertex, as computed by `chunk_vertex_texcoord()` in the shader.
+	///
+	/// Only the shader needs them outside of tests.
+	#[cfg(test)]
+	pub fn get_texcoord(&self) -> (f32, f32) {
+		let corner = self.get_raw(&ChunkVertexField::CORNER);
+		let repeat = (1 << self.get_raw(&ChunkVertexField::REPEAT)) as f32;
+		
+		let u = self.get(&ChunkVertexField::U) + (corner & 1) as f32 * self.get(&ChunkVertexField::WIDTH);
+		let v = self.get(&ChunkVertexField::V) + (corner >> 1) as f32 * self.get(&ChunkVertexField::HEIGHT);
+		(u * repeat, v * repeat)
+	}
+	
+	/// Enables and describes one unsigned integer attribute per word, for the currently bound vertex array and buffer.
+	///
+	/// Word `i` is bound to attribute location `i`.
+	pub fn setup_attributes(gl: &gl::Gl) {
+		let stride = std::mem::size_of::<Self>() as gl::types::GLsizei;
+		
+		for word in 0..CHUNK_VERTEX_WORDS {
+			unsafe {
+				gl.EnableVertexAttribArray(word as gl::types::GLuint);
+				gl.VertexAttribIPointer(
+					word as gl::types::GLuint, // attribute location
+					1, // sub-element count
+					gl::UNSIGNED_INT, // sub-element type
+					stride,
+					(word * std::mem::size_of::<u32>()) as *const gl::types::GLvoid
+				);
+			}
+		}
+	}
+	
+	/// Returns the GLSL declarations of the vertex words and the chunk offset, and a `chunk_vertex_<name>()` function unpacking each field.
+	///
+	/// Also declares `chunk_vertex_normal()` and `chunk_vertex_texcoord()`, which compute what the fields stand in for.
+	pub fn to_glsl() -> String {
+		let mut glsl = String::new();
+		
+		for word in 0..CHUNK_VERTEX_WORDS {
+			glsl += &format!("layout (location = {}) in uint ChunkVertexWord{};\n", word, word);
+		}
+		
+		glsl += &format!("layout (location = {}) in vec3 ChunkOffset;\n", CHUNK_OFFSET_LOCATION);
+		
+		for field in ChunkVertexField::ALL.iter() {
+			glsl += &field.to_glsl();
+		}
+		
+		let normals: Vec<String> = CHUNK_FACES.iter()
+			.map(|face| {
+				let (x, y, z) = face.normal();
+				format!("vec3({:?}, {:?}, {:?})", x, y, z)
+			})
+			.collect();
+		
+		glsl += &format!("const vec3 ChunkFaceNormals[6] = vec3[6]({});\n", normals.join(", "));
+		glsl += "vec3 chunk_vertex_normal() { return ChunkFaceNormals[uint(chunk_vertex_face())]; }\n";
+		glsl += concat!(
+			"vec2 chunk_vertex_texcoord() {\n",
+			"\tuint corner = uint(chunk_vertex_corner());\n",
+			"\tvec2 uv = vec2(chunk_vertex_u(), chunk_vertex_v()) + vec2(float(corner & 1u), float(corner >> 1u)) * vec2(chunk_vertex_width(), chunk_vertex_height());\n",
+			"\treturn uv * float(1u << uint(chunk_vertex_repeat()));\n",
+			"}\n"
+		);
+		
+		glsl
+	}
+}
+
+/// Inserts the declarations of `ChunkMeshVertex::to_glsl` into the source of a vertex shader, right after the `#version` line.
+pub fn insert_chunk_vertex_glsl(source: &str) -> String {
+	let split = match source.starts_with("#version") {
+		true => source.find('\n').map(|end| end + 1).unwrap_or_else(|| source.len()),
+		false => 0,
+	};
+	
+	let (version, rest) = source.split_at(split);
+	format!("{}\n{}\n{}", version, ChunkMeshVertex::to_glsl(), rest)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn baked(x: f32, y: f32, z: f32, u: f32, v: f32, normal: (f32, f32, f32)) -> BakedBlockMeshVertex {
+		BakedBlockMeshVertex {
+			texture: 7,
+			.. BakedBlockMeshVertex::new(x, y, z, u, v, normal.0, normal.1, normal.2)
+		}
+	}
+	
+	#[test]
+	fn vertex_is_compact() {
+		assert_eq!(std::mem::size_of::<ChunkMeshVertex>(), 12);
+	}
+	
+	#[test]
+	fn fields_fit_their_words_without_overlapping() {
+		let mut used = [0u64; CHUNK_VERTEX_WORDS];
+		
+		for field in ChunkVertexField::ALL.iter() {
+			assert!(field.word < CHUNK_VERTEX_WORDS, "field {} is outside the vertex", field.name);
+			assert!(field.shift + field.bits <= 32, "field {} is outside its word", field.name);
+			
+			let mask = (field.mask() as u64) << field.shift;
+			assert_eq!(used[field.word] & mask, 0, "field {} overlaps another", field.name);
+			used[field.word] |= mask;
+		}
+	}
+	
+	#[test]
+	fn fields_round_trip() {
+		for field in ChunkVertexField::ALL.iter() {
+			for raw in [0, 1, field.mask() / 2, field.mask()].iter() {
+				assert_eq!(field.encode(field.decode(*raw)), *raw, "field {}", field.name);
+			}
+			
+			// Values out of range are clamped.
+			assert_eq!(field.encode(field.decode(0) - 1000.0), 0);
+			assert_eq!(field.encode(field.decode(field.mask()) + 1000.0), field.mask());
+		}
+	}
+	
+	#[test]
+	fn fields_are_independent() {
+		let mut vertex = ChunkMeshVertex::default();
+		
+		// Fill every field with a different pattern, then check that none overwrote another.
+		let pattern = |index: usize, field: &ChunkVertexField| (index as u32).wrapping_mul(0x9E37_79B9) & field.mask();
+		
+		for (index, field) in ChunkVertexField::ALL.iter().enumerate() {
+			vertex.set_raw(field, pattern(index, field));
+		}
+		
+		for (index, field) in ChunkVertexField::ALL.iter().enumerate() {
+			assert_eq!(vertex.get_raw(field), pattern(index, field), "field {}", field.name);
+		}
+	}
+	
+	#[test]
+	fn face_from_normal() {
+		for face in CHUNK_FACES.iter() {
+			let (x, y, z) = face.normal();
+			assert_eq!(chunk_face_from_normal(x, y, z), chunk_face_index(*face));
+		}
+		
+		// Rotated faces use the closest axis.
+		assert_eq!(chunk_face_from_normal(0.7, -0.2, 0.6), chunk_face_index(Face::PositiveX));
+		assert_eq!(chunk_face_from_normal(0.1, -0.9, 0.3), chunk_face_index(Face::NegativeY));
+	}
+	
+	#[test]
+	fn quad_round_trip() {
+		// The top of a fence post: A 4/16 wide square, its texture taken from the middle of the image and rotated by 90°.
+		let up = (0.0, 1.0, 0.0);
+		let quad = [
+			baked(0.375, 1.0, 0.625, 0.375, 0.625, up),
+			baked(0.625, 1.0, 0.625, 0.375, 0.375, up),
+			baked(0.625, 1.0, 0.375, 0.625, 0.375, up),
+			baked(0.375, 1.0, 0.375, 0.625, 0.625, up),
+		];
+		
+		let offset = (3.0, 30.0, 0.0);
+		let vertices = ChunkMeshVertex::new_quad(&quad, 1.0, &offset, &[10, 20, 30], 0);
+		
+		for (vertex, baked) in vertices.iter().zip(quad.iter()) {
+			assert_eq!(vertex.get_position(), (baked.x + offset.0, baked.y + offset.1, baked.z + offset.2));
+			assert_eq!(vertex.get_texcoord(), (baked.u, baked.v));
+			assert_eq!(vertex.get_raw(&ChunkVertexField::FACE) as usize, chunk_face_index(Face::PositiveY));
+			assert_eq!(vertex.get_raw(&ChunkVertexField::LAYER), 7);
+			assert_eq!(vertex.get(&ChunkVertexField::AO), 1.0);
+			assert_eq!(vertex.get(&ChunkVertexField::LIGHT), 1.0);
+			assert_eq!(vertex.get_raw(&ChunkVertexField::RED), 10);
+			assert_eq!(vertex.get_raw(&ChunkVertexField::GREEN), 20);
+			assert_eq!(vertex.get_raw(&ChunkVertexField::BLUE), 30);
+		}
+		
+		// The corners are all different.
+		let mut corners: Vec<u32> = vertices.iter().map(|vertex| vertex.get_raw(&ChunkVertexField::CORNER)).collect();
+		corners.sort();
+		assert_eq!(corners, vec![0, 1, 2, 3]);
+	}
+	
+	#[test]
+	fn quad_repeats_texture() {
+		let north = (0.0, 0.0, -1.0);
+		let quad = [
+			baked(0.0, 0.0, 0.0, 0.0, 1.0, north),
+			baked(0.0, 1.0, 0.0, 0.0, 0.0, north),
+			baked(1.0, 1.0, 0.0, 1.0, 0.0, north),
+			baked(1.0, 0.0, 0.0, 1.0, 1.0, north),
+		];
+		
+		let vertices = ChunkMeshVertex::new_quad(&quad, 0.0, &(0.0, 0.0, 0.0), &TINT_NONE, 2);
+		
+		for (vertex, baked) in vertices.iter().zip(quad.iter()) {
+			assert_eq!(vertex.get_texcoord(), (baked.u * 4.0, baked.v * 4.0));
+			assert_eq!(vertex.get_raw(&ChunkVertexField::FACE) as usize, chunk_face_index(Face::NegativeZ));
+		}
+	}
+	
+	#[test]
+	fn glsl_declares_every_field() {
+		let glsl = ChunkMeshVertex::to_glsl();
+		
+		for word in 0..CHUNK_VERTEX_WORDS {
+			assert!(glsl.contains(&format!("in uint ChunkVertexWord{};", word)));
+		}
+		
+		for field in ChunkVertexField::ALL.iter() {
+			assert!(glsl.contains(&format!("float chunk_vertex_{}()", field.name)));
+		}
+		
+		assert!(glsl.contains("vec3 chunk_vertex_normal()"));
+		assert!(glsl.contains("vec2 chunk_vertex_texcoord()"));
+	}
+}
@@ -0,0 +1,238 @@
+use super::*;
+use blocks::Face;
+
+/// The six faces of a chunk, in the order used for indexing visibility sets.
+pub const CHUNK_FACES: [Face; 6] = [
+	Face::PositiveX, Face::NegativeX,
+	Face::PositiveY, Face::NegativeY,
+	Face::PositiveZ, Face::NegativeZ,
+];
+
+/// Returns the index of the given face within `CHUNK_FACES`.
+#[inline]
+pub fn chunk_face_index(face: Face) -> usize {
+	debug_assert!(face != Face::EveryDir, "EveryDir is not a chunk face.");
+	face.uid() - 1
+}
+
+/// Returns the face on the other side of a chunk.
+pub fn chunk_face_opposite(face: Face) -> Face {
+	match face {
+		Face::PositiveX => Face::NegativeX,
+		Face::NegativeX => Face::PositiveX,
+		Face::PositiveY => Face::NegativeY,
+		Face::NegativeY => Face::PositiveY,
+		Face::PositiveZ => Face::NegativeZ,
+		Face::NegativeZ => Face::PositiveZ,
+		Face::EveryDir => Face::EveryDir,
+	}
+}
+
+/// Returns the position of the chunk that lies beyond the given face.
+pub fn chunk_face_neighbour(pos: &ChunkCoord, face: Face) -> ChunkCoord {
+	match face {
+		Face::PositiveX => pos.add( 1, 0, 0),
+		Face::NegativeX => pos.add(-1, 0, 0),
+		Face::PositiveY => pos.add( 0, 1, 0),
+		Face::NegativeY => pos.add( 0,-1, 0),
+		Face::PositiveZ => pos.add( 0, 0, 1),
+		Face::NegativeZ => pos.add( 0, 0,-1),
+		Face::EveryDir => pos.clone(),
+	}
+}
+
+/// Records which pairs of the six faces of a chunk are connected through non-opaque blocks.
+///
+/// If two faces are not connected, nothing behind one of them can be seen by looking through the other.
+/// The relation is symmetric; a face is connected to itself if any non-opaque block touches it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChunkVisibility {
+	bits: u64,
+}
+
+impl ChunkVisibility {
+	/// A chunk where no face can be seen through any other; e.g. a chunk full of solid blocks.
+	pub fn none() -> Self {
+		Self { bits: 0 }
+	}
+	
+	/// A chunk where every face can be seen through every other; e.g. a chunk full of air.
+	pub fn all() -> Self {
+		Self { bits: (1 << 36) - 1 }
+	}
+	
+	/// Returns `true` if the two given faces are connected through the chunk.
+	pub fn connects(&self, a: Face, b: Face) -> bool {
+		let bit = chunk_face_index(a) * 6 + chunk_face_index(b);
+		(self.bits >> bit) & 1 != 0
+	}
+	
+	/// Marks every pair of faces in the given set (one bit per `CHUNK_FACES` index) as connected.
+	fn connect_set(&mut self, faces: u8) {
+		for a in 0..6 {
+			if faces & (1 << a) == 0 {
+				continue;
+			}
+			
+			for b in 0..6 {
+				if faces & (1 << b) != 0 {
+					self.bits |= 1 << (a * 6 + b);
+				}
+			}
+		}
+	}
+}
+
+/// Computes the face-to-face connectivity of the given chunk.
+///
+/// Every connected region of non-opaque blocks is flood-filled,
+/// and all the faces the region touches are connected to each other.
+/// Only the inner `CHUNK_SIZE³` blocks are considered; the edge is ignored.
+pub fn compute_visibility<F>(block_data: &ChunkWithEdge, is_opaque: F) -> ChunkVisibility
+	where F: Fn(BlockState) -> bool
+{
+	let mut visited = vec![false; CHUNK_VOLUME];
+	let mut open = 0;
+	
+	// --- Opaque blocks are never flooded, so mark them as visited right away.
+	for y in 0..CHUNK_SIZE {
+		for z in 0..CHUNK_SIZE {
+			for x in 0..CHUNK_SIZE {
+				let index = Chunk::coord_to_index(x as BlockDim, y as BlockDim, z as BlockDim);
+				if is_opaque(block_data[y+1][z+1][x+1]) {
+					visited[index] = true;
+				} else {
+					open += 1;
+				}
+			}
+		}
+	}
+	
+	// --- Trivial cases: Completely solid or completely open.
+	if open == 0 {
+		return ChunkVisibility::none()
+	}
+	
+	if open == CHUNK_VOLUME {
+		return ChunkVisibility::all()
+	}
+	
+	let mut visibility = ChunkVisibility::none();
+	let mut stack: Vec<usize> = Vec::with_capacity(open);
+	
+	let face_bit = |face: Face| 1u8 << chunk_face_index(face);
+	
+	for start in 0..CHUNK_VOLUME {
+		if visited[start] {
+			continue;
+		}
+		
+		visited[start] = true;
+		stack.push(start);
+		
+		let mut faces: u8 = 0;
+		
+		while let Some(index) = stack.pop() {
+			let x = index & CHUNK_SIZE_MASK;
+			let z = (index >> CHUNK_SIZE_BITS) & CHUNK_SIZE_MASK;
+			let y = index >> (CHUNK_SIZE_BITS * 2);
+			
+			let mut visit = |neighbour: usize| {
+				if !visited[neighbour] {
+					visited[neighbour] = true;
+					stack.push(neighbour);
+				}
+			};
+			
+			if x == 0 { faces |= face_bit(Face::NegativeX) } else { visit(index - 1) }
+			if x == CHUNK_SIZE_MASK { faces |= face_bit(Face::PositiveX) } else { visit(index + 1) }
+			
+			if z == 0 { faces |= face_bit(Face::NegativeZ) } else { visit(index - CHUNK_SIZE) }
+			if z == CHUNK_SIZE_MASK { faces |= face_bit(Face::PositiveZ) } else { visit(index + CHUNK_SIZE) }
+			
+			if y == 0 { faces |= face_bit(Face::NegativeY) } else { visit(index - CHUNK_SLICE) }
+			if y == CHUNK_SIZE_MASK { faces |= face_bit(Face::PositiveY) } else { visit(index + CHUNK_SLICE) }
+		}
+		
+		visibility.connect_set(faces);
+	}
+	
+	visibility
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn states() -> (BlockState, BlockState) {
+		let blocks = blocks::Blocks::new();
+		(
+			blocks.get_block_by_name_unchecked("air").get_default_state(),
+			blocks.get_block_by_name_unchecked("adm").get_default_state(),
+		)
+	}
+	
+	fn filled(state: BlockState) -> ChunkWithEdge {
+		Box::new([[[state; CHUNK_SIZE + 2]; CHUNK_SIZE + 2]; CHUNK_SIZE + 2])
+	}
+	
+	#[test]
+	fn empty_chunk_connects_all_faces() {
+		let (air, solid) = states();
+		let visibility = compute_visibility(&filled(air), |block| block == solid);
+		
+		assert_eq!(visibility, ChunkVisibility::all());
+		for a in CHUNK_FACES.iter() {
+			for b in CHUNK_FACES.iter() {
+				assert!(visibility.connects(*a, *b));
+			}
+		}
+	}
+	
+	#[test]
+	fn solid_chunk_connects_no_faces() {
+		let (_, solid) = states();
+		let visibility = compute_visibility(&filled(solid), |block| block == solid);
+		
+		assert_eq!(visibility, ChunkVisibility::none());
+		for a in CHUNK_FACES.iter() {
+			for b in CHUNK_FACES.iter() {
+				assert!(!visibility.connects(*a, *b));
+			}
+		}
+	}
+	
+	#[test]
+	fn wall_separates_its_sides() {
+		let (air, solid) = states();
+		
+		// A solid wall across the middle of the X axis.
+		let mut block_data = filled(air);
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				block_data[y + 1][z + 1][CHUNK_SIZE / 2 + 1] = solid;
+			}
+		}
+		
+		let visibility = compute_visibility(&block_data, |block| block == solid);
+		
+		assert!(!visibility.connects(Face::NegativeX, Face::PositiveX));
+		assert!(!visibility.connects(Face::PositiveX, Face::NegativeX));
+		
+		let sides = [Face::PositiveY, Face::NegativeY, Face::PositiveZ, Face::NegativeZ];
+		for end in [Face::NegativeX, Face::PositiveX].iter() {
+			assert!(visibility.connects(*end, *end));
+			
+			for side in sides.iter() {
+				assert!(visibility.connects(*end, *side));
+				assert!(visibility.connects(*side, *end));
+			}
+		}
+		
+		for a in sides.iter() {
+			for b in sides.iter() {
+				assert!(visibility.connects(*a, *b));
+			}
+		}
+	}
+}
@@ -39,11 +39,35 @@ pub use chunk_render::*;
 pub mod chunk_mesher;
 pub use chunk_mesher::*;
 
+pub mod chunk_vertex;
+pub use chunk_vertex::*;
+
+pub mod chunk_buffers;
+pub use chunk_buffers::*;
+
+pub mod chunk_lod;
+pub use chunk_lod::*;
+
+pub mod chunk_visibility;
+pub use chunk_visibility::*;
+
 pub mod block_material;
 pub use block_material::*;
 
 pub mod block_model;
 pub use block_model::*;
 
+pub mod block_model_loader;
+pub use block_model_loader::*;
+
+pub mod block_textures;
+pub use block_textures::*;
+
+pub mod block_animation;
+pub use block_animation::*;
+
+pub mod block_tint;
+pub use block_tint::*;
+
 pub mod block_bakery;
 pub use block_bakery::*;
@@ -0,0 +1,398 @@
+//! A free-list allocator for suballocating ranges of elements from one large buffer.
+//!
+//! The allocator never touches the buffer itself; it only keeps track of which ranges are in use,
+//! so it can be used for any kind of buffer (and without an OpenGL context).
+//! Offsets and sizes are counted in elements, not bytes.
+
+use rustc_hash::FxHashMap;
+
+/// A handle to a live allocation.
+///
+/// The offset of an allocation changes when the allocator is defragmented; the handle does not.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BufferAllocationId(u32);
+
+/// A contiguous range of elements.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferRange {
+	pub offset: usize,
+	pub size: usize,
+}
+
+impl BufferRange {
+	/// The first element past the end of the range.
+	#[inline]
+	pub fn end(&self) -> usize {
+		self.offset + self.size
+	}
+}
+
+/// A copy of elements that must be done in the buffer, as the result of defragmentation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferMove {
+	pub from: usize,
+	pub to: usize,
+	pub size: usize,
+}
+
+pub struct BufferAllocator {
+	capacity: usize,
+	
+	/// The free ranges, sorted by offset; two free ranges never touch.
+	free: Vec<BufferRange>,
+	
+	allocations: FxHashMap<BufferAllocationId, BufferRange>,
+	next_id: u32,
+	used: usize,
+}
+
+impl BufferAllocator {
+	/// Creates an allocator for a buffer of the given number of elements, all of which are free.
+	pub fn new(capacity: usize) -> Self {
+		let free = if capacity > 0 {
+			vec![BufferRange { offset: 0, size: capacity }]
+		} else {
+			vec![]
+		};
+		
+		Self {
+			capacity,
+			free,
+			allocations: FxHashMap::default(),
+			next_id: 0,
+			used: 0,
+		}
+	}
+	
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+	
+	/// The number of elements in use.
+	pub fn used(&self) -> usize {
+		self.used
+	}
+	
+	/// The number of free elements, whether or not they are contiguous.
+	pub fn available(&self) -> usize {
+		self.capacity - self.used
+	}
+	
+	/// The size of the largest allocation that can currently succeed.
+	pub fn largest_free(&self) -> usize {
+		self.free.iter().map(|range| range.size).max().unwrap_or(0)
+	}
+	
+	/// How fragmented the free space is, from 0 (one contiguous range) to almost 1 (many small ranges).
+	pub fn fragmentation(&self) -> f32 {
+		match self.available() {
+			0 => 0.0,
+			available => 1.0 - self.largest_free() as f32 / available as f32,
+		}
+	}
+	
+	/// Returns the range of a live allocation.
+	pub fn get(&self, id: BufferAllocationId) -> Option<BufferRange> {
+		self.allocations.get(&id).cloned()
+	}
+	
+	/// Allocates a range of the given (non-zero) size; `None` if no free range is large enough.
+	///
+	/// The smallest free range that fits is used, to keep large ranges available for large allocations.
+	pub fn allocate(&mut self, size: usize) -> Option<BufferAllocationId> {
+		assert!(size > 0, "Cannot allocate an empty range.");
+		
+		let (index, _) = self.free.iter()
+			.enumerate()
+			.filter(|(_, range)| range.size >= size)
+			.min_by_key(|(_, range)| range.size)?;
+		
+		let range = BufferRange {
+			offset: self.free[index].offset,
+			size,
+		};
+		
+		if self.free[index].size == size {
+			self.free.remove(index);
+		} else {
+			self.free[index].offset += size;
+			self.free[index].size -= size;
+		}
+		
+		let id = BufferAllocationId(self.next_id);
+		self.next_id = self.next_id.wrapping_add(1);
+		self.allocations.insert(id, range);
+		self.used += size;
+		
+		Some(id)
+	}
+	
+	/// Frees a live allocation, merging its range with the free ranges around it.
+	///
+	/// Returns the range the allocation occupied; `None` if it was not live.
+	pub fn free(&mut self, id: BufferAllocationId) -> Option<BufferRange> {
+		let range = self.allocations.remove(&id)?;
+		self.used -= range.size;
+		
+		// The free ranges never overlap live allocations, so the search never finds the offset itself.
+		let index = match self.free.binary_search_by_key(&range.offset, |free| free.offset) {
+			Ok(index) | Err(index) => index,
+		};
+		
+		let merges_prev = index > 0 && self.free[index - 1].end() == range.offset;
+		let merges_next = index < self.free.len() && self.free[index].offset == range.end();
+		
+		match (merges_prev, merges_next) {
+			(true, true) => {
+				self.free[index - 1].size += range.size + self.free[index].size;
+				self.free.remove(index);
+			},
+			(true, false) => self.free[index - 1].size += range.size,
+			(false, true) => {
+				self.free[index].offset = range.offset;
+				self.free[index].size += range.size;
+			},
+			(false, false) => self.free.insert(index, range),
+		}
+		
+		Some(range)
+	}
+	
+	/// Moves all live allocations to the start of the buffer, in their current order, leaving a single free range at the end.
+	///
+	/// Returns the copies that must be done in the buffer, ordered by offset.
+	/// A copy may overlap its own source, but never the source of a later copy.
+	pub fn defragment(&mut self) -> Vec<BufferMove> {
+		let mut live: Vec<(BufferAllocationId, BufferRange)> = self.allocations.iter()
+			.map(|(id, range)| (*id, *range))
+			.collect();
+		
+		live.sort_by_key(|(_, range)| range.offset);
+		
+		let mut moves = vec![];
+		let mut cursor = 0;
+		
+		for (id, range) in live.iter() {
+			if range.offset != cursor {
+				moves.push(BufferMove {
+					from: range.offset,
+					to: cursor,
+					size: range.size,
+				});
+				
+				self.allocations.insert(*id, BufferRange {
+					offset: cursor,
+					size: range.size,
+				});
+			}
+			
+			cursor += range.size;
+		}
+		
+		self.free.clear();
+		if cursor < self.capacity {
+			self.free.push(BufferRange {
+				offset: cursor,
+				size: self.capacity - cursor,
+			});
+		}
+		
+		moves
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn range(offset: usize, size: usize) -> BufferRange {
+		BufferRange { offset, size }
+	}
+	
+	/// Allocates the given sizes back to back from an empty allocator.
+	fn allocator_with(capacity: usize, sizes: &[usize]) -> (BufferAllocator, Vec<BufferAllocationId>) {
+		let mut allocator = BufferAllocator::new(capacity);
+		let ids = sizes.iter().map(|size| allocator.allocate(*size).unwrap()).collect();
+		(allocator, ids)
+	}
+	
+	#[test]
+	fn allocates_back_to_back() {
+		let (allocator, ids) = allocator_with(100, &[10, 20, 30]);
+		
+		assert_eq!(allocator.get(ids[0]), Some(range(0, 10)));
+		assert_eq!(allocator.get(ids[1]), Some(range(10, 20)));
+		assert_eq!(allocator.get(ids[2]), Some(range(30, 30)));
+		assert_eq!(allocator.used(), 60);
+		assert_eq!(allocator.available(), 40);
+		assert_eq!(allocator.free, vec![range(60, 40)]);
+	}
+	
+	#[test]
+	fn allocation_fails_without_a_large_enough_range() {
+		let (mut allocator, ids) = allocator_with(30, &[10, 10, 10]);
+		allocator.free(ids[0]);
+		allocator.free(ids[2]);
+		
+		// 20 elements are free, but not in one piece.
+		assert_eq!(allocator.available(), 20);
+		assert_eq!(allocator.largest_free(), 10);
+		assert_eq!(allocator.allocate(11), None);
+		assert!(allocator.allocate(10).is_some());
+	}
+	
+	#[test]
+	fn allocation_uses_best_fit() {
+		// Free ranges of 30, 10 and (at the end) 40 elements.
+		let (mut allocator, ids) = allocator_with(100, &[30, 10, 10, 10]);
+		allocator.free(ids[0]);
+		allocator.free(ids[2]);
+		assert_eq!(allocator.free, vec![range(0, 30), range(40, 10), range(60, 40)]);
+		
+		// The smallest range that fits is used, even though an earlier one is large enough.
+		let exact = allocator.allocate(10).unwrap();
+		assert_eq!(allocator.get(exact), Some(range(40, 10)));
+		
+		let partial = allocator.allocate(25).unwrap();
+		assert_eq!(allocator.get(partial), Some(range(0, 25)));
+		
+		assert_eq!(allocator.free, vec![range(25, 5), range(60, 40)]);
+	}
+	
+	#[test]
+	fn free_merges_with_previous_range() {
+		let (mut allocator, ids) = allocator_with(40, &[10, 10, 10, 10]);
+		allocator.free(ids[0]);
+		
+		assert_eq!(allocator.free(ids[1]), Some(range(10, 10)));
+		assert_eq!(allocator.free, vec![range(0, 20)]);
+	}
+	
+	#[test]
+	fn free_merges_with_next_range() {
+		let (mut allocator, ids) = allocator_with(40, &[10, 10, 10, 10]);
+		allocator.free(ids[2]);
+		
+		assert_eq!(allocator.free(ids[1]), Some(range(10, 10)));
+		assert_eq!(allocator.free, vec![range(10, 20)]);
+	}
+	
+	#[test]
+	fn free_merges_with_both_ranges() {
+		let (mut allocator, ids) = allocator_with(50, &[10, 10, 10, 10]);
+		allocator.free(ids[0]);
+		allocator.free(ids[2]);
+		assert_eq!(allocator.free, vec![range(0, 10), range(20, 10), range(40, 10)]);
+		
+		allocator.free(ids[1]);
+		assert_eq!(allocator.free, vec![range(0, 30), range(40, 10)]);
+		
+		allocator.free(ids[3]);
+		assert_eq!(allocator.free, vec![range(0, 50)]);
+		assert_eq!(allocator.used(), 0);
+	}
+	
+	#[test]
+	fn free_without_neighbours_inserts_range() {
+		let (mut allocator, ids) = allocator_with(40, &[10, 10, 10, 10]);
+		allocator.free(ids[2]);
+		allocator.free(ids[0]);
+		
+		assert_eq!(allocator.free, vec![range(0, 10), range(20, 10)]);
+	}
+	
+	#[test]
+	fn free_of_unknown_id_returns_none() {
+		let (mut allocator, ids) = allocator_with(40, &[10]);
+		
+		assert_eq!(allocator.free(BufferAllocationId(1000)), None);
+		
+		// Freeing twice does nothing the second time.
+		assert!(allocator.free(ids[0]).is_some());
+		assert_eq!(allocator.free(ids[0]), None);
+		assert_eq!(allocator.get(ids[0]), None);
+		assert_eq!(allocator.free, vec![range(0, 40)]);
+		assert_eq!(allocator.used(), 0);
+	}
+	
+	#[test]
+	fn defragment_moves_allocations_in_order() {
+		let (mut allocator, ids) = allocator_with(100, &[10, 20, 5, 15, 10]);
+		allocator.free(ids[0]);
+		allocator.free(ids[2]);
+		
+		let moves = allocator.defragment();
+		
+		// The first allocation already in place is not moved.
+		assert_eq!(moves, vec![
+			BufferMove { from: 10, to: 0, size: 20 },
+			BufferMove { from: 35, to: 20, size: 15 },
+			BufferMove { from: 50, to: 35, size: 10 },
+		]);
+		
+		assert_eq!(allocator.get(ids[1]), Some(range(0, 20)));
+		assert_eq!(allocator.get(ids[3]), Some(range(20, 15)));
+		assert_eq!(allocator.get(ids[4]), Some(range(35, 10)));
+		assert_eq!(allocator.free, vec![range(45, 55)]);
+		assert_eq!(allocator.fragmentation(), 0.0);
+		
+		// Nothing to do the second time.
+		assert_eq!(allocator.defragment(), vec![]);
+	}
+	
+	#[test]
+	fn defragment_moves_never_overlap_later_sources() {
+		let sizes: Vec<usize> = (1..=20).collect();
+		let (mut allocator, ids) = allocator_with(1000, &sizes);
+		for id in ids.iter().step_by(3) {
+			allocator.free(*id);
+		}
+		
+		let moves = allocator.defragment();
+		assert!(!moves.is_empty());
+		
+		for (index, current) in moves.iter().enumerate() {
+			assert!(current.to < current.from);
+			
+			for later in moves[index + 1..].iter() {
+				assert!(current.from < later.from, "moves are not ordered by offset");
+				assert!(current.to + current.size <= later.from, "{:?} overwrites the source of {:?}", current, later);
+			}
+		}
+		
+		// The live allocations are packed without gaps.
+		let mut live: Vec<BufferRange> = ids.iter().filter_map(|id| allocator.get(*id)).collect();
+		live.sort_by_key(|range| range.offset);
+		
+		let mut cursor = 0;
+		for range in live.iter() {
+			assert_eq!(range.offset, cursor);
+			cursor = range.end();
+		}
+		
+		assert_eq!(allocator.free, vec![range(cursor, 1000 - cursor)]);
+	}
+	
+	#[test]
+	fn fragmentation() {
+		assert_eq!(BufferAllocator::new(0).fragmentation(), 0.0);
+		assert_eq!(BufferAllocator::new(100).fragmentation(), 0.0);
+		
+		// Full: Nothing to fragment.
+		let (mut allocator, ids) = allocator_with(50, &[10, 10, 10, 10, 10]);
+		assert_eq!(allocator.fragmentation(), 0.0);
+		
+		// Two free ranges of 10: Half the free space is unusable for a single allocation of 20.
+		allocator.free(ids[0]);
+		allocator.free(ids[4]);
+		assert_eq!(allocator.fragmentation(), 0.5);
+		
+		// Free ranges of 20 and 10.
+		allocator.free(ids[1]);
+		assert!((allocator.fragmentation() - 1.0 / 3.0).abs() < 1e-6);
+		
+		allocator.free(ids[2]);
+		allocator.free(ids[3]);
+		assert_eq!(allocator.fragmentation(), 0.0);
+	}
+}
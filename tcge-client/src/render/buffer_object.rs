@@ -133,6 +133,53 @@ impl BufferObject {
 		}
 	}
 	
+	/// Wraps BufferSubData, replacing the elements starting at the given element offset.
+	///
+	/// See: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glBufferSubData.xhtml
+	#[allow(dead_code)]
+	pub fn buffer_sub_data<Element>(&self, gl: &Gl, offset: usize, elements: &[Element]) {
+		let element_size = std::mem::size_of::<Element>();
+		let offset_bytes = offset * element_size;
+		let elements_bytes = elements.len() * element_size;
+		
+		assert!(offset_bytes + elements_bytes <= self.bytes, "Cannot upload data to buffer {}: Out of bounds.", self.id);
+		
+		if elements_bytes == 0 {
+			return;
+		}
+		
+		unsafe {
+			gl.BindBuffer(self.target, self.id);
+			gl.BufferSubData(
+				self.target,
+				offset_bytes as gl::types::GLintptr,
+				elements_bytes as gl::types::GLsizeiptr,
+				elements.as_ptr() as *const gl::types::GLvoid
+			);
+			gl.BindBuffer(self.target, 0);
+		}
+	}
+	
+	/// Wraps BufferData, replacing the whole data store of the buffer with the given elements.
+	///
+	/// See: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glBufferData.xhtml
+	#[allow(dead_code)]
+	pub fn buffer_data_replace<Element>(&mut self, gl: &Gl, usage: gl::types::GLenum, elements: &[Element]) {
+		self.items = elements.len();
+		self.bytes = self.items * std::mem::size_of::<Element>();
+
+		unsafe {
+			gl.BindBuffer(self.target, self.id);
+			gl.BufferData(
+				self.target,
+				self.bytes as gl::types::GLsizeiptr,
+				elements.as_ptr() as *const gl::types::GLvoid,
+				usage
+			);
+			gl.BindBuffer(self.target, 0);
+		}
+	}
+
 	pub fn buffer_mapped_upload<Element>(&self, gl: &Gl, elements: &[Element]) -> Result<(), String> {
 		let element_size = std::mem::size_of::<Element>();
 		let elements_len = elements.len();
@@ -1,5 +1,8 @@
+use cgmath::Matrix;
 use cgmath::Matrix4;
 use cgmath::Vector3;
+use cgmath::Vector4;
+use cgmath::InnerSpace;
 
 /// Represents a camera.
 ///
@@ -36,3 +39,146 @@ pub trait Camera {
 		out
 	}
 }
+
+/// A view-frustum, represented by six planes whose normals point inwards.
+///
+/// The planes are stored as `(a, b, c, d)`, such that `a*x + b*y + c*z + d >= 0` holds for every point inside.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+	pub planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+	/// Extracts the frustum planes from a combined `projection * view` matrix.
+	///
+	/// The resulting frustum lives in whatever space the view-matrix transforms from,
+	/// e.g. camera-relative space if the view-matrix contains no translation.
+	///
+	/// See: http://www.cs.otago.ac.nz/postgrads/alexis/planeExtraction.pdf
+	pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+		let row0 = matrix.row(0);
+		let row1 = matrix.row(1);
+		let row2 = matrix.row(2);
+		let row3 = matrix.row(3);
+		
+		let mut planes = [
+			row3 + row0, // Left
+			row3 - row0, // Right
+			row3 + row1, // Bottom
+			row3 - row1, // Top
+			row3 + row2, // Near
+			row3 - row2, // Far
+		];
+		
+		for plane in planes.iter_mut() {
+			let length = plane.truncate().magnitude();
+			if length > 0.0 {
+				*plane /= length;
+			}
+		}
+		
+		Self {
+			planes
+		}
+	}
+	
+	/// Returns the signed distance of the given point to the given plane.
+	#[inline]
+	fn distance(plane: &Vector4<f32>, point: &Vector3<f32>) -> f32 {
+		plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+	}
+	
+	/// Returns `true` if the given sphere is (partially) inside the frustum.
+	#[allow(dead_code)]
+	pub fn intersects_sphere(&self, center: &Vector3<f32>, radius: f32) -> bool {
+		self.planes.iter().all(|plane| Self::distance(plane, center) >= -radius)
+	}
+	
+	/// Returns `true` if the given axis-aligned box is (partially) inside the frustum.
+	///
+	/// This test is conservative: Boxes near the corners of the frustum may be reported as visible.
+	pub fn intersects_aabb(&self, min: &Vector3<f32>, max: &Vector3<f32>) -> bool {
+		for plane in self.planes.iter() {
+			// Pick the corner of the box that lies furthest along the plane normal...
+			let corner = Vector3::new(
+				if plane.x >= 0.0 { max.x } else { min.x },
+				if plane.y >= 0.0 { max.y } else { min.y },
+				if plane.z >= 0.0 { max.z } else { min.z },
+			);
+			
+			// ...and if even that one is outside, the whole box is.
+			if Self::distance(plane, &corner) < 0.0 {
+				return false;
+			}
+		}
+		
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cgmath::{Deg, Point3};
+	
+	/// A camera at the origin looking down -Z, with a 90° field of view: Inside, `|x| <= -z` and `|y| <= -z`.
+	fn frustum() -> Frustum {
+		let projection = cgmath::perspective(Deg(90.0), 1.0, 0.1, 100.0);
+		let view = Matrix4::look_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::unit_y());
+		Frustum::from_matrix(&(projection * view))
+	}
+	
+	fn aabb(frustum: &Frustum, min: (f32, f32, f32), max: (f32, f32, f32)) -> bool {
+		frustum.intersects_aabb(&Vector3::new(min.0, min.1, min.2), &Vector3::new(max.0, max.1, max.2))
+	}
+	
+	fn sphere(frustum: &Frustum, center: (f32, f32, f32), radius: f32) -> bool {
+		frustum.intersects_sphere(&Vector3::new(center.0, center.1, center.2), radius)
+	}
+	
+	#[test]
+	fn inside() {
+		let frustum = frustum();
+		assert!(aabb(&frustum, (-1.0, -1.0, -11.0), (1.0, 1.0, -9.0)));
+		assert!(sphere(&frustum, (0.0, 0.0, -10.0), 1.0));
+	}
+	
+	#[test]
+	fn behind() {
+		let frustum = frustum();
+		assert!(!aabb(&frustum, (-1.0, -1.0, 5.0), (1.0, 1.0, 7.0)));
+		assert!(!sphere(&frustum, (0.0, 0.0, 5.0), 1.0));
+	}
+	
+	#[test]
+	fn left() {
+		let frustum = frustum();
+		assert!(!aabb(&frustum, (-30.0, -1.0, -11.0), (-25.0, 1.0, -9.0)));
+		assert!(!sphere(&frustum, (-30.0, 0.0, -10.0), 1.0));
+		assert!(!sphere(&frustum, (-12.0, 0.0, -10.0), 1.0));
+	}
+	
+	#[test]
+	fn beyond_far() {
+		let frustum = frustum();
+		assert!(!aabb(&frustum, (-1.0, -1.0, -120.0), (1.0, 1.0, -110.0)));
+		assert!(!sphere(&frustum, (0.0, 0.0, -150.0), 10.0));
+	}
+	
+	#[test]
+	fn straddling() {
+		let frustum = frustum();
+		
+		// The left plane.
+		assert!(aabb(&frustum, (-12.0, -1.0, -11.0), (-8.0, 1.0, -9.0)));
+		assert!(sphere(&frustum, (-10.5, 0.0, -10.0), 1.0));
+		
+		// The near plane.
+		assert!(aabb(&frustum, (-1.0, -1.0, -1.0), (1.0, 1.0, 1.0)));
+		assert!(sphere(&frustum, (0.0, 0.0, 0.5), 1.0));
+		
+		// The far plane.
+		assert!(aabb(&frustum, (-1.0, -1.0, -105.0), (1.0, 1.0, -95.0)));
+		assert!(sphere(&frustum, (0.0, 0.0, -100.5), 1.0));
+	}
+}
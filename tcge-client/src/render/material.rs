@@ -0,0 +1,552 @@
+//! Materials: Shader programs described by a definition file, instead of by code.
+//!
+//! A material definition is a TOML file, usually in `core:materials/`. It may contain:
+//!
+//! - `name`: The name of the program, for debugging; defaults to the location of the file.
+//! - `[shaders]`: The source of each stage, by stage name (`vertex`, `fragment`, `geometry`, `compute`).
+//! - `[uniforms]`: The uniforms the material uses, by name. Each is either a type name, or a table with a `type`
+//!   and a `default` value. The types are `float`, `int`, `vec2`, `vec3`, `vec4` and `mat4`;
+//!   vectors and matrices are written as flat arrays of numbers.
+//! - `[textures]`: The sampler uniforms, by name, with the texture unit each one reads from.
+//! - `[defines]`: Preprocessor defines for every stage, by name; the values are strings, numbers or booleans.
+//!   This allows several variants of a material to share their shaders.
+//! - `[state]`: The render state to switch to while the material is bound: `depth_test`, `depth_write` and `cull_face`
+//!   (booleans), and `blend` (`none`, `alpha`, `additive` or `invert`). Anything not listed is left alone.
+//!
+//! Uniforms that are declared but missing from the linked program (e.g. because the compiler optimized them out)
+//! only cause a warning; setting them does nothing.
+
+use super::*;
+use common::resources::{Resources, ResourceProvider, ResourceLocation, ResourceLocationError, ResourceError};
+use rustc_hash::FxHashMap;
+
+/// The types of uniforms a material can declare.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UniformKind {
+	Float,
+	Int,
+	Vec2,
+	Vec3,
+	Vec4,
+	Mat4,
+}
+
+impl UniformKind {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"float" => Some(UniformKind::Float),
+			"int" => Some(UniformKind::Int),
+			"vec2" => Some(UniformKind::Vec2),
+			"vec3" => Some(UniformKind::Vec3),
+			"vec4" => Some(UniformKind::Vec4),
+			"mat4" => Some(UniformKind::Mat4),
+			_ => None,
+		}
+	}
+	
+	/// The number of floats in a value of this type; 1 for integers.
+	pub fn components(&self) -> usize {
+		match self {
+			UniformKind::Float | UniformKind::Int => 1,
+			UniformKind::Vec2 => 2,
+			UniformKind::Vec3 => 3,
+			UniformKind::Vec4 => 4,
+			UniformKind::Mat4 => 16,
+		}
+	}
+}
+
+/// A value for a uniform.
+#[derive(Clone, Debug, PartialEq)]
+pub enum UniformValue {
+	Int(i32),
+	
+	/// The components of a `float`, vector or (column-major) matrix.
+	Floats(smallvec::SmallVec<[f32; 4]>),
+}
+
+impl UniformValue {
+	/// Sets the uniform at the given location of the program currently in use.
+	pub fn apply(&self, program: &ProgramObject, kind: UniformKind, location: UniformLocation) -> bool {
+		match (kind, self) {
+			(UniformKind::Int, UniformValue::Int(value)) => {
+				if location == -1 {return false}
+				unsafe { program.gl.Uniform1i(location, *value); }
+				true
+			},
+			(UniformKind::Float, UniformValue::Floats(values)) if values.len() == 1 => program.set_uniform_scalar(location, values[0]),
+			(UniformKind::Vec2, UniformValue::Floats(values)) => program.set_uniform_vector2_raw(location, values),
+			(UniformKind::Vec3, UniformValue::Floats(values)) => program.set_uniform_vector3_raw(location, values),
+			(UniformKind::Vec4, UniformValue::Floats(values)) => program.set_uniform_vector4_raw(location, values),
+			(UniformKind::Mat4, UniformValue::Floats(values)) => program.set_uniform_matrix4_raw(location, values),
+			_ => false,
+		}
+	}
+}
+
+/// How the colour of a fragment is combined with the colour already in the framebuffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+	None,
+	
+	/// Classic alpha blending.
+	Alpha,
+	
+	/// Adds the colour, weighted by its alpha.
+	Additive,
+	
+	/// Inverts the colour behind the fragment; used for things that must be visible on any background.
+	Invert,
+}
+
+impl BlendMode {
+	pub fn from_name(name: &str) -> Option<Self> {
+		match name {
+			"none" => Some(BlendMode::None),
+			"alpha" => Some(BlendMode::Alpha),
+			"additive" => Some(BlendMode::Additive),
+			"invert" => Some(BlendMode::Invert),
+			_ => None,
+		}
+	}
+}
+
+/// The render state of a material; `None` means the state is left as it is.
+///
+/// Unbinding a material resets everything it changed to the default state:
+/// Depth test and writes enabled, back faces culled, blending disabled.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MaterialState {
+	pub depth_test: Option<bool>,
+	pub depth_write: Option<bool>,
+	pub cull_face: Option<bool>,
+	pub blend: Option<BlendMode>,
+}
+
+impl MaterialState {
+	pub fn apply(&self, gl: &Gl) {
+		unsafe {
+			if let Some(depth_test) = self.depth_test {
+				Self::set_enabled(gl, gl::DEPTH_TEST, depth_test);
+			}
+			
+			if let Some(depth_write) = self.depth_write {
+				gl.DepthMask(if depth_write {gl::TRUE} else {gl::FALSE});
+			}
+			
+			if let Some(cull_face) = self.cull_face {
+				Self::set_enabled(gl, gl::CULL_FACE, cull_face);
+			}
+			
+			match self.blend {
+				Some(BlendMode::None) => gl.Disable(gl::BLEND),
+				Some(BlendMode::Alpha) => {
+					gl.Enable(gl::BLEND);
+					gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+				},
+				Some(BlendMode::Additive) => {
+					gl.Enable(gl::BLEND);
+					gl.BlendFunc(gl::SRC_ALPHA, gl::ONE);
+				},
+				Some(BlendMode::Invert) => {
+					gl.Enable(gl::BLEND);
+					gl.BlendFunc(gl::ONE_MINUS_DST_COLOR, gl::ZERO);
+				},
+				None => (),
+			}
+		}
+	}
+	
+	/// Resets the state changed by `apply` to the default.
+	pub fn restore(&self, gl: &Gl) {
+		unsafe {
+			if self.depth_test.is_some() {
+				gl.Enable(gl::DEPTH_TEST);
+			}
+			
+			if self.depth_write.is_some() {
+				gl.DepthMask(gl::TRUE);
+			}
+			
+			if self.cull_face.is_some() {
+				gl.Enable(gl::CULL_FACE);
+			}
+			
+			if self.blend.is_some() {
+				gl.Disable(gl::BLEND);
+				gl.BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			}
+		}
+	}
+	
+	unsafe fn set_enabled(gl: &Gl, capability: gl::types::GLenum, enabled: bool) {
+		if enabled {
+			gl.Enable(capability);
+		} else {
+			gl.Disable(capability);
+		}
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub struct MaterialUniformDef {
+	pub name: String,
+	pub kind: UniformKind,
+	pub default: Option<UniformValue>,
+}
+
+/// A material definition, as written in its file.
+#[derive(Clone, Debug)]
+pub struct MaterialDef {
+	pub name: String,
+	
+	/// The kind and source location of each shader stage.
+	pub stages: Vec<(gl::types::GLenum, ResourceLocation)>,
+	pub uniforms: Vec<MaterialUniformDef>,
+	
+	/// The sampler uniforms, and the texture unit of each.
+	pub textures: Vec<(String, i32)>,
+	
+	/// The defines inserted into every stage.
+	pub defines: Vec<(String, String)>,
+	pub state: MaterialState,
+}
+
+impl MaterialDef {
+	pub fn load(res: &Resources, location: &ResourceLocation) -> Result<Self, MaterialError> {
+		let source = res.res_as_string(location)
+			.map_err(|err| MaterialError::Resource(location.to_string(), err))?;
+		
+		Self::parse(&location.to_string(), &source)
+			.map_err(|err| MaterialError::Parse(location.to_string(), err))
+	}
+	
+	/// Parses a material definition; the `name` is used unless the definition has one of its own.
+	pub fn parse(name: &str, source: &str) -> Result<Self, String> {
+		let root = match toml::from_str(source) {
+			Ok(toml::Value::Table(root)) => root,
+			Ok(_) => return Err("Root is not a table".to_string()),
+			Err(e) => return Err(e.to_string()),
+		};
+		
+		let name = match root.get("name") {
+			Some(toml::Value::String(name)) => name.clone(),
+			Some(_) => return Err("'name' must be a string".to_string()),
+			None => name.to_string(),
+		};
+		
+		let mut stages = vec![];
+		match root.get("shaders") {
+			Some(toml::Value::Table(table)) => for (stage, value) in table.iter() {
+				let kind = match stage.as_str() {
+					"vertex" => gl::VERTEX_SHADER,
+					"fragment" => gl::FRAGMENT_SHADER,
+					"geometry" => gl::GEOMETRY_SHADER,
+					"compute" => gl::COMPUTE_SHADER,
+					_ => return Err(format!("Unknown shader stage '{}'", stage)),
+				};
+				
+				match value {
+					toml::Value::String(location) => stages.push((kind, ResourceLocation::parse(location)
+						.map_err(|err| format!("Shader stage '{}': {}", stage, err))?)),
+					_ => return Err(format!("Shader stage '{}' must be a resource location", stage)),
+				}
+			},
+			Some(_) => return Err("'shaders' must be a table".to_string()),
+			None => return Err("Material has no 'shaders'".to_string()),
+		};
+		
+		let mut uniforms = vec![];
+		match root.get("uniforms") {
+			Some(toml::Value::Table(table)) => for (name, value) in table.iter() {
+				uniforms.push(Self::parse_uniform(name, value)?);
+			},
+			Some(_) => return Err("'uniforms' must be a table".to_string()),
+			None => (),
+		};
+		
+		let mut textures = vec![];
+		match root.get("textures") {
+			Some(toml::Value::Table(table)) => for (name, value) in table.iter() {
+				match value {
+					toml::Value::Integer(unit) if *unit >= 0 => textures.push((name.clone(), *unit as i32)),
+					_ => return Err(format!("Texture '{}' must be a non-negative texture unit", name)),
+				}
+			},
+			Some(_) => return Err("'textures' must be a table".to_string()),
+			None => (),
+		};
+		
+		let mut defines = vec![];
+		match root.get("defines") {
+			Some(toml::Value::Table(table)) => for (name, value) in table.iter() {
+				let value = match value {
+					toml::Value::String(value) => value.clone(),
+					toml::Value::Integer(value) => value.to_string(),
+					toml::Value::Float(value) => format!("{:?}", value),
+					toml::Value::Boolean(value) => (*value as i32).to_string(),
+					_ => return Err(format!("Define '{}' must be a string, number or boolean", name)),
+				};
+				defines.push((name.clone(), value));
+			},
+			Some(_) => return Err("'defines' must be a table".to_string()),
+			None => (),
+		};
+		
+		let state = match root.get("state") {
+			Some(value) => Self::parse_state(value)?,
+			None => MaterialState::default(),
+		};
+		
+		Ok(Self {
+			name,
+			stages,
+			uniforms,
+			textures,
+			defines,
+			state,
+		})
+	}
+	
+	fn parse_uniform(name: &str, value: &toml::Value) -> Result<MaterialUniformDef, String> {
+		let (kind, default) = match value {
+			toml::Value::String(kind) => (kind, None),
+			toml::Value::Table(table) => match table.get("type") {
+				Some(toml::Value::String(kind)) => (kind, table.get("default")),
+				_ => return Err(format!("Uniform '{}' must have a 'type'", name)),
+			},
+			_ => return Err(format!("Uniform '{}' must be a type name or a table", name)),
+		};
+		
+		let kind = UniformKind::from_name(kind)
+			.ok_or_else(|| format!("Uniform '{}' has an unknown type '{}'", name, kind))?;
+		
+		let default = match default {
+			Some(value) => Some(Self::parse_value(kind, value)
+				.ok_or_else(|| format!("Default of uniform '{}' is not a valid {:?}", name, kind))?),
+			None => None,
+		};
+		
+		Ok(MaterialUniformDef {
+			name: name.to_string(),
+			kind,
+			default,
+		})
+	}
+	
+	fn parse_value(kind: UniformKind, value: &toml::Value) -> Option<UniformValue> {
+		let number = |value: &toml::Value| match value {
+			toml::Value::Float(value) => Some(*value as f32),
+			toml::Value::Integer(value) => Some(*value as f32),
+			_ => None,
+		};
+		
+		match (kind, value) {
+			(UniformKind::Int, toml::Value::Integer(value)) => Some(UniformValue::Int(*value as i32)),
+			(UniformKind::Int, _) => None,
+			(UniformKind::Float, value) => Some(UniformValue::Floats(smallvec![number(value)?])),
+			(kind, toml::Value::Array(values)) if values.len() == kind.components() => {
+				let mut floats = smallvec::SmallVec::new();
+				for value in values.iter() {
+					floats.push(number(value)?);
+				}
+				Some(UniformValue::Floats(floats))
+			},
+			_ => None,
+		}
+	}
+	
+	fn parse_state(value: &toml::Value) -> Result<MaterialState, String> {
+		let table = match value {
+			toml::Value::Table(table) => table,
+			_ => return Err("'state' must be a table".to_string()),
+		};
+		
+		let flag = |key: &str| match table.get(key) {
+			Some(toml::Value::Boolean(value)) => Ok(Some(*value)),
+			Some(_) => Err(format!("State '{}' must be a boolean", key)),
+			None => Ok(None),
+		};
+		
+		let blend = match table.get("blend") {
+			Some(toml::Value::String(mode)) => Some(BlendMode::from_name(mode)
+				.ok_or_else(|| format!("Unknown blend mode '{}'", mode))?),
+			Some(_) => return Err("State 'blend' must be a string".to_string()),
+			None => None,
+		};
+		
+		Ok(MaterialState {
+			depth_test: flag("depth_test")?,
+			depth_write: flag("depth_write")?,
+			cull_face: flag("cull_face")?,
+			blend,
+		})
+	}
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+
+/// A linked shader program, with its declared uniforms and render state.
+pub struct Material {
+	pub program: ProgramObject,
+	
+	/// The location of each declared uniform and sampler; `-1` if the program does not have it.
+	pub uniforms: FxHashMap<String, UniformLocation>,
+	pub state: MaterialState,
+	
+	/// The locations of the files the material was built from: Its definition, shaders and their includes.
+	pub files: Vec<ResourceLocation>,
+}
+
+impl Material {
+	/// Loads the definition at the given location, and builds the material from it.
+	pub fn load(gl: &Gl, res: &Resources, location: &ResourceLocation) -> Result<Self, MaterialError> {
+		Self::load_with(gl, res, location, &mut |_, source| source)
+	}
+	
+	/// Like `load`, but passes the source of each stage through `preprocess` before it is compiled.
+	///
+	/// `preprocess` runs after the includes and defines are resolved; code it inserts right after the `#version`
+	/// is followed by a `#line` directive, so it does not disturb the line numbers in compiler errors.
+	pub fn load_with(
+		gl: &Gl,
+		res: &Resources,
+		location: &ResourceLocation,
+		preprocess: &mut dyn FnMut(gl::types::GLenum, String) -> String
+	) -> Result<Self, MaterialError> {
+		let def = MaterialDef::load(res, location)?;
+		let mut material = Self::build(gl, res, &def, preprocess)?;
+		material.files.insert(0, location.clone());
+		Ok(material)
+	}
+	
+	/// Compiles and links the shaders of the definition, looks up its uniforms and sets their defaults.
+	pub fn build(
+		gl: &Gl,
+		res: &Resources,
+		def: &MaterialDef,
+		preprocess: &mut dyn FnMut(gl::types::GLenum, String) -> String
+	) -> Result<Self, MaterialError> {
+		debug!("Building material: {}", def.name);
+		
+		let mut shaders: smallvec::SmallVec<[ShaderObjectRef; 2]> = smallvec![];
+		let mut files = vec![];
+		
+		for (kind, location) in def.stages.iter() {
+			let source = preprocess_shader(res, location, &def.defines)
+				.map_err(|err| MaterialError::Preprocess(err))?;
+			
+			for file in source.files.iter() {
+				if !files.contains(file) {
+					files.push(file.clone());
+				}
+			}
+			
+			let code = CString::new(preprocess(*kind, source.source.clone()))
+				.map_err(|err| MaterialError::Resource(location.to_string(), ResourceError::HasNil(err.nul_position())))?;
+			
+			let shader = ShaderObject::new_shader(gl, &code, *kind)
+				.map_err(|err| MaterialError::Shader(location.to_string(), source.map_log(&err)))?;
+			
+			shaders.push(shader);
+		}
+		
+		let program = ProgramObject::new(gl, &def.name, &shaders)
+			.map_err(|err| MaterialError::Shader(def.name.clone(), err))?;
+		
+		let mut uniforms = FxHashMap::default();
+		program.set_used();
+		
+		for uniform in def.uniforms.iter() {
+			// A missing uniform is warned about, and then ignored.
+			let location = program.get_uniform_location(&uniform.name).unwrap_or(-1);
+			
+			if let Some(default) = &uniform.default {
+				default.apply(&program, uniform.kind, location);
+			}
+			
+			uniforms.insert(uniform.name.clone(), location);
+		}
+		
+		for (name, unit) in def.textures.iter() {
+			let location = program.get_uniform_location(name).unwrap_or(-1);
+			UniformValue::Int(*unit).apply(&program, UniformKind::Int, location);
+			
+			uniforms.insert(name.clone(), location);
+		}
+		
+		unsafe {
+			gl.UseProgram(0);
+		}
+		
+		Ok(Self {
+			program,
+			uniforms,
+			state: def.state,
+			files,
+		})
+	}
+	
+	/// Returns the location of a declared uniform; `-1` (which every setter ignores) if it is missing.
+	pub fn uniform(&self, name: &str) -> UniformLocation {
+		match self.uniforms.get(name) {
+			Some(location) => *location,
+			None => {
+				warn!("Uniform '{}' is not declared by material '{}'.", name, self.program.name);
+				-1
+			}
+		}
+	}
+	
+	/// Returns whether any of the given resource locations is one of the files of the material.
+	pub fn is_affected_by(&self, changed: &[ResourceLocation]) -> bool {
+		changed.iter().any(|location| self.files.contains(location))
+	}
+	
+	/// Uses the program and switches to the render state of the material.
+	pub fn bind(&self) {
+		self.program.set_used();
+		self.state.apply(&self.program.gl);
+	}
+	
+	/// Resets the render state changed by `bind`.
+	pub fn unbind(&self) {
+		self.state.restore(&self.program.gl);
+	}
+}
+
+pub enum MaterialError {
+	/// A file of the material could not be read.
+	Resource(String, ResourceError),
+	
+	/// The definition of the material is invalid.
+	Parse(String, String),
+	
+	/// The source of a shader could not be preprocessed.
+	Preprocess(ShaderPreprocessError),
+	
+	/// A shader failed to compile, or the program failed to link.
+	Shader(String, String),
+}
+
+impl std::fmt::Display for MaterialError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			MaterialError::Resource(file, ResourceError::NotFound) => write!(f, "Could not find material file '{}'", file),
+			MaterialError::Resource(file, ResourceError::Io(err)) => write!(f, "Could not read material file '{}': {}", file, err),
+			MaterialError::Resource(file, ResourceError::HasNil(position)) => write!(f, "Material file '{}' contains a NUL byte at {}", file, position),
+			MaterialError::Resource(file, _) => write!(f, "Could not read material file '{}'", file),
+			MaterialError::Parse(file, error) => write!(f, "Could not parse material '{}': {}", file, error),
+			MaterialError::Preprocess(error) => write!(f, "Could not preprocess shader: {}", error),
+			MaterialError::Shader(name, error) => write!(f, "Could not compile '{}': {}", name, error),
+		}
+	}
+}
+
+impl From<ResourceLocationError> for MaterialError {
+	fn from(err: ResourceLocationError) -> Self {
+		MaterialError::Parse(err.location.clone(), err.reason.to_string())
+	}
+}
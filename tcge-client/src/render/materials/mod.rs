@@ -1,3 +1,7 @@
 pub mod solid_color;
 pub use solid_color::*;
 
+/// Shader code shared between materials, to be pulled in with `#include`.
+pub const SHADER_LIBRARY_FILES: [(&str, &[u8]); 1] = [
+	("core/shaders/lib/color.glsl", include_bytes!("lib/color.glsl")),
+];
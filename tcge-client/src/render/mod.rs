@@ -16,18 +16,34 @@ pub mod buffer_object;
 pub use buffer_object::BufferObject;
 pub use buffer_object::BufferObjectRef;
 
+pub mod buffer_allocator;
+pub use buffer_allocator::BufferAllocator;
+pub use buffer_allocator::BufferAllocationId;
+pub use buffer_allocator::BufferRange;
+
 pub mod texture_object;
 pub use texture_object::TextureObject;
 pub use texture_object::TextureObjectBuilder;
-pub use texture_object::TextureError;
+pub use texture_object::TextureLoader;
 
 pub mod vertex_array;
 pub use vertex_array::VertexArray;
 pub use vertex_array::VertexArrayAttrib;
 pub use vertex_array::VertexArrayBuilder;
 
+pub mod shader_preprocessor;
+pub use shader_preprocessor::ShaderPreprocessError;
+pub use shader_preprocessor::preprocess_shader;
+
+pub mod screenshot;
+
+pub mod material;
+pub use material::Material;
+pub use material::MaterialError;
+
 pub mod camera;
 pub use camera::Camera;
+pub use camera::Frustum;
 
 // --- 'Special' submodules...
 pub mod text;
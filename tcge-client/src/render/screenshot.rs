@@ -0,0 +1,37 @@
+use super::super::gl;
+use common::resources::{ResourceStorage, ResourceLocation, ResourceError};
+use image::png::PNGEncoder;
+use image::ColorType;
+
+/// Reads the framebuffer as RGBA rows, from top to bottom.
+///
+/// Must be called before the buffers are swapped.
+pub fn capture(gl: &gl::Gl, width: i32, height: i32) -> Vec<u8> {
+	let row = width as usize * 4;
+	let mut pixels = vec![0u8; row * height as usize];
+	
+	unsafe {
+		gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+		gl.ReadBuffer(gl::BACK);
+		gl.ReadPixels(0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+	}
+	
+	// OpenGL returns the rows from the bottom up.
+	let mut flipped = Vec::with_capacity(pixels.len());
+	for line in pixels.chunks(row).rev() {
+		flipped.extend_from_slice(line);
+	}
+	
+	flipped
+}
+
+/// Encodes the captured pixels as PNG and writes them to the storage.
+pub fn save(storage: &dyn ResourceStorage, location: &ResourceLocation, width: i32, height: i32, pixels: &[u8]) -> Result<(), ResourceError> {
+	let mut buffer = vec![];
+	
+	PNGEncoder::new(&mut buffer)
+		.encode(pixels, width as u32, height as u32, ColorType::RGBA(8))
+		.map_err(|ioe| ResourceError::Io(ioe))?;
+	
+	storage.write_buffer(location, &buffer)
+}
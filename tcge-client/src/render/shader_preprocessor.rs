@@ -0,0 +1,436 @@
+//! A small GLSL preprocessor, run on shader sources before they are handed to the driver.
+//!
+//! It understands two things the GLSL compiler does not:
+//!
+//! - `#include "core:shaders/lib/..."` is replaced by the file at that resource location.
+//!   Every file is included at most once per shader, so includes may freely depend on each other.
+//!   Includes are resolved before any `#if`, so they cannot be made conditional.
+//! - A list of defines is inserted right after the `#version` directive, as `#define NAME VALUE`.
+//!
+//! Every file gets a GLSL source-string number (its index in `ShaderSource::files`),
+//! and `#line` directives are emitted around each include and the inserted defines,
+//! so the line numbers the compiler reports still point into the original files.
+//! `ShaderSource::map_log` translates a compiler log back to file names.
+//!
+//! The preprocessor works on plain text; it only touches `Resources` through the `load` callback.
+
+use common::resources::{Resources, ResourceProvider, ResourceLocation, ResourceError};
+use rustc_hash::FxHashSet;
+
+/// The output of the preprocessor.
+#[derive(Clone, Debug)]
+pub struct ShaderSource {
+	/// The complete source of the shader, to be compiled.
+	pub source: String,
+	
+	/// The resource location of every file in the source, by GLSL source-string number.
+	pub files: Vec<ResourceLocation>,
+}
+
+impl ShaderSource {
+	/// Replaces the `<source>:<line>` and `<source>(<line>)` references in a compiler log with `<file>:<line>`.
+	///
+	/// Covers the formats of the common drivers (e.g. `0:12(5): error` and `0(12) : error`);
+	/// only the first reference of every line of the log is replaced.
+	pub fn map_log(&self, log: &str) -> String {
+		let mut mapped = String::with_capacity(log.len());
+		
+		for (index, line) in log.lines().enumerate() {
+			if index > 0 {
+				mapped.push('\n');
+			}
+			
+			match self.find_reference(line) {
+				Some((start, end, file, number)) => {
+					mapped.push_str(&line[..start]);
+					mapped.push_str(&format!("{}:{}", file, number));
+					mapped.push_str(&line[end..]);
+				},
+				None => mapped.push_str(line),
+			}
+		}
+		
+		mapped
+	}
+	
+	/// Finds the first reference to a known source string in a line of a compiler log.
+	fn find_reference<'a>(&'a self, line: &str) -> Option<(usize, usize, &'a ResourceLocation, usize)> {
+		let bytes = line.as_bytes();
+		let digits = |from: usize| bytes[from..].iter().take_while(|b| b.is_ascii_digit()).count();
+		
+		for start in 0..bytes.len() {
+			if !bytes[start].is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+				continue;
+			}
+			
+			let source_end = start + digits(start);
+			let (number_start, closing) = match bytes.get(source_end) {
+				Some(b':') => (source_end + 1, None),
+				Some(b'(') => (source_end + 1, Some(b')')),
+				_ => continue,
+			};
+			
+			let number_end = number_start + digits(number_start);
+			if number_end == number_start {
+				continue;
+			}
+			
+			let end = match closing {
+				Some(closing) if bytes.get(number_end) == Some(&closing) => number_end + 1,
+				Some(_) => continue,
+				None => number_end,
+			};
+			
+			let file = match line[start..source_end].parse::<usize>().ok().and_then(|source| self.files.get(source)) {
+				Some(file) => file,
+				None => continue,
+			};
+			
+			let number = line[number_start..number_end].parse().ok()?;
+			return Some((start, end, file, number));
+		}
+		
+		None
+	}
+}
+
+pub enum ShaderPreprocessError {
+	/// A file could not be loaded.
+	Resource(String, ResourceError),
+	
+	/// A directive could not be understood; with the file and line it is on.
+	Syntax(String, usize, String),
+}
+
+impl std::fmt::Display for ShaderPreprocessError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			ShaderPreprocessError::Resource(file, ResourceError::NotFound) => write!(f, "Could not find shader file '{}'", file),
+			ShaderPreprocessError::Resource(file, ResourceError::Io(err)) => write!(f, "Could not read shader file '{}': {}", file, err),
+			ShaderPreprocessError::Resource(file, _) => write!(f, "Could not read shader file '{}'", file),
+			ShaderPreprocessError::Syntax(file, line, error) => write!(f, "{}:{}: {}", file, line, error),
+		}
+	}
+}
+
+/// Preprocesses the shader at the given location, loading it and its includes from the resources.
+pub fn preprocess_shader(
+	res: &Resources,
+	location: &ResourceLocation,
+	defines: &[(String, String)]
+) -> Result<ShaderSource, ShaderPreprocessError> {
+	preprocess_shader_with(location, defines, &mut |file| {
+		res.res_as_string(file)
+	})
+}
+
+/// Preprocesses the shader at the given location, loading it and its includes with `load`.
+pub fn preprocess_shader_with(
+	location: &ResourceLocation,
+	defines: &[(String, String)],
+	load: &mut dyn FnMut(&ResourceLocation) -> Result<String, ResourceError>
+) -> Result<ShaderSource, ShaderPreprocessError> {
+	let mut preprocessor = Preprocessor {
+		load,
+		files: vec![],
+		included: FxHashSet::default(),
+		output: String::new(),
+	};
+	
+	let main = preprocessor.open(location)?;
+	let mut lines = main.lines().enumerate().peekable();
+	
+	// The `#version` directive must stay the first line; the defines follow it.
+	let mut next_line = 1;
+	if let Some((_, line)) = lines.peek() {
+		if line.trim_start().starts_with("#version") {
+			preprocessor.output.push_str(line);
+			preprocessor.output.push('\n');
+			lines.next();
+			next_line = 2;
+		}
+	}
+	
+	for (name, value) in defines.iter() {
+		preprocessor.output.push_str(&format!("#define {} {}\n", name, value));
+	}
+	
+	preprocessor.output.push_str(&format!("#line {} 0\n", next_line));
+	preprocessor.process(0, lines)?;
+	
+	Ok(ShaderSource {
+		source: preprocessor.output,
+		files: preprocessor.files,
+	})
+}
+
+struct Preprocessor<'a> {
+	load: &'a mut dyn FnMut(&ResourceLocation) -> Result<String, ResourceError>,
+	files: Vec<ResourceLocation>,
+	included: FxHashSet<ResourceLocation>,
+	output: String,
+}
+
+impl<'a> Preprocessor<'a> {
+	/// Loads a file, and gives it the next source-string number.
+	fn open(&mut self, location: &ResourceLocation) -> Result<String, ShaderPreprocessError> {
+		let source = (self.load)(location)
+			.map_err(|err| ShaderPreprocessError::Resource(location.to_string(), err))?;
+		
+		self.files.push(location.clone());
+		self.included.insert(location.clone());
+		Ok(source)
+	}
+	
+	/// Copies the (zero-based, numbered) lines of the given file to the output, expanding its includes.
+	fn process<'l>(
+		&mut self,
+		file: usize,
+		lines: impl Iterator<Item=(usize, &'l str)>
+	) -> Result<(), ShaderPreprocessError> {
+		for (index, line) in lines {
+			let directive = line.trim_start();
+			
+			if file > 0 && directive.starts_with("#version") {
+				return Err(self.syntax_error(file, index, "Included files must not have a '#version'"));
+			}
+			
+			if !directive.starts_with("#include") {
+				self.output.push_str(line);
+				self.output.push('\n');
+				continue;
+			}
+			
+			let location = Self::parse_include(directive)
+				.ok_or_else(|| self.syntax_error(file, index, "Expected '#include \"<resource location>\"'"))?;
+			
+			let location = ResourceLocation::parse(location)
+				.map_err(|err| self.syntax_error(file, index, &err.to_string()))?;
+			
+			if self.included.contains(&location) {
+				// Keep the line count, so the following lines need no '#line'.
+				self.output.push('\n');
+				continue;
+			}
+			
+			let source = self.open(&location)?;
+			let included = self.files.len() - 1;
+			
+			self.output.push_str(&format!("#line 1 {}\n", included));
+			self.process(included, source.lines().enumerate())?;
+			self.output.push_str(&format!("#line {} {}\n", index + 2, file));
+		}
+		
+		Ok(())
+	}
+	
+	/// Returns the location in a `#include "<location>"` directive.
+	fn parse_include(directive: &str) -> Option<&str> {
+		let rest = directive["#include".len()..].trim();
+		
+		if rest.len() < 2 || !rest.starts_with('"') || !rest.ends_with('"') {
+			return None;
+		}
+		
+		Some(&rest[1..rest.len() - 1]).filter(|location| !location.is_empty())
+	}
+	
+	fn syntax_error(&self, file: usize, index: usize, error: &str) -> ShaderPreprocessError {
+		ShaderPreprocessError::Syntax(self.files[file].to_string(), index + 1, error.to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rustc_hash::FxHashMap;
+	
+	fn preprocess(
+		files: &[(&str, &str)],
+		main: &str,
+		defines: &[(&str, &str)]
+	) -> Result<ShaderSource, ShaderPreprocessError> {
+		let files: FxHashMap<ResourceLocation, String> = files.iter()
+			.map(|(location, source)| (ResourceLocation::parse(location).unwrap(), source.to_string()))
+			.collect();
+		
+		let defines: Vec<(String, String)> = defines.iter()
+			.map(|(name, value)| (name.to_string(), value.to_string()))
+			.collect();
+		
+		preprocess_shader_with(&ResourceLocation::parse(main).unwrap(), &defines, &mut |location| {
+			files.get(location).cloned().ok_or(ResourceError::NotFound)
+		})
+	}
+	
+	fn unwrap(result: Result<ShaderSource, ShaderPreprocessError>) -> ShaderSource {
+		match result {
+			Ok(source) => source,
+			Err(err) => panic!("{}", err),
+		}
+	}
+	
+	#[test]
+	fn source_without_directives_is_unchanged() {
+		let source = unwrap(preprocess(&[
+			("core:shaders/main.vert", "#version 330 core\nvoid main() {}\n"),
+		], "core:shaders/main.vert", &[]));
+		
+		assert_eq!(source.source, "#version 330 core\n#line 2 0\nvoid main() {}\n");
+		assert_eq!(source.files, vec![ResourceLocation::parse("core:shaders/main.vert").unwrap()]);
+	}
+	
+	#[test]
+	fn defines_follow_version() {
+		let source = unwrap(preprocess(&[
+			("core:shaders/main.vert", "#version 330 core\nvoid main() {}\n"),
+		], "core:shaders/main.vert", &[("FOG", "1"), ("LAYERS", "256")]));
+		
+		assert_eq!(source.source, concat!(
+			"#version 330 core\n",
+			"#define FOG 1\n",
+			"#define LAYERS 256\n",
+			"#line 2 0\n",
+			"void main() {}\n",
+		));
+	}
+	
+	#[test]
+	fn defines_without_version_come_first() {
+		let source = unwrap(preprocess(&[
+			("core:shaders/main.vert", "void main() {}\n"),
+		], "core:shaders/main.vert", &[("FOG", "1")]));
+		
+		assert_eq!(source.source, "#define FOG 1\n#line 1 0\nvoid main() {}\n");
+	}
+	
+	#[test]
+	fn nested_includes_restore_line_numbers() {
+		let source = unwrap(preprocess(&[
+			("core:shaders/main.vert", "#version 330 core\n#include \"core:shaders/lib/a.glsl\"\nvoid main() {}\n"),
+			("core:shaders/lib/a.glsl", "// a\n#include \"core:shaders/lib/b.glsl\"\nfloat a() { return b(); }\n"),
+			("core:shaders/lib/b.glsl", "float b() { return 1.0; }\n"),
+		], "core:shaders/main.vert", &[]));
+		
+		assert_eq!(source.source, concat!(
+			"#version 330 core\n",
+			"#line 2 0\n",
+			"#line 1 1\n",
+			"// a\n",
+			"#line 1 2\n",
+			"float b() { return 1.0; }\n",
+			"#line 3 1\n",
+			"float a() { return b(); }\n",
+			"#line 3 0\n",
+			"void main() {}\n",
+		));
+		
+		assert_eq!(source.files, vec![
+			ResourceLocation::parse("core:shaders/main.vert").unwrap(),
+			ResourceLocation::parse("core:shaders/lib/a.glsl").unwrap(),
+			ResourceLocation::parse("core:shaders/lib/b.glsl").unwrap(),
+		]);
+	}
+	
+	#[test]
+	fn files_are_included_once() {
+		let source = unwrap(preprocess(&[
+			("core:shaders/main.vert", concat!(
+				"#include \"core:shaders/lib/a.glsl\"\n",
+				"#include \"core:shaders/lib/b.glsl\"\n",
+				"#include \"core:shaders/lib/a.glsl\"\n",
+				"void main() {}\n",
+			)),
+			("core:shaders/lib/a.glsl", "#include \"core:shaders/lib/b.glsl\"\n#include \"core:shaders/lib/a.glsl\"\nfloat a;\n"),
+			("core:shaders/lib/b.glsl", "float b;\n"),
+		], "core:shaders/main.vert", &[]));
+		
+		// Repeated includes, including a file including itself, are replaced by empty lines.
+		assert_eq!(source.source, concat!(
+			"#line 1 0\n",
+			"#line 1 1\n",
+			"#line 1 2\n",
+			"float b;\n",
+			"#line 2 1\n",
+			"\n",
+			"float a;\n",
+			"#line 2 0\n",
+			"\n",
+			"\n",
+			"void main() {}\n",
+		));
+		
+		assert_eq!(source.files.len(), 3);
+	}
+	
+	#[test]
+	fn version_in_include_is_rejected() {
+		let result = preprocess(&[
+			("core:shaders/main.vert", "#version 330 core\n#include \"core:shaders/lib/a.glsl\"\n"),
+			("core:shaders/lib/a.glsl", "// a\n#version 330 core\n"),
+		], "core:shaders/main.vert", &[]);
+		
+		match result {
+			Err(ShaderPreprocessError::Syntax(file, line, _)) => {
+				assert_eq!(file, "core:shaders/lib/a.glsl");
+				assert_eq!(line, 2);
+			},
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+	}
+	
+	#[test]
+	fn malformed_include_is_rejected() {
+		for include in ["#include core:shaders/lib/a.glsl", "#include \"\"", "#include \"core:shaders/lib/a.glsl"].iter() {
+			let result = preprocess(&[
+				("core:shaders/main.vert", include),
+			], "core:shaders/main.vert", &[]);
+			
+			match result {
+				Err(ShaderPreprocessError::Syntax(file, line, _)) => {
+					assert_eq!(file, "core:shaders/main.vert");
+					assert_eq!(line, 1);
+				},
+				_ => panic!("Expected a syntax error for '{}'", include),
+			}
+		}
+	}
+	
+	#[test]
+	fn missing_include_is_reported() {
+		let result = preprocess(&[
+			("core:shaders/main.vert", "#include \"core:shaders/lib/missing.glsl\"\n"),
+		], "core:shaders/main.vert", &[]);
+		
+		match result {
+			Err(ShaderPreprocessError::Resource(file, ResourceError::NotFound)) => assert_eq!(file, "core:shaders/lib/missing.glsl"),
+			_ => panic!("Expected the include to be missing"),
+		}
+	}
+	
+	#[test]
+	fn map_log_replaces_source_numbers() {
+		let source = ShaderSource {
+			source: String::new(),
+			files: vec![
+				ResourceLocation::parse("core:shaders/main.vert").unwrap(),
+				ResourceLocation::parse("core:shaders/lib/a.glsl").unwrap(),
+			],
+		};
+		
+		let log = concat!(
+			"0:12(5): error: `foo' undeclared\n",
+			"ERROR: 1(3) : error C1008: undefined variable \"bar\"\n",
+			"7:4(1): error: unknown source string\n",
+			"warning: no reference"
+		);
+		
+		assert_eq!(source.map_log(log), concat!(
+			"core:shaders/main.vert:12(5): error: `foo' undeclared\n",
+			"ERROR: core:shaders/lib/a.glsl:3 : error C1008: undefined variable \"bar\"\n",
+			"7:4(1): error: unknown source string\n",
+			"warning: no reference"
+		));
+	}
+}
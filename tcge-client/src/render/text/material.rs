@@ -1,15 +1,13 @@
 use rustc_hash::FxHashMap;
-use common::resources::{Resources, ResourceProvider, ResourceLocation};
-use super::super::{ShaderObject, ProgramObject, UniformLocation, TextureObject};
+use common::resources::{Resources, ResourceLocation};
+use common::assets::Handle;
+use super::super::{Material, MaterialError, UniformLocation, TextureObject};
 use super::TextRendererError;
 
 pub struct TextRendererMaterial {
-	pub program: ProgramObject,
-	pub pages: FxHashMap<usize, TextureObject>,
+	pub shader: Material,
+	pub pages: FxHashMap<usize, Handle<TextureObject>>,
 	pub uniform_matrix: UniformLocation,
-	pub uniform_sdfmap: UniformLocation,
-	pub uniform_color:  UniformLocation,
-	pub uniform_spread: UniformLocation,
 	pub uniform_scale:  UniformLocation,
 }
 
@@ -18,57 +16,29 @@ impl TextRendererMaterial {
 		
 		gl.push_debug("Preparing text renderer shaders");
 		
-		let location = ResourceLocation::from_str("core/shaders/text");
+		let location = ResourceLocation::parse("core:materials/text.toml")
+			.map_err(|e| TextRendererError::Parse(e.to_string()))?;
 		
-		debug!("Loading font shader: {}", location);
+		debug!("Loading font material: {}", location);
+		let shader = Material::load(gl, res, &location)
+			.map_err(|e| TextRendererError::Material(e))?;
 		
-		trace!("Loading vertex shader...");
-		let shader_vert = res.res_as_cstring(&location.add(".vert"))
-			.map_err(|e| TextRendererError::Resource(e))?;
-		
-		trace!("Compiling vertex shader...");
-		let shader_vert = ShaderObject::new_vertex_shader(gl, &shader_vert)
-			.map_err(|e| TextRendererError::Shader(e))?;
-		
-		trace!("Loading fragment shader...");
-		let shader_frag = res.res_as_cstring(&location.add(".frag"))
-			.map_err(|e| TextRendererError::Resource(e))?;
-		
-		trace!("Compiling fragment shader...");
-		let shader_frag = ShaderObject::new_fragment_shader(gl, &shader_frag)
-			.map_err(|e| TextRendererError::Shader(e))?;
-		
-		trace!("Linking program...");
-		let program = ProgramObject::new(gl, "Text Renderer", &smallvec![shader_vert, shader_frag])
-			.map_err(|e| TextRendererError::Shader(e))?;
-		
-		trace!("Fetching uniforms...");
-		let uniform_matrix = program.get_uniform_location("transform")
-			.map_err(|_| TextRendererError::Shader("Missing uniform 'transform'.".to_string()))?;
-		
-		let uniform_sdfmap = program.get_uniform_location("sdfmap")
-			.map_err(|_| TextRendererError::Shader("Missing uniform 'sdfmap'.".to_string()))?;
-		
-		let uniform_color = program.get_uniform_location("color")
-			.map_err(|_| TextRendererError::Shader("Missing uniform 'color'.".to_string()))?;
-		
-		let uniform_spread = program.get_uniform_location("spread")
-			.map_err(|_| TextRendererError::Shader("Missing uniform 'spread'.".to_string()))?;
-		
-		let uniform_scale = program.get_uniform_location("scale")
-			.map_err(|_| TextRendererError::Shader("Missing uniform 'scale'.".to_string()))?;
-		
-		trace!("Returning material.");
 		gl.pop_debug();
 		
 		Ok(Self {
-			program,
+			uniform_matrix: shader.uniform("transform"),
+			uniform_scale: shader.uniform("scale"),
+			shader,
 			pages: FxHashMap::default(),
-			uniform_matrix,
-			uniform_sdfmap,
-			uniform_color,
-			uniform_spread,
-			uniform_scale,
 		})
 	}
+	
+	/// Rebuilds the shader, keeping the font pages.
+	pub fn reload(&mut self, gl: &gl::Gl, res: &Resources) -> Result<(), MaterialError> {
+		let shader = Material::load(gl, res, &ResourceLocation::parse("core:materials/text.toml")?)?;
+		self.uniform_matrix = shader.uniform("transform");
+		self.uniform_scale = shader.uniform("scale");
+		self.shader = shader;
+		Ok(())
+	}
 }
@@ -1,10 +1,12 @@
 use rustc_hash::FxHashMap;
 use common::resources::{Resources, ResourceLocation, ResourceError};
-use super::{TextureError, VertexArray};
+use common::assets::{AssetManager, AssetError};
+use super::{VertexArray, MaterialError};
 
 pub type TextRendererComp = crate::WrapperComponent<TextRenderer>;
 
-pub const TEXT_RENDERER_FILES: [(&str, &[u8]); 4] = [
+pub const TEXT_RENDERER_FILES: [(&str, &[u8]); 5] = [
+	("core/materials/text.toml", include_bytes!("sdf-text.toml")),
 	("core/shaders/text.vert", include_bytes!("sdf-text.vert")),
 	("core/shaders/text.frag", include_bytes!("sdf-text.frag")),
 	("core/fonts/hack/hack.fnt", include_bytes!("hack/hack.fnt")),
@@ -20,12 +22,14 @@ use parser::*;
 mod renderer;
 use renderer::TextRenderer;
 
-pub fn new(gl: &gl::Gl, res: &Resources, name: &str) -> Result<TextRenderer, TextRendererError>{
+pub fn new(gl: &gl::Gl, res: &Resources, assets: &mut AssetManager, name: &str) -> Result<TextRenderer, TextRendererError>{
 	
 	gl.push_debug(&format!("Loading font: {}", name));
 	
-	let font_loc = ResourceLocation::from(format!("core/fonts/{}", name));
-	let index_loc = font_loc.add(&format!("/{}.fnt", name));
+	let font_loc = ResourceLocation::parse(&format!("core:fonts/{}", name))
+		.map_err(|e| TextRendererError::Parse(e.to_string()))?;
+	let index_loc = font_loc.add(&format!("/{}.fnt", name))
+		.map_err(|e| TextRendererError::Parse(e.to_string()))?;
 	
 	info!("Loading font: {} -> {}", font_loc, index_loc);
 	
@@ -53,10 +57,10 @@ pub fn new(gl: &gl::Gl, res: &Resources, name: &str) -> Result<TextRenderer, Tex
 	
 	parse_file(
 		res,
+		assets,
 		&font_loc,
 		&index_loc,
-		&mut text,
-		name
+		&mut text
 	)?;
 	
 	while let Some(error) = gl.get_error() {
@@ -185,7 +189,7 @@ impl TextGlyph {
 
 pub enum TextRendererError {
 	Resource(ResourceError),
-	Texture(TextureError),
-	Shader(String),
+	Asset(AssetError),
+	Material(MaterialError),
 	Parse(String),
 }
\ No newline at end of file
@@ -1,14 +1,15 @@
 use std::io::{BufRead, BufReader};
 use common::resources::{ResourceLocation, Resources, ResourceProvider};
+use common::assets::AssetManager;
 use super::{TextRenderer, TextRendererError, TextGlyph};
 use crate::render;
 
 pub fn parse_file(
 	res: &Resources,
+	assets: &mut AssetManager,
 	font_loc: &ResourceLocation,
 	index_loc: &ResourceLocation,
 	text: &mut TextRenderer,
-	name: &str,
 ) -> Result<(), TextRendererError> {
 	
 	let font_file = res.res_as_stream(&index_loc)
@@ -46,14 +47,13 @@ pub fn parse_file(
 				Ok(())
 			})?;
 			
-			let page_loc = ResourceLocation::from_string(font_loc.inner.clone() + "/" + &page_name);
+			let page_loc = font_loc.add(&format!("/{}", page_name))
+				.map_err(|e| TextRendererError::Parse(e.to_string()))?;
 			
 			&text.gl.push_debug(&format!("Loading font-page {}", page_name));
 			debug!("Loading font page: {}", page_name);
-			let page_tex = render::TextureObjectBuilder::new()
-				.name(format!("Font Page {}/{}", name, page_name))
-				.build_from_res(&text.gl, &res, &page_loc)
-				.map_err(|e| TextRendererError::Texture(e))?;
+			let page_tex = assets.load::<render::TextureObject>(res, &page_loc)
+				.map_err(|e| TextRendererError::Asset(e))?;
 			
 			while let Some(error) = &text.gl.get_error() {
 				error!("OpenGL error while loading font-page {}: {}", page_name, error);
@@ -136,7 +136,7 @@ pub fn parse_file(
 				Ok(())
 			})?;
 			
-			char.uv = text.material.pages[&char.page].get_uv_rect(
+			char.uv = text.material.pages[&char.page].get().get_uv_rect(
 				char.x, char.y,
 				char.width, char.height
 			);
@@ -18,7 +18,7 @@ impl TextRenderer {
 		self.gl.push_debug("Draw Text");
 		self.draw_reset(font_size);
 		
-		self.material.pages[&0].set_used();
+		self.material.pages[&0].get().set_used();
 		
 		let line_start = x;
 		let mut x_pos = x;
@@ -35,14 +35,14 @@ impl TextRenderer {
 			
 			let character = match self.characters.get(&(char as u32)) {
 				Some(x) => x.clone(),
-				None => return
+				None => break
 			};
 			
 			if character.page != page {
 				self.draw_submit(count);
 				count = 0; // reset counter
 				page = character.page;
-				self.material.pages[&page].set_used();
+				self.material.pages[&page].get().set_used();
 			}
 			
 			if self.draw_char(
@@ -56,6 +56,7 @@ impl TextRenderer {
 		}
 		
 		self.draw_submit(count);
+		self.material.shader.unbind();
 		self.gl.pop_debug();
 	}
 	
@@ -85,16 +86,11 @@ impl TextRenderer {
 	}
 	
 	fn draw_reset(&mut self, font_size: f32) {
-		let color: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
-		let spread = 8.0;
 		let scale = font_size / self.metrics.scale;
 		
-		self.material.program.set_used();
-		self.material.program.set_uniform_vector4_raw(self.material.uniform_color, &color);
-		self.material.program.set_uniform_matrix4(self.material.uniform_matrix, &self.transform);
-		self.material.program.set_uniform_scalar(self.material.uniform_spread, spread);
-		self.material.program.set_uniform_scalar(self.material.uniform_scale, scale);
-		self.material.program.set_uniform_sampler(self.material.uniform_sdfmap, 0);
+		self.material.shader.bind();
+		self.material.shader.program.set_uniform_matrix4(self.material.uniform_matrix, &self.transform);
+		self.material.shader.program.set_uniform_scalar(self.material.uniform_scale, scale);
 		self.buffer.clear();
 	}
 	
@@ -113,5 +109,5 @@ impl TextRenderer {
 		// Clear buffer to make space for the next span...
 		self.buffer.clear();
 	}
-	
+
 }
@@ -1,6 +1,7 @@
 use image;
 use image::GenericImageView;
 use common::resources;
+use common::assets::{AssetLoader, AssetError, LoadContext};
 
 pub struct TextureObjectBuilder {
 	name: Option<String>,
@@ -42,6 +43,12 @@ impl TextureObjectBuilder {
 		self
 	}
 	
+	#[allow(dead_code)]
+	pub fn target(mut self, target: gl::types::GLenum) -> Self {
+		self.target = target;
+		self
+	}
+	
 	#[allow(dead_code)]
 	pub fn wrapping(mut self, wrapping: gl::types::GLenum) -> Self {
 		self.wrapping = wrapping;
@@ -108,40 +115,27 @@ impl TextureObjectBuilder {
 		// TODO: Maybe automate this one day?
 		let image = image.to_rgba();
 		
+		Ok(self.build_from_rgba(gl, width, height, &image))
+	}
+	
+	/// Builds a texture from raw, tightly packed RGBA pixels, with rows ordered top to bottom.
+	#[allow(dead_code)]
+	pub fn build_from_rgba(
+		self,
+		gl: &super::Gl,
+		width: u32,
+		height: u32,
+		pixels: &[u8]
+	) -> TextureObject {
+		assert_eq!(pixels.len(), (width * height * 4) as usize, "RGBA buffer does not match the texture size.");
+		
 		let target = self.target;
 		
-		let mut id: gl::types::GLuint = 0;
+		let id: gl::types::GLuint;
 		unsafe {
 			gl.push_debug(&format!("Uploading texture: {}x{}", width, height));
 			
-			// create & bind
-			gl.GenTextures(1, &mut id);
-			gl.BindTexture(target, id);
-			
-			if let Some(name) = self.name {
-				gl.label_object(
-					gl::TEXTURE,
-					id,
-					&format!("Texture: {}", name)
-				);
-			}
-			
-			// wrapping
-			gl.TexParameteri(target, gl::TEXTURE_WRAP_S, self.wrapping as gl::types::GLint);
-			gl.TexParameteri(target, gl::TEXTURE_WRAP_T, self.wrapping as gl::types::GLint);
-			
-			// sampling
-			gl.TexParameteri(target, gl::TEXTURE_MIN_FILTER, self.filter_min as gl::types::GLint);
-			gl.TexParameteri(target, gl::TEXTURE_MAG_FILTER, self.filter_mag as gl::types::GLint);
-			
-			if self.anisotropy {
-				// Attempt to enable anisotropic filtering...
-				let mut aniso: f32 = 0.0;
-				gl.GetFloatv(0x84FF, &mut aniso);
-				if aniso != 0.0 {
-					gl.TexParameterf(gl::TEXTURE_2D, 0x84FE, aniso);
-				}
-			}
+			id = self.create(gl);
 			
 			gl.TexImage2D(
 				target,
@@ -152,7 +146,7 @@ impl TextureObjectBuilder {
 				0,
 				gl::RGBA,
 				gl::UNSIGNED_BYTE,
-				image.as_ptr() as *const std::ffi::c_void
+				pixels.as_ptr() as *const std::ffi::c_void
 			);
 			
 			while let Some(error) = gl.get_error() {
@@ -172,16 +166,132 @@ impl TextureObjectBuilder {
 		let tx = 1.0 / width as f32;
 		let ty = 1.0 / height as f32;
 		
-		Ok(TextureObject {
+		TextureObject {
 			gl: gl.clone(),
 			id,
 			target,
 			width,
 			height,
+			layers: 1,
 			tx,
 			ty,
-		})
+		}
+	}
+	
+	/// Builds an array texture (`GL_TEXTURE_2D_ARRAY`) from raw RGBA layers of the same size, uploading one layer at a time.
+	///
+	/// The target of the builder is ignored. Mipmaps are generated for every layer separately, so layers never bleed into each other.
+	#[allow(dead_code)]
+	pub fn build_array_from_rgba(
+		mut self,
+		gl: &super::Gl,
+		width: u32,
+		height: u32,
+		layers: &[Vec<u8>]
+	) -> TextureObject {
+		for pixels in layers.iter() {
+			assert_eq!(pixels.len(), (width * height * 4) as usize, "RGBA buffer does not match the texture size.");
+		}
+		
+		self.target = gl::TEXTURE_2D_ARRAY;
+		let target = self.target;
+		
+		let id: gl::types::GLuint;
+		unsafe {
+			gl.push_debug(&format!("Uploading array texture: {}x{}x{}", width, height, layers.len()));
+			
+			id = self.create(gl);
+			
+			// allocate all layers...
+			gl.TexImage3D(
+				target,
+				0,
+				self.internal_format as gl::types::GLint,
+				width as gl::types::GLsizei,
+				height as gl::types::GLsizei,
+				layers.len() as gl::types::GLsizei,
+				0,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				std::ptr::null()
+			);
+			
+			// ...then fill them one by one
+			for (layer, pixels) in layers.iter().enumerate() {
+				gl.TexSubImage3D(
+					target,
+					0,
+					0, 0, layer as gl::types::GLint,
+					width as gl::types::GLsizei,
+					height as gl::types::GLsizei,
+					1,
+					gl::RGBA,
+					gl::UNSIGNED_BYTE,
+					pixels.as_ptr() as *const std::ffi::c_void
+				);
+			}
+			
+			while let Some(error) = gl.get_error() {
+				error!("OpenGL error while uploading array texture: {}", error);
+			}
+			
+			if self.mipmaps {
+				// Gen mipmaps
+				gl.GenerateMipmap(target);
+			}
+			
+			// unbind
+			gl.BindTexture(target, 0);
+			gl.pop_debug();
+		}
 		
+		TextureObject {
+			gl: gl.clone(),
+			id,
+			target,
+			width,
+			height,
+			layers: layers.len() as u32,
+			tx: 1.0 / width as f32,
+			ty: 1.0 / height as f32,
+		}
+	}
+	
+	/// Creates and binds a new texture, applying the labels and parameters of this builder.
+	unsafe fn create(&self, gl: &super::Gl) -> gl::types::GLuint {
+		let target = self.target;
+		let mut id: gl::types::GLuint = 0;
+		
+		// create & bind
+		gl.GenTextures(1, &mut id);
+		gl.BindTexture(target, id);
+		
+		if let Some(name) = &self.name {
+			gl.label_object(
+				gl::TEXTURE,
+				id,
+				&format!("Texture: {}", name)
+			);
+		}
+		
+		// wrapping
+		gl.TexParameteri(target, gl::TEXTURE_WRAP_S, self.wrapping as gl::types::GLint);
+		gl.TexParameteri(target, gl::TEXTURE_WRAP_T, self.wrapping as gl::types::GLint);
+		
+		// sampling
+		gl.TexParameteri(target, gl::TEXTURE_MIN_FILTER, self.filter_min as gl::types::GLint);
+		gl.TexParameteri(target, gl::TEXTURE_MAG_FILTER, self.filter_mag as gl::types::GLint);
+		
+		if self.anisotropy {
+			// Attempt to enable anisotropic filtering...
+			let mut aniso: f32 = 0.0;
+			gl.GetFloatv(0x84FF, &mut aniso);
+			if aniso != 0.0 {
+				gl.TexParameterf(target, 0x84FE, aniso);
+			}
+		}
+		
+		id
 	}
 }
 
@@ -192,6 +302,7 @@ pub struct TextureObject {
 	
 	width: u32,
 	height: u32,
+	layers: u32,
 	tx: f32,
 	ty: f32,
 }
@@ -219,6 +330,45 @@ impl TextureObject {
 		self.height
 	}
 	
+	/// The number of layers of an array texture; 1 for all other textures.
+	#[allow(dead_code)]
+	pub fn layers(&self) -> u32 {
+		self.layers
+	}
+	
+	/// Replaces the whole image of one layer of an array texture; mipmaps are not updated.
+	#[allow(dead_code)]
+	pub fn upload_layer(&self, layer: u32, pixels: &[u8]) {
+		assert!(layer < self.layers, "Layer {} is out of bounds.", layer);
+		assert_eq!(pixels.len(), (self.width * self.height * 4) as usize, "RGBA buffer does not match the texture size.");
+		
+		unsafe {
+			self.gl.BindTexture(self.target, self.id);
+			self.gl.TexSubImage3D(
+				self.target,
+				0,
+				0, 0, layer as gl::types::GLint,
+				self.width as gl::types::GLsizei,
+				self.height as gl::types::GLsizei,
+				1,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				pixels.as_ptr() as *const std::ffi::c_void
+			);
+			self.gl.BindTexture(self.target, 0);
+		}
+	}
+	
+	/// Regenerates all mipmaps from the base level.
+	#[allow(dead_code)]
+	pub fn generate_mipmaps(&self) {
+		unsafe {
+			self.gl.BindTexture(self.target, self.id);
+			self.gl.GenerateMipmap(self.target);
+			self.gl.BindTexture(self.target, 0);
+		}
+	}
+	
 	#[allow(dead_code)]
 	pub fn get_uv_rect(&self, x: u32, y:u32, w: u32, h: u32) -> [f32;4] {
 		return [
@@ -234,3 +384,32 @@ pub enum TextureError {
 	Image(image::ImageError),
 	Resource(resources::ResourceError),
 }
+
+/// Loads image resources as textures, with the default settings of `TextureObjectBuilder`.
+pub struct TextureLoader {
+	gl: gl::Gl,
+}
+
+impl TextureLoader {
+	pub fn new(gl: &gl::Gl) -> Self {
+		Self {
+			gl: gl.clone()
+		}
+	}
+}
+
+impl AssetLoader for TextureLoader {
+	type Asset = TextureObject;
+	
+	fn load(&self, context: &mut LoadContext, location: &resources::ResourceLocation) -> Result<TextureObject, AssetError> {
+		let buffer = context.read_buffer(location)?;
+		
+		TextureObjectBuilder::new()
+			.name(location.to_string())
+			.build_from_buffer(&self.gl, &buffer)
+			.map_err(|err| match err {
+				TextureError::Image(err) => AssetError::Load(location.to_string(), err.to_string()),
+				TextureError::Resource(err) => AssetError::Resource(location.to_string(), err),
+			})
+	}
+}
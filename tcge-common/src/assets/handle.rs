@@ -0,0 +1,60 @@
+use super::ResourceLocation;
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+
+/// A shared reference to a loaded asset.
+///
+/// All handles to an asset share it; when the asset is reloaded, every handle sees the new version.
+/// Because of that, the asset can only be borrowed for a moment (`get`): A borrow that is
+/// still alive while the asset is reloaded makes the reload fail.
+pub struct Handle<T> {
+	location: ResourceLocation,
+	cell: Rc<RefCell<T>>,
+}
+
+impl<T> Handle<T> {
+	pub(super) fn new(location: ResourceLocation, cell: Rc<RefCell<T>>) -> Self {
+		Self {
+			location,
+			cell
+		}
+	}
+	
+	/// Borrows the asset.
+	///
+	/// # Panics
+	/// If the asset is being reloaded, i.e. when called by the loader of the asset itself.
+	pub fn get(&self) -> Ref<'_, T> {
+		self.cell.borrow()
+	}
+	
+	/// The location the asset was loaded from.
+	pub fn location(&self) -> &ResourceLocation {
+		&self.location
+	}
+	
+	/// The number of handles to the asset, not counting the one of the asset manager.
+	pub fn ref_count(&self) -> usize {
+		Rc::strong_count(&self.cell) - 1
+	}
+	
+	/// Returns whether both handles refer to the same asset.
+	pub fn ptr_eq(&self, other: &Self) -> bool {
+		Rc::ptr_eq(&self.cell, &other.cell)
+	}
+}
+
+impl<T> Clone for Handle<T> {
+	fn clone(&self) -> Self {
+		Self {
+			location: self.location.clone(),
+			cell: self.cell.clone(),
+		}
+	}
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.location)
+	}
+}
@@ -0,0 +1,92 @@
+use super::{AssetManager, AssetKey, AssetError, Dependency, Handle};
+use super::{ResourceProvider, ResourceLocation};
+use std::any::Any;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Turns resources into assets of one type.
+///
+/// Loaders only read through their `LoadContext`, so the manager knows what every asset depends on.
+pub trait AssetLoader {
+	type Asset: 'static;
+	
+	fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<Self::Asset, AssetError>;
+}
+
+/// What a loader can do while loading an asset: Read resources and load other assets.
+///
+/// Everything read or loaded through the context becomes a dependency of the asset.
+pub struct LoadContext<'a> {
+	pub(super) manager: &'a mut AssetManager,
+	pub(super) res: &'a dyn ResourceProvider,
+	pub(super) dependencies: Vec<Dependency>,
+}
+
+impl<'a> LoadContext<'a> {
+	pub fn read_buffer(&mut self, location: &ResourceLocation) -> Result<Vec<u8>, AssetError> {
+		self.add_dependency(location);
+		self.res.res_as_buffer(location)
+			.map_err(|err| AssetError::Resource(location.to_string(), err))
+	}
+	
+	pub fn read_string(&mut self, location: &ResourceLocation) -> Result<String, AssetError> {
+		self.add_dependency(location);
+		self.res.res_as_string(location)
+			.map_err(|err| AssetError::Resource(location.to_string(), err))
+	}
+	
+	/// Loads (or gets from the cache) another asset, that this one depends on.
+	pub fn load<T: 'static>(&mut self, location: &ResourceLocation) -> Result<Handle<T>, AssetError> {
+		let handle = self.manager.load::<T>(self.res, location)?;
+		
+		let key = AssetKey::of::<T>(location);
+		if !self.dependencies.contains(&Dependency::Asset(key.clone())) {
+			self.dependencies.push(Dependency::Asset(key));
+		}
+		
+		Ok(handle)
+	}
+	
+	/// The resources, for loaders that read through existing functions.
+	///
+	/// Anything read like this must be registered with `add_dependency`.
+	pub fn resources(&self) -> &dyn ResourceProvider {
+		self.res
+	}
+	
+	pub fn add_dependency(&mut self, location: &ResourceLocation) {
+		let dependency = Dependency::Resource(location.clone());
+		if !self.dependencies.contains(&dependency) {
+			self.dependencies.push(dependency);
+		}
+	}
+}
+
+/// An `AssetLoader` with its asset type erased, so loaders of all types can be stored together.
+pub(super) trait ErasedLoader {
+	/// Loads the asset, returning it as a `RefCell<L::Asset>`.
+	fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<Rc<dyn Any>, AssetError>;
+	
+	/// Loads the asset again, replacing the one in the given `RefCell<L::Asset>`.
+	fn reload(&self, context: &mut LoadContext, location: &ResourceLocation, cell: &dyn Any) -> Result<(), AssetError>;
+}
+
+impl<L: AssetLoader> ErasedLoader for L {
+	fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<Rc<dyn Any>, AssetError> {
+		let asset = AssetLoader::load(self, context, location)?;
+		Ok(Rc::new(RefCell::new(asset)))
+	}
+	
+	fn reload(&self, context: &mut LoadContext, location: &ResourceLocation, cell: &dyn Any) -> Result<(), AssetError> {
+		let asset = AssetLoader::load(self, context, location)?;
+		
+		let cell = cell.downcast_ref::<RefCell<L::Asset>>()
+			.expect("Asset type must match its loader");
+		
+		let mut borrow = cell.try_borrow_mut()
+			.map_err(|_| AssetError::InUse(location.to_string()))?;
+		
+		*borrow = asset;
+		Ok(())
+	}
+}
@@ -0,0 +1,543 @@
+//! A typed, cached layer on top of the resources.
+//!
+//! Resources are files; assets are what the program makes of them (textures, configurations, fonts...).
+//! The `AssetManager` loads every asset through the `AssetLoader` registered for its type,
+//! and loads each asset (type and location) only once: Every further `load` returns a handle to the same asset.
+//!
+//! While loading, the manager records what the asset was made from, i.e. the resources it read
+//! and the other assets it loaded. When resources change, `reload` loads everything that depends
+//! on them again, directly or through other assets, with the dependencies before their dependents.
+//!
+//! Nothing here knows about rendering; loaders that need more than resources (like an OpenGL context)
+//! keep it themselves.
+
+use crate::resources::{ResourceProvider, ResourceLocation, ResourceError};
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+pub mod handle;
+pub use handle::Handle;
+
+pub mod loader;
+pub use loader::{AssetLoader, LoadContext};
+use loader::ErasedLoader;
+
+/// Identifies an asset: Its type and the location it was loaded from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AssetKey {
+	type_id: TypeId,
+	type_name: &'static str,
+	location: ResourceLocation,
+}
+
+impl AssetKey {
+	pub fn of<T: 'static>(location: &ResourceLocation) -> Self {
+		Self {
+			type_id: TypeId::of::<T>(),
+			type_name: std::any::type_name::<T>(),
+			location: location.clone(),
+		}
+	}
+	
+	pub fn location(&self) -> &ResourceLocation {
+		&self.location
+	}
+}
+
+impl std::fmt::Display for AssetKey {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		write!(f, "{} ({})", self.location, self.type_name)
+	}
+}
+
+/// Something an asset was made from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Dependency {
+	Resource(ResourceLocation),
+	Asset(AssetKey),
+}
+
+pub enum AssetError {
+	/// No loader is registered for the type of asset.
+	NoLoader(&'static str),
+	
+	/// A resource could not be read; with its location.
+	Resource(String, ResourceError),
+	
+	/// The asset (directly or through others) depends on itself.
+	Cycle(String),
+	
+	/// The asset was borrowed while it was being reloaded.
+	InUse(String),
+	
+	/// The loader failed; with the location and the reason.
+	Load(String, String),
+}
+
+impl std::fmt::Display for AssetError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		match self {
+			AssetError::NoLoader(type_name) => write!(f, "No loader is registered for assets of type {}", type_name),
+			AssetError::Resource(location, ResourceError::NotFound) => write!(f, "Could not find resource '{}'", location),
+			AssetError::Resource(location, ResourceError::Io(err)) => write!(f, "Could not read resource '{}': {}", location, err),
+			AssetError::Resource(location, _) => write!(f, "Could not read resource '{}'", location),
+			AssetError::Cycle(asset) => write!(f, "Asset {} depends on itself", asset),
+			AssetError::InUse(location) => write!(f, "Asset '{}' is in use and cannot be replaced", location),
+			AssetError::Load(location, reason) => write!(f, "Failed to load asset '{}': {}", location, reason),
+		}
+	}
+}
+
+struct AssetEntry {
+	/// The asset, as a `RefCell<T>`; the manager holds one reference.
+	cell: Rc<dyn Any>,
+	
+	dependencies: Vec<Dependency>,
+	
+	/// When the asset finished loading; every asset finishes after the assets it depends on.
+	sequence: u64,
+}
+
+pub struct AssetManager {
+	loaders: HashMap<TypeId, Rc<dyn ErasedLoader>>,
+	entries: HashMap<AssetKey, AssetEntry>,
+	
+	/// The assets that are currently being loaded, to detect cycles.
+	loading: Vec<AssetKey>,
+	
+	sequence: u64,
+}
+
+impl AssetManager {
+	pub fn new() -> Self {
+		Self {
+			loaders: HashMap::new(),
+			entries: HashMap::new(),
+			loading: vec![],
+			sequence: 0,
+		}
+	}
+	
+	/// Registers the loader for the asset type of the given loader, replacing the previous one.
+	pub fn register_loader<L: 'static + AssetLoader>(&mut self, loader: L) {
+		self.loaders.insert(TypeId::of::<L::Asset>(), Rc::new(loader));
+	}
+	
+	/// Returns the asset of type `T` at the given location, loading it if it isn't loaded yet.
+	pub fn load<T: 'static>(&mut self, res: &dyn ResourceProvider, location: &ResourceLocation) -> Result<Handle<T>, AssetError> {
+		let key = AssetKey::of::<T>(location);
+		
+		if let Some(handle) = self.get::<T>(location) {
+			return Ok(handle);
+		}
+		
+		let loader = self.loaders.get(&key.type_id)
+			.cloned()
+			.ok_or(AssetError::NoLoader(key.type_name))?;
+		
+		if self.loading.contains(&key) {
+			return Err(AssetError::Cycle(key.to_string()));
+		}
+		
+		debug!("Loading asset: {}", key);
+		self.loading.push(key.clone());
+		let mut context = LoadContext {
+			manager: self,
+			res,
+			dependencies: vec![],
+		};
+		
+		let result = loader.load(&mut context, location);
+		let dependencies = context.dependencies;
+		self.loading.pop();
+		
+		let cell = result?;
+		self.sequence += 1;
+		self.entries.insert(key, AssetEntry {
+			cell: cell.clone(),
+			dependencies,
+			sequence: self.sequence,
+		});
+		
+		let cell = cell.downcast().unwrap_or_else(|_| panic!("Asset type must match its loader"));
+		Ok(Handle::new(location.clone(), cell))
+	}
+	
+	/// Returns the asset of type `T` at the given location, if it is loaded.
+	pub fn get<T: 'static>(&self, location: &ResourceLocation) -> Option<Handle<T>> {
+		let entry = self.entries.get(&AssetKey::of::<T>(location))?;
+		let cell = entry.cell.clone().downcast().ok()?;
+		Some(Handle::new(location.clone(), cell))
+	}
+	
+	/// The resources and assets the given asset was made from.
+	pub fn dependencies(&self, key: &AssetKey) -> &[Dependency] {
+		self.entries.get(key)
+			.map(|entry| &entry.dependencies[..])
+			.unwrap_or(&[])
+	}
+	
+	/// The number of loaded assets.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+	
+	/// Loads every asset that depends on one of the `changed` resources again, or all assets if `None`.
+	///
+	/// Assets that fail to reload are kept as they were, with an error logged.
+	/// Returns the assets that were reloaded.
+	pub fn reload(&mut self, res: &dyn ResourceProvider, changed: Option<&[ResourceLocation]>) -> Vec<AssetKey> {
+		let mut dirty: HashSet<AssetKey> = self.entries.iter()
+			.filter(|(_, entry)| match changed {
+				None => true,
+				Some(changed) => entry.dependencies.iter().any(|dependency| match dependency {
+					Dependency::Resource(location) => changed.contains(location),
+					Dependency::Asset(_) => false,
+				}),
+			})
+			.map(|(key, _)| key.clone())
+			.collect();
+		
+		// Cascade to the dependents of the reloaded assets, until nothing changes.
+		loop {
+			let dependents: Vec<AssetKey> = self.entries.iter()
+				.filter(|(key, _)| !dirty.contains(*key))
+				.filter(|(_, entry)| entry.dependencies.iter().any(|dependency| match dependency {
+					Dependency::Asset(key) => dirty.contains(key),
+					Dependency::Resource(_) => false,
+				}))
+				.map(|(key, _)| key.clone())
+				.collect();
+			
+			if dependents.is_empty() {
+				break;
+			}
+			
+			dirty.extend(dependents);
+		}
+		
+		let mut dirty: Vec<(u64, AssetKey)> = dirty.into_iter()
+			.map(|key| (self.entries[&key].sequence, key))
+			.collect();
+		dirty.sort_by_key(|(sequence, _)| *sequence);
+		
+		let mut reloaded = vec![];
+		for (_, key) in dirty {
+			match self.reload_asset(res, &key) {
+				Ok(()) => reloaded.push(key),
+				Err(err) => error!("Failed to reload asset {}: {}", key, err),
+			}
+		}
+		
+		reloaded
+	}
+	
+	fn reload_asset(&mut self, res: &dyn ResourceProvider, key: &AssetKey) -> Result<(), AssetError> {
+		let loader = self.loaders.get(&key.type_id)
+			.cloned()
+			.ok_or(AssetError::NoLoader(key.type_name))?;
+		
+		let cell = match self.entries.get(key) {
+			Some(entry) => entry.cell.clone(),
+			None => return Ok(()),
+		};
+		
+		debug!("Reloading asset: {}", key);
+		self.loading.push(key.clone());
+		let mut context = LoadContext {
+			manager: self,
+			res,
+			dependencies: vec![],
+		};
+		
+		let result = loader.reload(&mut context, &key.location, &*cell);
+		let dependencies = context.dependencies;
+		self.loading.pop();
+		result?;
+		
+		self.sequence += 1;
+		if let Some(entry) = self.entries.get_mut(key) {
+			entry.dependencies = dependencies;
+			entry.sequence = self.sequence;
+		}
+		
+		Ok(())
+	}
+	
+	/// Drops every asset that has no handles left, outside the manager.
+	///
+	/// Returns the number of dropped assets.
+	pub fn collect_unused(&mut self) -> usize {
+		let count = self.entries.len();
+		
+		// Dropping an asset can release the assets it holds handles to.
+		loop {
+			let before = self.entries.len();
+			self.entries.retain(|_, entry| Rc::strong_count(&entry.cell) > 1);
+			
+			if self.entries.len() == before {
+				break;
+			}
+		}
+		
+		count - self.entries.len()
+	}
+}
+
+impl Default for AssetManager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::resources::providers::include::IncludeProvider;
+	use std::cell::RefCell;
+	
+	/// The loads done by the test loaders, in order.
+	type LoadLog = Rc<RefCell<Vec<String>>>;
+	
+	/// The contents of a text file.
+	struct Text(String);
+	
+	struct TextLoader(LoadLog);
+	
+	impl AssetLoader for TextLoader {
+		type Asset = Text;
+		
+		fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<Text, AssetError> {
+			self.0.borrow_mut().push(location.to_string());
+			Ok(Text(context.read_string(location)?))
+		}
+	}
+	
+	/// The concatenation of other assets, listed one per line as `text <location>` or `joined <location>`.
+	struct Joined {
+		value: String,
+		
+		#[allow(dead_code)]
+		texts: Vec<Handle<Text>>,
+		
+		#[allow(dead_code)]
+		joined: Vec<Handle<Joined>>,
+	}
+	
+	struct JoinedLoader(LoadLog);
+	
+	/// Parses a location listed in the file at `list`.
+	fn parse(list: &ResourceLocation, part: &str) -> Result<ResourceLocation, AssetError> {
+		ResourceLocation::parse(part.trim())
+			.map_err(|err| AssetError::Load(list.to_string(), err.to_string()))
+	}
+	
+	impl AssetLoader for JoinedLoader {
+		type Asset = Joined;
+		
+		fn load(&self, context: &mut LoadContext, location: &ResourceLocation) -> Result<Joined, AssetError> {
+			let list = context.read_string(location)?;
+			let mut joined = Joined {
+				value: String::new(),
+				texts: vec![],
+				joined: vec![],
+			};
+			
+			for line in list.lines() {
+				match line.split_at(line.find(' ').unwrap()) {
+					("text", part) => {
+						let text = context.load::<Text>(&parse(location, part)?)?;
+						joined.value += &text.get().0;
+						joined.texts.push(text);
+					},
+					("joined", part) => {
+						let part = context.load::<Joined>(&parse(location, part)?)?;
+						joined.value += &part.get().value;
+						joined.joined.push(part);
+					},
+					_ => return Err(AssetError::Load(location.to_string(), format!("Invalid line: {}", line))),
+				}
+			}
+			
+			self.0.borrow_mut().push(location.to_string());
+			Ok(joined)
+		}
+	}
+	
+	fn resources() -> IncludeProvider {
+		IncludeProvider::new(vec![
+			("core/a.txt", b"A"),
+			("core/b.txt", b"B"),
+			("core/ab.list", b"text core:a.txt\ntext core:b.txt"),
+			("core/top.list", b"joined core:ab.list\ntext core:b.txt"),
+			("core/self.list", b"text core:a.txt\njoined core:self.list"),
+		])
+	}
+	
+	fn manager() -> (AssetManager, LoadLog) {
+		let log = LoadLog::default();
+		
+		let mut manager = AssetManager::new();
+		manager.register_loader(TextLoader(log.clone()));
+		manager.register_loader(JoinedLoader(log.clone()));
+		(manager, log)
+	}
+	
+	fn location(location: &str) -> ResourceLocation {
+		ResourceLocation::parse(location).unwrap()
+	}
+	
+	#[test]
+	fn load_is_cached() {
+		let res = resources();
+		let (mut manager, log) = manager();
+		assert!(manager.is_empty());
+		
+		let first = manager.load::<Text>(&res, &location("core:a.txt")).unwrap_or_else(|err| panic!("{}", err));
+		let second = manager.load::<Text>(&res, &location("core:a.txt")).unwrap_or_else(|err| panic!("{}", err));
+		
+		assert!(first.ptr_eq(&second));
+		assert_eq!(first.get().0, "A");
+		assert_eq!(first.ref_count(), 2);
+		assert_eq!(*log.borrow(), vec!["core:a.txt"]);
+		assert_eq!(manager.len(), 1);
+		
+		assert!(manager.get::<Text>(&location("core:a.txt")).unwrap().ptr_eq(&first));
+		assert!(manager.get::<Text>(&location("core:b.txt")).is_none());
+	}
+	
+	#[test]
+	fn load_without_loader() {
+		let res = resources();
+		let mut manager = AssetManager::default();
+		
+		match manager.load::<Text>(&res, &location("core:a.txt")) {
+			Err(AssetError::NoLoader(type_name)) => assert!(type_name.ends_with("Text")),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+		
+		assert!(manager.is_empty());
+	}
+	
+	#[test]
+	fn load_missing_resource() {
+		let res = resources();
+		let (mut manager, _) = manager();
+		
+		match manager.load::<Text>(&res, &location("core:missing.txt")) {
+			Err(AssetError::Resource(location, ResourceError::NotFound)) => assert_eq!(location, "core:missing.txt"),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+	}
+	
+	#[test]
+	fn load_self_dependent_asset() {
+		let res = resources();
+		let (mut manager, _) = manager();
+		
+		match manager.load::<Joined>(&res, &location("core:self.list")) {
+			Err(AssetError::Cycle(asset)) => assert!(asset.starts_with("core:self.list")),
+			Err(err) => panic!("Unexpected error: {}", err),
+			Ok(_) => panic!("Expected an error"),
+		}
+		
+		// Only the assets loaded before the cycle was found are kept.
+		assert_eq!(manager.len(), 1);
+		assert!(manager.get::<Joined>(&location("core:self.list")).is_none());
+		assert!(manager.get::<Text>(&location("core:a.txt")).is_some());
+	}
+	
+	#[test]
+	fn dependencies_are_recorded() {
+		let res = resources();
+		let (mut manager, _) = manager();
+		
+		let top = manager.load::<Joined>(&res, &location("core:top.list")).unwrap_or_else(|err| panic!("{}", err));
+		assert_eq!(top.get().value, "ABB");
+		assert_eq!(manager.len(), 4);
+		
+		assert_eq!(manager.dependencies(&AssetKey::of::<Joined>(&location("core:top.list"))), &[
+			Dependency::Resource(location("core:top.list")),
+			Dependency::Asset(AssetKey::of::<Joined>(&location("core:ab.list"))),
+			Dependency::Asset(AssetKey::of::<Text>(&location("core:b.txt"))),
+		][..]);
+		
+		assert_eq!(manager.dependencies(&AssetKey::of::<Text>(&location("core:a.txt"))), &[
+			Dependency::Resource(location("core:a.txt")),
+		][..]);
+	}
+	
+	#[test]
+	fn reload_cascades_to_dependents_in_order() {
+		let res = resources();
+		let (mut manager, log) = manager();
+		
+		let _top = manager.load::<Joined>(&res, &location("core:top.list")).unwrap_or_else(|err| panic!("{}", err));
+		log.borrow_mut().clear();
+		
+		// `b.txt` is not affected; `ab.list` must be reloaded before `top.list`, which depends on it.
+		let reloaded = manager.reload(&res, Some(&[location("core:a.txt")]));
+		
+		assert_eq!(reloaded, vec![
+			AssetKey::of::<Text>(&location("core:a.txt")),
+			AssetKey::of::<Joined>(&location("core:ab.list")),
+			AssetKey::of::<Joined>(&location("core:top.list")),
+		]);
+		assert_eq!(*log.borrow(), vec!["core:a.txt", "core:ab.list", "core:top.list"]);
+		
+		// A change that nothing depends on reloads nothing.
+		assert!(manager.reload(&res, Some(&[location("core:other.txt")])).is_empty());
+		
+		// Reloading everything keeps the dependencies before their dependents.
+		log.borrow_mut().clear();
+		assert_eq!(manager.reload(&res, None).len(), 4);
+		
+		let log = log.borrow();
+		let position = |location: &str| log.iter().position(|entry| entry == location).unwrap();
+		assert!(position("core:a.txt") < position("core:ab.list"));
+		assert!(position("core:b.txt") < position("core:ab.list"));
+		assert!(position("core:ab.list") < position("core:top.list"));
+	}
+	
+	#[test]
+	fn reload_keeps_borrowed_asset() {
+		let res = resources();
+		let (mut manager, _) = manager();
+		
+		let a = manager.load::<Text>(&res, &location("core:a.txt")).unwrap_or_else(|err| panic!("{}", err));
+		let borrow = a.get();
+		
+		assert!(manager.reload(&res, None).is_empty());
+		assert_eq!(borrow.0, "A");
+	}
+	
+	#[test]
+	fn collect_unused_drops_released_assets() {
+		let res = resources();
+		let (mut manager, _) = manager();
+		
+		let top = manager.load::<Joined>(&res, &location("core:top.list")).unwrap_or_else(|err| panic!("{}", err));
+		let a = manager.load::<Text>(&res, &location("core:a.txt")).unwrap_or_else(|err| panic!("{}", err));
+		
+		// Everything is still in use, by the handles or by `top.list`.
+		assert_eq!(manager.collect_unused(), 0);
+		assert_eq!(manager.len(), 4);
+		
+		// Dropping `top.list` releases `ab.list`, which releases `b.txt`; `a.txt` still has a handle.
+		drop(top);
+		assert_eq!(manager.collect_unused(), 3);
+		assert_eq!(manager.len(), 1);
+		assert!(manager.get::<Text>(&location("core:a.txt")).unwrap().ptr_eq(&a));
+		
+		drop(a);
+		assert_eq!(manager.collect_unused(), 1);
+		assert!(manager.is_empty());
+	}
+}
@@ -14,6 +14,12 @@ pub mod resources;
 pub use resources::Resources;
 pub use resources::ResourceLocation;
 
+pub mod assets;
+pub use assets::AssetManager;
+pub use assets::Handle;
+
+pub mod loading;
+
 pub fn current_time_nanos() -> u128 {
 	use std::time::{SystemTime, UNIX_EPOCH};
 	let start = SystemTime::now();
@@ -0,0 +1,146 @@
+//! Loading things over many frames, so the window stays responsive and can show the progress.
+//!
+//! A `LoadingSequence` is a list of tasks, run one after the other on the main thread.
+//! Every task has a weight, its share of the total progress. A task is stepped until it is done;
+//! a step that has to wait, usually for a job on a `WorkerPool`, returns `TaskStatus::Running`
+//! with the progress of its task, which ends the update so the next frame can be drawn.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+pub mod workers;
+pub use workers::{WorkerPool, Pending, JobStatus};
+
+/// The result of one step of a loading task.
+pub enum TaskStatus {
+	Done,
+	
+	/// The task has to be stepped again; with its progress, from 0 to 1.
+	Running(f32),
+}
+
+/// The overall progress of a loading sequence, for display.
+#[derive(Clone, Debug, Default)]
+pub struct LoadingProgress {
+	/// The weighted share of the done work, from 0 to 1.
+	pub fraction: f32,
+	
+	/// The name of the current task; empty when all are done.
+	pub task: String,
+}
+
+pub struct LoadingError {
+	pub task: String,
+	pub reason: String,
+}
+
+impl std::fmt::Display for LoadingError {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		write!(f, "Loading task '{}' failed: {}", self.task, self.reason)
+	}
+}
+
+type LoadingStep<C> = Box<dyn FnMut(&mut C) -> Result<TaskStatus, String>>;
+
+struct LoadingTask<C> {
+	name: String,
+	weight: f32,
+	step: LoadingStep<C>,
+}
+
+/// A list of weighted tasks that work on a shared context of type `C`.
+pub struct LoadingSequence<C> {
+	tasks: VecDeque<LoadingTask<C>>,
+	total_weight: f32,
+	done_weight: f32,
+	
+	/// The progress of the first task, from 0 to 1.
+	current: f32,
+	
+	/// When the first task was first stepped.
+	started: Option<Instant>,
+}
+
+impl<C> LoadingSequence<C> {
+	pub fn new() -> Self {
+		Self {
+			tasks: VecDeque::new(),
+			total_weight: 0.0,
+			done_weight: 0.0,
+			current: 0.0,
+			started: None,
+		}
+	}
+	
+	/// Appends a task; `step` is called until it returns `TaskStatus::Done` or an error.
+	pub fn add<F>(&mut self, name: &str, weight: f32, step: F)
+		where F: 'static + FnMut(&mut C) -> Result<TaskStatus, String>
+	{
+		let weight = weight.max(0.0);
+		self.total_weight += weight;
+		self.tasks.push_back(LoadingTask {
+			name: name.to_string(),
+			weight,
+			step: Box::new(step),
+		});
+	}
+	
+	/// Steps the tasks until one is waiting, the time budget is used up, or all are done.
+	///
+	/// Returns whether all tasks are done. After an error, the failed task is dropped.
+	pub fn update(&mut self, context: &mut C, budget: Duration) -> Result<bool, LoadingError> {
+		let start = Instant::now();
+		
+		while let Some(task) = self.tasks.front_mut() {
+			let started = *self.started.get_or_insert_with(Instant::now);
+			
+			match (task.step)(context) {
+				Ok(TaskStatus::Done) => {
+					info!("Loaded '{}' in {:.0?}.", task.name, started.elapsed());
+					self.done_weight += task.weight;
+					self.current = 0.0;
+					self.started = None;
+					self.tasks.pop_front();
+				},
+				Ok(TaskStatus::Running(progress)) => {
+					self.current = progress.clamp(0.0, 1.0);
+					return Ok(false);
+				},
+				Err(reason) => {
+					let task = self.tasks.pop_front().map(|task| task.name).unwrap_or_default();
+					self.current = 0.0;
+					self.started = None;
+					return Err(LoadingError { task, reason });
+				}
+			}
+			
+			if start.elapsed() >= budget {
+				break;
+			}
+		}
+		
+		Ok(self.tasks.is_empty())
+	}
+	
+	pub fn is_done(&self) -> bool {
+		self.tasks.is_empty()
+	}
+	
+	pub fn progress(&self) -> LoadingProgress {
+		let current = self.tasks.front();
+		
+		let done = self.done_weight + current.map_or(0.0, |task| task.weight * self.current);
+		let fraction = if self.total_weight > 0.0 { done / self.total_weight } else { 1.0 };
+		
+		LoadingProgress {
+			fraction: fraction.clamp(0.0, 1.0),
+			task: current.map(|task| task.name.clone()).unwrap_or_default(),
+		}
+	}
+}
+
+impl<C> Default for LoadingSequence<C> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
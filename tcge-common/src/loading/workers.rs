@@ -0,0 +1,127 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A fixed number of background threads, that run jobs in the order they were spawned.
+///
+/// Jobs must not touch OpenGL (or anything else that belongs to the main thread);
+/// they return their result to the main thread instead, which picks it up by polling its `Pending`.
+pub struct WorkerPool {
+	jobs: Option<mpsc::Sender<Job>>,
+	threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl WorkerPool {
+	/// Starts `count` threads (at least one), named `<name>-<index>`.
+	pub fn new(name: &str, count: usize) -> Self {
+		let (jobs, receiver) = mpsc::channel::<Job>();
+		let receiver = Arc::new(Mutex::new(receiver));
+		
+		let threads = (0..std::cmp::max(count, 1))
+			.map(|index| {
+				let receiver = receiver.clone();
+				thread::Builder::new()
+					.name(format!("{}-{}", name, index))
+					.spawn(move || work(receiver))
+					.unwrap()
+			})
+			.collect();
+		
+		Self {
+			jobs: Some(jobs),
+			threads,
+		}
+	}
+	
+	/// Starts one thread per CPU core, except for the one of the main thread.
+	pub fn with_available_threads(name: &str) -> Self {
+		let count = thread::available_parallelism()
+			.map(|count| count.get())
+			.unwrap_or(2);
+		
+		Self::new(name, count.saturating_sub(1))
+	}
+	
+	/// Queues a job; its result can be taken from the returned `Pending` once it is done.
+	pub fn spawn<T, F>(&self, job: F) -> Pending<T>
+		where T: 'static + Send, F: 'static + Send + FnOnce() -> T
+	{
+		let (result, receiver) = mpsc::channel();
+		
+		let job: Job = Box::new(move || {
+			// If the receiver is gone, nobody is interested in the result anymore.
+			result.send(job()).ok();
+		});
+		
+		if let Some(jobs) = &self.jobs {
+			if jobs.send(job).is_err() {
+				error!("Unable to queue a job: All worker threads have stopped.");
+			}
+		}
+		
+		Pending {
+			receiver
+		}
+	}
+}
+
+impl Drop for WorkerPool {
+	/// Lets the threads finish the queued jobs, then waits for them to stop.
+	fn drop(&mut self) {
+		self.jobs = None;
+		
+		for thread in self.threads.drain(..) {
+			thread.join().ok();
+		}
+	}
+}
+
+fn work(jobs: Arc<Mutex<mpsc::Receiver<Job>>>) {
+	loop {
+		let job = match jobs.lock() {
+			Ok(jobs) => jobs.recv(),
+			Err(_) => break,
+		};
+		
+		let job = match job {
+			Ok(job) => job,
+			Err(_) => break, // The pool was dropped.
+		};
+		
+		// A failing job must not take the thread with it; its `Pending` reports the failure.
+		if std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)).is_err() {
+			error!("A job panicked on worker thread '{}'.", thread::current().name().unwrap_or("?"));
+		}
+	}
+}
+
+/// The state of a job, as seen by `Pending::poll`.
+pub enum JobStatus<T> {
+	Running,
+	Done(T),
+	
+	/// The job panicked, or its result was already taken.
+	Failed,
+}
+
+/// The result of a job that may still be running.
+pub struct Pending<T> {
+	receiver: mpsc::Receiver<T>,
+}
+
+impl<T> Pending<T> {
+	/// Takes the result if the job is done, without blocking.
+	pub fn poll(&self) -> JobStatus<T> {
+		match self.receiver.try_recv() {
+			Ok(result) => JobStatus::Done(result),
+			Err(mpsc::TryRecvError::Empty) => JobStatus::Running,
+			Err(mpsc::TryRecvError::Disconnected) => JobStatus::Failed,
+		}
+	}
+	
+	/// Blocks until the job is done; `None` if it failed.
+	pub fn wait(self) -> Option<T> {
+		self.receiver.recv().ok()
+	}
+}
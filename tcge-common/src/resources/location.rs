@@ -1,45 +1,195 @@
+/// The namespace of locations that don't name one: The assets of the game itself.
+pub const DEFAULT_NAMESPACE: &str = "core";
+
+/// The location of a resource: A namespace and a path within it, written as `namespace:path`.
+///
+/// If the namespace is left out, it is `core`. Providers store the resource at `namespace/path`.
+///
+/// Namespaces consist of lowercase letters, digits, `_` and `-`; paths additionally of `.` and `/`.
+/// Paths are normalised (empty and `.` segments are dropped) and can never leave their namespace:
+/// `..` segments, absolute paths and backslashes are rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ResourceLocation {
-	pub inner: String
+	namespace: String,
+	path: String,
 }
 
 impl ResourceLocation {
-	pub fn from_str(path: &str) -> Self {
-		Self::from_string(path.to_string())
+	/// Parses and validates a location.
+	pub fn parse(location: &str) -> Result<Self, ResourceLocationError> {
+		let (namespace, path) = match location.find(':') {
+			Some(colon) => (&location[..colon], &location[colon + 1..]),
+			None => (DEFAULT_NAMESPACE, location),
+		};
+		
+		Self::new(namespace, path)
 	}
 	
-	pub fn from_string(path: String) -> Self {
-		Self {
-			inner: path
+	/// Validates a location from its parts.
+	pub fn new(namespace: &str, path: &str) -> Result<Self, ResourceLocationError> {
+		let invalid = |reason: &'static str| ResourceLocationError {
+			location: format!("{}:{}", namespace, path),
+			reason
+		};
+		
+		if namespace.is_empty() {
+			return Err(invalid("The namespace is empty"));
+		}
+		
+		if !namespace.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-') {
+			return Err(invalid("The namespace may only contain lowercase letters, digits, '_' and '-'"));
+		}
+		
+		if path.starts_with('/') {
+			return Err(invalid("The path is absolute"));
+		}
+		
+		if path.contains('\\') {
+			return Err(invalid("The path contains a backslash"));
+		}
+		
+		if !path.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-' || c == '.' || c == '/') {
+			return Err(invalid("The path may only contain lowercase letters, digits, '_', '-', '.' and '/'"));
 		}
+		
+		let mut segments = vec![];
+		for segment in path.split('/') {
+			match segment {
+				"" | "." => continue,
+				".." => return Err(invalid("The path leaves its namespace")),
+				segment => segments.push(segment),
+			}
+		}
+		
+		if segments.is_empty() {
+			return Err(invalid("The path is empty"));
+		}
+		
+		Ok(Self {
+			namespace: namespace.to_string(),
+			path: segments.join("/"),
+		})
 	}
 	
-	pub fn pre(&self, lhs: &str) -> Self {
-		Self {
-			inner: lhs.to_string() + &self.inner
-		}
+	/// Converts the path a provider stores a resource at (`namespace/path`) back to its location.
+	pub fn from_storage_path(path: &str) -> Option<Self> {
+		let slash = path.find('/')?;
+		Self::new(&path[..slash], &path[slash + 1..]).ok()
 	}
 	
-	pub fn add(&self, rhs: &str) -> Self {
-		Self {
-			inner: self.inner.clone() + rhs
-		}
+	pub fn namespace(&self) -> &str {
+		&self.namespace
+	}
+	
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+	
+	/// The path a provider stores the resource at: `namespace/path`.
+	pub fn get_storage_path(&self) -> String {
+		format!("{}/{}", self.namespace, self.path)
+	}
+	
+	/// Appends to the path of this location; the result is validated like a parsed location.
+	pub fn add(&self, rhs: &str) -> Result<Self, ResourceLocationError> {
+		Self::new(&self.namespace, &(self.path.clone() + rhs))
 	}
 }
 
-impl From<&str> for ResourceLocation {
-	fn from(path: &str) -> Self {
-		Self::from_str(path)
+impl std::fmt::Display for ResourceLocation {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+		write!(f, "{}:{}", self.namespace, self.path)
 	}
 }
 
-impl From<String> for ResourceLocation {
-	fn from(path: String) -> Self {
-		Self::from_string(path)
-	}
+/// A location that failed validation, with the reason why.
+#[derive(Clone, Debug)]
+pub struct ResourceLocationError {
+	pub location: String,
+	pub reason: &'static str,
 }
 
-impl std::fmt::Display for ResourceLocation {
+impl std::fmt::Display for ResourceLocationError {
 	fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-		write!(f, "{}", self.inner)
+		write!(f, "Invalid resource location '{}': {}", self.location, self.reason)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn parsed(location: &str) -> String {
+		ResourceLocation::parse(location).unwrap().to_string()
+	}
+	
+	fn rejected(location: &str) -> bool {
+		ResourceLocation::parse(location).is_err()
+	}
+	
+	#[test]
+	fn default_namespace() {
+		assert_eq!(parsed("textures/block/adm.png"), "core:textures/block/adm.png");
+		assert_eq!(parsed("core:textures/block/adm.png"), "core:textures/block/adm.png");
+		assert_eq!(parsed("pack-2:textures/block/adm.png"), "pack-2:textures/block/adm.png");
+		
+		let location = ResourceLocation::parse("lang/en.txt").unwrap();
+		assert_eq!(location.namespace(), DEFAULT_NAMESPACE);
+		assert_eq!(location.path(), "lang/en.txt");
+		assert_eq!(location.get_storage_path(), "core/lang/en.txt");
+	}
+	
+	#[test]
+	fn paths_are_normalised() {
+		assert_eq!(parsed("core:lang//en.txt"), "core:lang/en.txt");
+		assert_eq!(parsed("core:./lang/./en.txt"), "core:lang/en.txt");
+		assert_eq!(parsed("core:lang/en.txt/"), "core:lang/en.txt");
+		assert_eq!(parsed("core:fonts/.hidden"), "core:fonts/.hidden");
+		assert_eq!(parsed("core:fonts/...txt"), "core:fonts/...txt");
+		
+		// Normalised locations are equal to the ones written normally.
+		assert_eq!(ResourceLocation::parse("core:lang//./en.txt").unwrap(), ResourceLocation::parse("lang/en.txt").unwrap());
+	}
+	
+	#[test]
+	fn paths_cannot_leave_their_namespace() {
+		assert!(rejected("core:.."));
+		assert!(rejected("core:../pack/lang/en.txt"));
+		assert!(rejected("core:lang/../../en.txt"));
+		assert!(rejected("core:lang/../en.txt"));
+		assert!(rejected("core:/lang/en.txt"));
+		assert!(rejected("/lang/en.txt"));
+		assert!(rejected("core:lang\\en.txt"));
+		assert!(rejected("core:..\\en.txt"));
+	}
+	
+	#[test]
+	fn allowed_characters() {
+		assert_eq!(parsed("my_pack-2:a_b-c.0/d.e"), "my_pack-2:a_b-c.0/d.e");
+		
+		assert!(rejected("Core:lang/en.txt"));
+		assert!(rejected("core.pack:lang/en.txt"));
+		assert!(rejected("co/re:lang/en.txt"));
+		assert!(rejected("core:Lang/en.txt"));
+		assert!(rejected("core:lang/en txt"));
+		assert!(rejected("core:lang/en.txt:2"));
+		assert!(rejected("core:lang/ä.txt"));
+		
+		// Empty namespaces and paths are not locations.
+		assert!(rejected(":lang/en.txt"));
+		assert!(rejected("core:"));
+		assert!(rejected("core:./"));
+		assert!(rejected(""));
+	}
+	
+	#[test]
+	fn add_and_storage_paths() {
+		let font = ResourceLocation::parse("core:fonts/mono").unwrap();
+		assert_eq!(font.add("/mono.fnt").unwrap().to_string(), "core:fonts/mono/mono.fnt");
+		assert!(font.add("/../../lang/en.txt").is_err());
+		
+		assert_eq!(ResourceLocation::from_storage_path("core/lang/en.txt"), Some(ResourceLocation::parse("core:lang/en.txt").unwrap()));
+		assert_eq!(ResourceLocation::from_storage_path("core"), None);
+		assert_eq!(ResourceLocation::from_storage_path("core/lang\\en.txt"), None);
 	}
 }
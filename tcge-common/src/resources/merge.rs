@@ -0,0 +1,130 @@
+//! Merging of resources that are supplied by several providers.
+//!
+//! Usually the provider with the highest priority supplies a resource, overriding all others.
+//! Some resources are collections that every pack should be able to add to instead;
+//! those are merged from the versions of all providers, lowest priority first.
+
+/// How the versions of a resource from several providers are combined.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MergeStrategy {
+	/// The version of the provider with the highest priority is used.
+	Override,
+	
+	/// The lines of all versions are concatenated, dropping the lines that an earlier version already has.
+	///
+	/// Lines repeated within a single version are kept, so merging a version with itself does not change it.
+	///
+	/// Used for lists, like the blocks to register.
+	Lines,
+	
+	/// Every version is a list of `key=value` entries; for each key, the entry of the highest priority is used.
+	///
+	/// Used for language files. Empty lines and lines starting with `#` are dropped.
+	Entries,
+}
+
+impl MergeStrategy {
+	/// Merges the given versions of a resource, ordered from lowest to highest priority.
+	pub fn merge(&self, versions: &[String]) -> String {
+		match self {
+			MergeStrategy::Override => versions.last().cloned().unwrap_or_default(),
+			MergeStrategy::Lines => merge_lines(versions),
+			MergeStrategy::Entries => merge_entries(versions),
+		}
+	}
+}
+
+fn merge_lines(versions: &[String]) -> String {
+	// The lines of the versions before the current one.
+	let mut seen = std::collections::HashSet::new();
+	let mut merged = String::new();
+	
+	for version in versions {
+		for line in version.lines() {
+			if !seen.contains(line) {
+				merged.push_str(line);
+				merged.push('\n');
+			}
+		}
+		
+		seen.extend(version.lines());
+	}
+	
+	merged
+}
+
+fn merge_entries(versions: &[String]) -> String {
+	// Keeps the order in which the keys first appear, so the merged file stays readable.
+	let mut keys: Vec<&str> = vec![];
+	let mut entries: std::collections::HashMap<&str, &str> = std::collections::HashMap::new();
+	
+	for line in versions.iter().flat_map(|version| version.lines()) {
+		let trimmed = line.trim();
+		if trimmed.is_empty() || trimmed.starts_with('#') {
+			continue;
+		}
+		
+		let key = match trimmed.find('=') {
+			Some(equals) => trimmed[..equals].trim(),
+			None => trimmed,
+		};
+		
+		if entries.insert(key, trimmed).is_none() {
+			keys.push(key);
+		}
+	}
+	
+	let mut merged = String::new();
+	for key in keys {
+		merged.push_str(entries[key]);
+		merged.push('\n');
+	}
+	
+	merged
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn merge(strategy: MergeStrategy, versions: &[&str]) -> String {
+		let versions: Vec<String> = versions.iter().map(|version| version.to_string()).collect();
+		strategy.merge(&versions)
+	}
+	
+	#[test]
+	fn override_uses_the_last_version() {
+		assert_eq!(merge(MergeStrategy::Override, &["low", "high"]), "high");
+		assert_eq!(merge(MergeStrategy::Override, &["only"]), "only");
+		assert_eq!(merge(MergeStrategy::Override, &[]), "");
+	}
+	
+	#[test]
+	fn lines_are_appended_once() {
+		assert_eq!(merge(MergeStrategy::Lines, &["adm\nadm_fence", "adm_fence\nadm_cross\nadm"]), "adm\nadm_fence\nadm_cross\n");
+		assert_eq!(merge(MergeStrategy::Lines, &["a", "b", "a\nc", "b\nc"]), "a\nb\nc\n");
+		assert_eq!(merge(MergeStrategy::Lines, &[]), "");
+	}
+	
+	#[test]
+	fn lines_repeated_within_a_version_are_kept() {
+		let list = "# blocks\nadm\n\n# fences\nadm_fence\n\n";
+		assert_eq!(merge(MergeStrategy::Lines, &[list]), list);
+		assert_eq!(merge(MergeStrategy::Lines, &[list, list]), list);
+		
+		// Only lines an earlier version has are dropped, not those repeated in the later version itself.
+		assert_eq!(merge(MergeStrategy::Lines, &["a", "b\nb\na"]), "a\nb\nb\n");
+	}
+	
+	#[test]
+	fn entries_of_higher_priority_win() {
+		let low = "# English\ngreeting = Hello\nfarewell = Bye\n\n";
+		let high = "farewell = Goodbye\nthanks=Thanks";
+		
+		assert_eq!(merge(MergeStrategy::Entries, &[low, high]), "greeting = Hello\nfarewell = Goodbye\nthanks=Thanks\n");
+		assert_eq!(merge(MergeStrategy::Entries, &[high, low]), "farewell = Bye\nthanks=Thanks\ngreeting = Hello\n");
+		
+		// Lines without '=' are keys without a value.
+		assert_eq!(merge(MergeStrategy::Entries, &["flag", "flag = on"]), "flag = on\n");
+	}
+}
@@ -2,10 +2,31 @@ pub use std::io::Read;
 
 pub mod location;
 pub use location::ResourceLocation;
+pub use location::ResourceLocationError;
 
 pub mod providers;
 pub use providers::*;
 
+pub mod storage;
+pub use storage::{ResourceStorage, StorageWriter};
+
+pub mod watcher;
+pub use watcher::ResourceWatcher;
+
+pub mod merge;
+pub use merge::MergeStrategy;
+
+use std::collections::BTreeMap;
+
+/// The priority of the loose files in the assets folder: They override everything else.
+pub const PRIORITY_FILESYSTEM: i32 = 200;
+
+/// The priority of resource packs.
+pub const PRIORITY_ARCHIVES: i32 = 100;
+
+/// The priority of the files embedded in the binary: The defaults, overridden by everything else.
+pub const PRIORITY_INCLUDES: i32 = 0;
+
 pub fn new() -> Resources {
 	Resources::new()
 }
@@ -17,27 +38,140 @@ pub enum ResourceError {
 	Unknown,
 }
 
+/// The set of all resource providers.
+///
+/// Every provider has a priority; if several providers have a resource, the one with the highest priority supplies it.
+/// Providers with the same priority are asked in the order they were registered.
+/// Resources whose path starts with a prefix given to `set_merge_strategy` are merged from all providers instead.
 pub struct Resources {
-	providers: Vec<Box<dyn ResourceProvider>>
+	/// The providers, sorted by descending priority.
+	providers: Vec<(i32, Box<dyn ResourceProvider>)>,
+	
+	/// The merge strategies, by path prefix.
+	merge_strategies: Vec<(String, MergeStrategy)>,
 }
 
 impl Resources {
 	pub fn new() -> Self {
-		let mut new = Resources {providers: vec![]};
+		let mut new = Resources {
+			providers: vec![],
+			merge_strategies: vec![],
+		};
+		
+		new.set_merge_strategy("lang/", MergeStrategy::Entries);
+		new.set_merge_strategy("lists/", MergeStrategy::Lines);
 		
 		let filesystem = FilesystemProvider::from_exe_path()
 			.ok().expect("Could not open filesystem provider.");
-		new.register_provider_by_type(filesystem);
+		new.register_provider_by_type(filesystem, PRIORITY_FILESYSTEM);
+		
+		match get_exe_path() {
+			Ok(exe_path) => new.register_archives(&exe_path.join("assets").join("packs")),
+			Err(_) => warn!("Could not find the resource packs folder."),
+		}
 		
 		new
 	}
 	
-	pub fn register_provider_by_type<P: 'static + ResourceProvider>(&mut self, provider: P) {
-		self.providers.push(Box::new(provider));
+	/// Registers every `.zip` archive in the given directory with `PRIORITY_ARCHIVES`, in the order of their names.
+	///
+	/// Of two archives with the same resource, the one whose name sorts first supplies it.
+	/// Archives that fail to open are skipped, with an error logged.
+	pub fn register_archives(&mut self, directory: &std::path::Path) {
+		let entries = match std::fs::read_dir(directory) {
+			Ok(entries) => entries,
+			Err(_) => return,
+		};
+		
+		let mut archives: Vec<std::path::PathBuf> = entries
+			.filter_map(|e| e.ok())
+			.map(|e| e.path())
+			.filter(|path| path.is_file())
+			.filter(|path| path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("zip")))
+			.collect();
+		
+		archives.sort();
+		
+		for path in archives {
+			match ArchiveProvider::open(&path) {
+				Ok(archive) => self.register_provider_by_type(archive, PRIORITY_ARCHIVES),
+				Err(_) => error!("Failed to open resource pack: {}", path.to_str().unwrap_or("[ERROR]")),
+			}
+		}
 	}
 	
-	pub fn register_provider(&mut self, provider: Box<dyn ResourceProvider>) {
-		self.providers.push(provider);
+	pub fn register_provider_by_type<P: 'static + ResourceProvider>(&mut self, provider: P, priority: i32) {
+		self.register_provider(Box::new(provider), priority);
+	}
+	
+	pub fn register_provider(&mut self, provider: Box<dyn ResourceProvider>, priority: i32) {
+		// Insert behind all providers of the same priority, so those keep precedence.
+		let index = self.providers.iter()
+			.position(|(other, _)| *other < priority)
+			.unwrap_or(self.providers.len());
+		
+		self.providers.insert(index, (priority, provider));
+	}
+	
+	/// Sets how the resources whose path (without namespace) starts with the given prefix are combined.
+	///
+	/// If several prefixes match a path, the longest one applies.
+	pub fn set_merge_strategy(&mut self, prefix: &str, strategy: MergeStrategy) {
+		self.merge_strategies.retain(|(other, _)| other != prefix);
+		self.merge_strategies.push((prefix.to_string(), strategy));
+	}
+	
+	pub fn get_merge_strategy(&self, location: &ResourceLocation) -> MergeStrategy {
+		self.merge_strategies.iter()
+			.filter(|(prefix, _)| location.path().starts_with(prefix.as_str()))
+			.max_by_key(|(prefix, _)| prefix.len())
+			.map(|(_, strategy)| *strategy)
+			.unwrap_or(MergeStrategy::Override)
+	}
+	
+	/// Lists every resource once, with the name of the provider that supplies it.
+	///
+	/// For merged resources, that is the provider with the highest priority.
+	pub fn res_list_resolved(&self) -> Result<BTreeMap<String, &str>, ResourceError> {
+		let mut resolved = BTreeMap::new();
+		
+		for (_, provider) in self.providers.iter() {
+			for path in provider.res_list()? {
+				resolved.entry(path).or_insert(provider.get_internal_name());
+			}
+		}
+		
+		Ok(resolved)
+	}
+	
+	/// Returns the names of all providers that have the given resource, from the highest priority to the lowest.
+	pub fn res_suppliers(&self, location: &ResourceLocation) -> Vec<&str> {
+		self.providers.iter()
+			.filter(|(_, provider)| provider.res_exists(location))
+			.map(|(_, provider)| provider.get_internal_name())
+			.collect()
+	}
+	
+	/// Reads the versions of a resource from all providers that have it, and merges them.
+	fn res_merged(&self, location: &ResourceLocation, strategy: MergeStrategy) -> Result<Box<dyn Read>, ResourceError> {
+		let mut versions = vec![];
+		
+		for (_, provider) in self.providers.iter().rev() {
+			if provider.res_exists(location) {
+				versions.push(provider.res_as_string(location)?);
+			}
+		}
+		
+		if versions.is_empty() {
+			error!("Failed to find resource: {}", location);
+			return Err(ResourceError::NotFound);
+		}
+		
+		if versions.len() > 1 {
+			debug!("Merging {} versions of resource: {}", versions.len(), location);
+		}
+		
+		Ok(Box::new(std::io::Cursor::new(strategy.merge(&versions).into_bytes())))
 	}
 }
 
@@ -47,15 +181,19 @@ impl ResourceProvider for Resources {
 	}
 	
 	fn res_list(&self) -> Result<Box<dyn Iterator<Item = String>>, ResourceError> {
-		let mut iter: Box<dyn Iterator<Item = String>> = Box::new(std::iter::empty());
-		
-		for provider in self.providers.iter() {
-			let sub_iter = match provider.res_list() {
-				Err(err) => return Err(err),
-				Ok(iter) => iter,
-			};
-			
-			iter = Box::new(iter.chain(sub_iter));
+		let paths: Vec<String> = self.res_list_resolved()?
+			.into_iter()
+			.map(|(path, _)| path)
+			.collect();
+		
+		Ok(Box::new(paths.into_iter()))
+	}
+	
+	fn res_list_modified(&self) -> Result<Box<dyn Iterator<Item = (String, std::time::SystemTime)>>, ResourceError> {
+		let mut iter: Box<dyn Iterator<Item = (String, std::time::SystemTime)>> = Box::new(std::iter::empty());
+		
+		for (_, provider) in self.providers.iter() {
+			iter = Box::new(iter.chain(provider.res_list_modified()?));
 		}
 		
 		Ok(iter)
@@ -64,7 +202,12 @@ impl ResourceProvider for Resources {
 	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError> {
 		debug!("Attempting to find resource: {}", location);
 		
-		for provider in self.providers.iter() {
+		let strategy = self.get_merge_strategy(location);
+		if strategy != MergeStrategy::Override {
+			return self.res_merged(location, strategy);
+		}
+		
+		for (_, provider) in self.providers.iter() {
 			match provider.res_as_stream(location) {
 				Err(_) => continue,
 				Ok(stream) => {
@@ -76,6 +219,10 @@ impl ResourceProvider for Resources {
 		error!("Failed to find resource: {}", location);
 		Err(ResourceError::NotFound)
 	}
+	
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		self.providers.iter().any(|(_, provider)| provider.res_exists(location))
+	}
 }
 
 pub fn get_exe_path() -> Result<std::path::PathBuf, std::io::Error> {
@@ -85,4 +232,62 @@ pub fn get_exe_path() -> Result<std::path::PathBuf, std::io::Error> {
 		.ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?;
 	
 	Ok(exe_path.into())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn read(res: &Resources, location: &str) -> Option<String> {
+		res.res_as_string(&ResourceLocation::parse(location).unwrap()).ok()
+	}
+	
+	#[test]
+	fn higher_priority_wins() {
+		let mut res = Resources::new();
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"low"), ("core/b.txt", b"only low")]), PRIORITY_INCLUDES);
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"high")]), PRIORITY_ARCHIVES);
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"middle")]), PRIORITY_ARCHIVES - 1);
+		
+		assert_eq!(read(&res, "core:a.txt").as_deref(), Some("high"));
+		assert_eq!(read(&res, "core:b.txt").as_deref(), Some("only low"));
+		assert_eq!(read(&res, "core:c.txt"), None);
+	}
+	
+	#[test]
+	fn equal_priorities_keep_the_order_of_registration() {
+		let mut res = Resources::new();
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"first")]), PRIORITY_ARCHIVES);
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"second"), ("core/b.txt", b"second")]), PRIORITY_ARCHIVES);
+		res.register_provider_by_type(IncludeProvider::new(vec![("core/a.txt", b"third"), ("core/b.txt", b"third")]), PRIORITY_ARCHIVES);
+		
+		assert_eq!(read(&res, "core:a.txt").as_deref(), Some("first"));
+		assert_eq!(read(&res, "core:b.txt").as_deref(), Some("second"));
+	}
+	
+	#[test]
+	fn merge_strategies_by_prefix() {
+		let mut res = Resources::new();
+		res.set_merge_strategy("lists/blocks/", MergeStrategy::Override);
+		
+		res.register_provider_by_type(IncludeProvider::new(vec![
+			("core/lang/en.txt", b"greeting = Hello\nfarewell = Bye"),
+			("core/lists/items.txt", b"stick\nstone"),
+			("core/lists/blocks/all.txt", b"adm"),
+			("core/other.txt", b"low"),
+		]), PRIORITY_INCLUDES);
+		
+		res.register_provider_by_type(IncludeProvider::new(vec![
+			("core/lang/en.txt", b"farewell = Goodbye"),
+			("core/lists/items.txt", b"stone\nbrick"),
+			("core/lists/blocks/all.txt", b"adm_fence"),
+			("core/other.txt", b"high"),
+		]), PRIORITY_ARCHIVES);
+		
+		assert_eq!(read(&res, "core:lang/en.txt").as_deref(), Some("greeting = Hello\nfarewell = Goodbye\n"));
+		assert_eq!(read(&res, "core:lists/items.txt").as_deref(), Some("stick\nstone\nbrick\n"));
+		
+		// The longest matching prefix applies.
+		assert_eq!(read(&res, "core:lists/blocks/all.txt").as_deref(), Some("adm_fence"));
+		assert_eq!(read(&res, "core:other.txt").as_deref(), Some("high"));
+	}
+}
@@ -1,11 +1,217 @@
 extern crate zip;
 
+use super::ResourceLocation;
+use super::ResourceError;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Cursor};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Provides the files of a zip archive (a 'resource pack').
+///
+/// The central directory is read once, when the archive is opened;
+/// entries whose names could escape the archive (absolute paths, `..`, drive letters) are ignored.
 pub struct ArchiveProvider {
-	// TODO: Everything.
+	/// The file name of the archive, which names the provider.
+	name: String,
+	
+	archive: Mutex<zip::ZipArchive<File>>,
+	
+	/// The cached central directory: The index of every file entry, by name.
+	entries: HashMap<String, usize>,
 }
 
 impl ArchiveProvider {
-	pub fn new() {
-		unimplemented!()
+	pub fn open(path: &Path) -> Result<Self, ResourceError> {
+		let file = File::open(path)
+			.map_err(|ioe| ResourceError::Io(ioe))?;
+		
+		let mut archive = zip::ZipArchive::new(file)
+			.map_err(|err| Self::map_error(err))?;
+		
+		let mut entries = HashMap::with_capacity(archive.len());
+		for index in 0..archive.len() {
+			let entry = archive.by_index(index)
+				.map_err(|err| Self::map_error(err))?;
+			
+			let name = entry.name();
+			
+			if name.ends_with('/') {
+				// Directories are not resources.
+				continue;
+			}
+			
+			if !Self::is_safe_name(name) {
+				warn!("Ignoring unsafe entry '{}' in archive {}", name, path.to_str().unwrap_or("[ERROR]"));
+				continue;
+			}
+			
+			entries.insert(name.to_string(), index);
+		}
+		
+		let name = path.file_name()
+			.and_then(|name| name.to_str())
+			.unwrap_or("Archive")
+			.to_string();
+		
+		info!("Created ArchiveProvider: {} ({} Items)", path.to_str().unwrap_or("[ERROR]"), entries.len());
+		Ok(Self {
+			name,
+			archive: Mutex::new(archive),
+			entries,
+		})
+	}
+	
+	/// Returns whether the given entry name is a plain relative path, that stays within the archive.
+	fn is_safe_name(name: &str) -> bool {
+		!name.is_empty()
+			&& !name.starts_with('/')
+			&& !name.contains('\\')
+			&& !name.contains(':')
+			&& name.split('/').all(|part| !part.is_empty() && part != "." && part != "..")
+	}
+	
+	fn map_error(err: zip::result::ZipError) -> ResourceError {
+		match err {
+			zip::result::ZipError::Io(ioe) => ResourceError::Io(ioe),
+			zip::result::ZipError::FileNotFound => ResourceError::NotFound,
+			err => ResourceError::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string())),
+		}
+	}
+}
+
+impl super::ResourceProvider for ArchiveProvider {
+	fn get_internal_name(&self) -> &str {
+		&self.name
+	}
+	
+	fn res_list(&self) -> Result<Box<dyn Iterator<Item = String>>, ResourceError> {
+		let iter: Vec<String> = self.entries.keys()
+			.cloned()
+			.collect()
+		;
+		Ok(Box::new(iter.into_iter()))
+	}
+	
+	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError> {
+		let index = *self.entries.get(&location.get_storage_path())
+			.ok_or(ResourceError::NotFound)?;
+		
+		let mut archive = self.archive.lock()
+			.map_err(|_| ResourceError::Unknown)?;
+		let mut entry = archive.by_index(index)
+			.map_err(|err| Self::map_error(err))?;
+		
+		// The entry borrows the archive, so it has to be read completely.
+		let mut buffer = Vec::with_capacity(entry.size() as usize);
+		entry.read_to_end(&mut buffer)
+			.map_err(|ioe| ResourceError::Io(ioe))?;
+		
+		Ok(Box::new(Cursor::new(buffer)))
+	}
+	
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		self.entries.contains_key(&location.get_storage_path())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::ResourceProvider;
+	use super::super::filesystem::tests::TempDir;
+	use std::io::Write;
+	
+	/// Writes a zip archive with the given entries; directories end with a `/`.
+	fn write_archive(path: &Path, entries: &[(&str, &str)]) {
+		let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+		
+		for (name, content) in entries {
+			if name.ends_with('/') {
+				zip.add_directory(*name, zip::write::FileOptions::default()).unwrap();
+			} else {
+				zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+				zip.write_all(content.as_bytes()).unwrap();
+			}
+		}
+		
+		zip.finish().unwrap();
+	}
+	
+	fn location(location: &str) -> ResourceLocation {
+		ResourceLocation::parse(location).unwrap()
+	}
+	
+	fn read(archive: &ArchiveProvider, path: &str) -> Option<String> {
+		archive.res_as_string(&location(path)).ok()
+	}
+	
+	#[test]
+	fn unsafe_names() {
+		assert!(ArchiveProvider::is_safe_name("core/lang/en.txt"));
+		assert!(ArchiveProvider::is_safe_name("core/textures/block/adm.png"));
+		
+		assert!(!ArchiveProvider::is_safe_name(""));
+		assert!(!ArchiveProvider::is_safe_name("../core/lang/en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("core/../../en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("/core/lang/en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("core\\lang\\en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("C:/core/lang/en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("core/./lang/en.txt"));
+		assert!(!ArchiveProvider::is_safe_name("core//lang/en.txt"));
+	}
+	
+	#[test]
+	fn unsafe_entries_are_ignored() {
+		let dir = TempDir::new("archive-unsafe");
+		let path = dir.0.join("pack.zip");
+		write_archive(&path, &[
+			("core/", ""),
+			("core/lang/", ""),
+			("core/lang/en.txt", "good"),
+			("../core/lang/de.txt", "parent"),
+			("core/../core/lang/fr.txt", "parent inside"),
+			("/core/lang/es.txt", "absolute"),
+			("core\\lang\\it.txt", "backslashes"),
+			("C:/core/lang/nl.txt", "drive letter"),
+		]);
+		
+		let archive = ArchiveProvider::open(&path).ok().unwrap();
+		assert_eq!(archive.get_internal_name(), "pack.zip");
+		assert_eq!(archive.res_list().ok().unwrap().collect::<Vec<_>>(), vec!["core/lang/en.txt"]);
+		
+		assert!(archive.res_exists(&location("core:lang/en.txt")));
+		assert_eq!(read(&archive, "core:lang/en.txt").as_deref(), Some("good"));
+		
+		for language in &["de", "fr", "es", "it", "nl"] {
+			let location = location(&format!("core:lang/{}.txt", language));
+			assert!(!archive.res_exists(&location), "{} exists", location);
+			assert!(matches!(archive.res_as_stream(&location), Err(ResourceError::NotFound)), "{} can be read", location);
+		}
+	}
+	
+	#[test]
+	fn entries_are_read_from_the_cached_directory() {
+		let dir = TempDir::new("archive-read");
+		let path = dir.0.join("pack.zip");
+		write_archive(&path, &[
+			("core/lang/en.txt", "hello"),
+			("core/lists/blocks.txt", "adm\nadm_fence\nadm_cross"),
+		]);
+		
+		let archive = ArchiveProvider::open(&path).ok().unwrap();
+		
+		let mut list: Vec<String> = archive.res_list().ok().unwrap().collect();
+		list.sort();
+		assert_eq!(list, vec!["core/lang/en.txt", "core/lists/blocks.txt"]);
+		
+		// Entries can be read any number of times, in any order.
+		assert_eq!(read(&archive, "core:lists/blocks.txt").as_deref(), Some("adm\nadm_fence\nadm_cross"));
+		assert_eq!(read(&archive, "core:lang/en.txt").as_deref(), Some("hello"));
+		assert_eq!(read(&archive, "core:lists/blocks.txt").as_deref(), Some("adm\nadm_fence\nadm_cross"));
+		
+		assert!(!archive.res_exists(&location("core:lang/de.txt")));
+		assert!(matches!(archive.res_as_stream(&location("core:lang/de.txt")), Err(ResourceError::NotFound)));
 	}
 }
@@ -1,24 +1,49 @@
 use super::ResourceLocation;
 use super::ResourceError;
-use std::path::PathBuf;
-use std::io::Read;
+use super::super::{ResourceStorage, StorageWriter};
+use std::path::{Path, PathBuf};
+use std::io::{Read, Write, BufWriter};
 use std::fs::File;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 use walkdir;
 
+/// Provides the files in a directory, stored at `<root>/<namespace>/<path>`.
+///
+/// It is also a `ResourceStorage`; writes go to a temporary file next to the target, which then replaces it.
 pub struct FilesystemProvider {
 	root_path: PathBuf
 }
 
 impl FilesystemProvider {
+	pub fn new(root_path: PathBuf) -> FilesystemProvider {
+		info!("Created FilesystemProvider: {}", root_path.to_str().unwrap_or("[ERROR]"));
+		FilesystemProvider {
+			root_path
+		}
+	}
+	
 	pub fn from_exe_path() -> Result<FilesystemProvider, ResourceError> {
 		let exe_path = super::super::get_exe_path()
 			.map_err(|ioe| ResourceError::Io(ioe))?;
-		let fs_path = exe_path.join("assets");
 		
-		info!("Created FilesystemProvider: {}", fs_path.to_str().unwrap_or("[ERROR]"));
-		Ok(FilesystemProvider {
-			root_path: fs_path
-		})
+		Ok(Self::new(exe_path.join("assets")))
+	}
+	
+	pub fn get_root_path(&self) -> &Path {
+		&self.root_path
+	}
+	
+	fn get_path(&self, location: &ResourceLocation) -> PathBuf {
+		let mut path: PathBuf = self.root_path.clone();
+		
+		// Instead of passing the path directly...
+		// (Locations are validated, so no part can leave the root.)
+		for part in location.get_storage_path().split("/") {
+			path = path.join(part);
+		}
+		
+		path
 	}
 }
 
@@ -58,17 +83,247 @@ impl super::ResourceProvider for FilesystemProvider {
 		Ok(Box::new(walker_iter))
 	}
 	
+	fn res_list_modified(&self) -> Result<Box<dyn Iterator<Item = (String, SystemTime)>>, ResourceError> {
+		let root = self.root_path.clone();
+		let walker = walkdir::WalkDir::new(root.clone())
+			.max_depth(5)
+			.same_file_system(true)
+		;
+		
+		let walker_iter = walker.into_iter()
+			.filter_map(|e| e.ok())
+			.filter(|e| e.file_type().is_file())
+			.filter_map(move |e| {
+				let modified = e.metadata().ok()?.modified().ok()?;
+				let path = e.path().strip_prefix(&root).ok()?.to_str()?.replace('\\', "/");
+				Some((path, modified))
+			})
+		;
+		
+		Ok(Box::new(walker_iter))
+	}
+	
 	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError> {
-		let mut path: PathBuf = self.root_path.clone();
+		let file = File::open(self.get_path(location))
+			.map_err(|ioe| ResourceError::Io(ioe))?;
 		
-		// Instead of passing the path directly...
-		for part in location.inner.split("/") {
-			path = path.join(part);
+		Ok(Box::new(file))
+	}
+	
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		self.get_path(location).is_file()
+	}
+}
+
+impl ResourceStorage for FilesystemProvider {
+	fn write_stream(&self, location: &ResourceLocation) -> Result<Box<dyn StorageWriter>, ResourceError> {
+		// Used to give the temporary files of concurrent writes distinct names.
+		static WRITES: AtomicUsize = AtomicUsize::new(0);
+		
+		let path = self.get_path(location);
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)
+				.map_err(|ioe| ResourceError::Io(ioe))?;
 		}
 		
-		let file = File::open(path)
+		let file_name = path.file_name()
+			.and_then(|name| name.to_str())
+			.ok_or(ResourceError::Unknown)?;
+		
+		let temp_name = format!(".{}.{}-{}.tmp", file_name, std::process::id(), WRITES.fetch_add(1, Ordering::Relaxed));
+		let temp_path = path.with_file_name(temp_name);
+		
+		let file = File::create(&temp_path)
 			.map_err(|ioe| ResourceError::Io(ioe))?;
 		
-		Ok(Box::new(file))
+		Ok(Box::new(FilesystemWriter {
+			file: Some(BufWriter::new(file)),
+			temp_path,
+			path,
+		}))
+	}
+	
+	fn create_dir(&self, location: &ResourceLocation) -> Result<(), ResourceError> {
+		std::fs::create_dir_all(self.get_path(location))
+			.map_err(|ioe| ResourceError::Io(ioe))
+	}
+	
+	fn delete(&self, location: &ResourceLocation) -> Result<(), ResourceError> {
+		let path = self.get_path(location);
+		
+		let result = if path.is_dir() {
+			std::fs::remove_dir(path)
+		} else {
+			std::fs::remove_file(path)
+		};
+		
+		result.map_err(|ioe| match ioe.kind() {
+			std::io::ErrorKind::NotFound => ResourceError::NotFound,
+			_ => ResourceError::Io(ioe),
+		})
+	}
+	
+	fn rename(&self, from: &ResourceLocation, to: &ResourceLocation) -> Result<(), ResourceError> {
+		let from = self.get_path(from);
+		let to = self.get_path(to);
+		
+		if !from.exists() {
+			return Err(ResourceError::NotFound);
+		}
+		
+		if let Some(parent) = to.parent() {
+			std::fs::create_dir_all(parent)
+				.map_err(|ioe| ResourceError::Io(ioe))?;
+		}
+		
+		std::fs::rename(from, to)
+			.map_err(|ioe| ResourceError::Io(ioe))
+	}
+}
+
+/// Writes to a temporary file, which replaces the target on commit; or is deleted, if never committed.
+struct FilesystemWriter {
+	file: Option<BufWriter<File>>,
+	temp_path: PathBuf,
+	path: PathBuf,
+}
+
+impl Write for FilesystemWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		match &mut self.file {
+			Some(file) => file.write(buf),
+			None => Err(std::io::Error::from(std::io::ErrorKind::BrokenPipe)),
+		}
+	}
+	
+	fn flush(&mut self) -> std::io::Result<()> {
+		match &mut self.file {
+			Some(file) => file.flush(),
+			None => Ok(()),
+		}
+	}
+}
+
+impl StorageWriter for FilesystemWriter {
+	fn commit(mut self: Box<Self>) -> Result<(), ResourceError> {
+		let file = self.file.take()
+			.ok_or(ResourceError::Unknown)?;
+		
+		let result = file.into_inner()
+			.map_err(|err| err.into_error())
+			.and_then(|file| file.sync_all())
+			.and_then(|_| std::fs::rename(&self.temp_path, &self.path));
+		
+		if let Err(ioe) = result {
+			std::fs::remove_file(&self.temp_path).ok();
+			return Err(ResourceError::Io(ioe));
+		}
+		
+		Ok(())
+	}
+}
+
+impl Drop for FilesystemWriter {
+	fn drop(&mut self) {
+		if let Some(file) = self.file.take() {
+			drop(file);
+			std::fs::remove_file(&self.temp_path).ok();
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use super::super::ResourceProvider;
+	
+	/// A fresh directory in the system's temporary directory, deleted when dropped.
+	pub(crate) struct TempDir(pub PathBuf);
+	
+	impl TempDir {
+		pub(crate) fn new(name: &str) -> Self {
+			let path = std::env::temp_dir().join(format!("tcge-{}-{}", name, std::process::id()));
+			std::fs::remove_dir_all(&path).ok();
+			std::fs::create_dir_all(&path).unwrap();
+			TempDir(path)
+		}
+		
+		/// The names of the files in the given directory below the root.
+		fn files(&self, dir: &str) -> Vec<String> {
+			let mut files: Vec<String> = std::fs::read_dir(self.0.join(dir)).unwrap()
+				.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+				.collect();
+			
+			files.sort();
+			files
+		}
+	}
+	
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			std::fs::remove_dir_all(&self.0).ok();
+		}
+	}
+	
+	fn location(location: &str) -> ResourceLocation {
+		ResourceLocation::parse(location).unwrap()
+	}
+	
+	fn read(storage: &FilesystemProvider, path: &str) -> Option<String> {
+		storage.res_as_string(&location(path)).ok()
+	}
+	
+	#[test]
+	fn commit_replaces_file() {
+		let dir = TempDir::new("commit");
+		let storage = FilesystemProvider::new(dir.0.clone());
+		let location = location("core:saves/world.dat");
+		
+		storage.write_buffer(&location, b"old").ok().unwrap();
+		
+		let mut writer = storage.write_stream(&location).ok().unwrap();
+		writer.write_all(b"new").unwrap();
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("old"));
+		
+		writer.commit().ok().unwrap();
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("new"));
+		assert_eq!(dir.files("core/saves"), vec!["world.dat"]);
+	}
+	
+	#[test]
+	fn dropped_write_keeps_old_file() {
+		let dir = TempDir::new("drop");
+		let storage = FilesystemProvider::new(dir.0.clone());
+		let location = location("core:saves/world.dat");
+		
+		storage.write_buffer(&location, b"old").ok().unwrap();
+		
+		let mut writer = storage.write_stream(&location).ok().unwrap();
+		writer.write_all(b"new").unwrap();
+		assert_eq!(dir.files("core/saves").len(), 2, "the write does not go to a temporary file");
+		drop(writer);
+		
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("old"));
+		assert_eq!(dir.files("core/saves"), vec!["world.dat"]);
+	}
+	
+	#[test]
+	fn failed_write_leaves_no_temporary_file() {
+		let dir = TempDir::new("fail");
+		let storage = FilesystemProvider::new(dir.0.clone());
+		
+		// The target is a directory that is not empty, so the temporary file cannot replace it.
+		storage.write_buffer(&location("core:saves/world/level.dat"), b"level").ok().unwrap();
+		
+		let mut writer = storage.write_stream(&location("core:saves/world")).ok().unwrap();
+		writer.write_all(b"new").unwrap();
+		
+		match writer.commit() {
+			Err(ResourceError::Io(_)) => (),
+			_ => panic!("Expected the commit to fail"),
+		}
+		
+		assert_eq!(read(&storage, "core:saves/world/level.dat").as_deref(), Some("level"));
+		assert_eq!(dir.files("core/saves"), vec!["world"]);
 	}
 }
@@ -32,9 +32,9 @@ impl super::ResourceProvider for IncludeProvider {
 	}
 	
 	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError> {
-		let location = &location.inner;
+		let location = location.get_storage_path();
 		let file = self.includes.iter()
-			.find(|(name, _bytes)| name == location);
+			.find(|(name, _bytes)| *name == location);
 		
 		match file {
 			None => Err(ResourceError::NotFound),
@@ -45,4 +45,9 @@ impl super::ResourceProvider for IncludeProvider {
 			}
 		}
 	}
+	
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		let location = location.get_storage_path();
+		self.includes.iter().any(|(name, _bytes)| *name == location)
+	}
 }
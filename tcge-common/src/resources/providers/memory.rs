@@ -0,0 +1,354 @@
+use super::ResourceLocation;
+use super::ResourceError;
+use super::super::{ResourceStorage, StorageWriter};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write, Cursor};
+use std::sync::{Arc, Mutex};
+
+/// A storage that keeps everything in memory, for tests and for running without user data.
+///
+/// Directories exist implicitly for every stored resource, and explicitly once created.
+pub struct MemoryStorage {
+	state: Arc<Mutex<MemoryState>>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+	/// The content of every resource, by storage path.
+	files: BTreeMap<String, Vec<u8>>,
+	
+	/// The storage paths of the created directories.
+	dirs: BTreeSet<String>,
+}
+
+impl MemoryState {
+	fn is_dir(&self, path: &str) -> bool {
+		let prefix = format!("{}/", path);
+		self.dirs.contains(path)
+			|| self.dirs.iter().any(|dir| dir.starts_with(&prefix))
+			|| self.files.keys().any(|file| file.starts_with(&prefix))
+	}
+}
+
+impl MemoryStorage {
+	pub fn new() -> Self {
+		Self {
+			state: Arc::new(Mutex::new(MemoryState::default()))
+		}
+	}
+	
+	fn lock(&self) -> Result<std::sync::MutexGuard<'_, MemoryState>, ResourceError> {
+		self.state.lock().map_err(|_| ResourceError::Unknown)
+	}
+	
+	fn error(kind: std::io::ErrorKind, message: &str) -> ResourceError {
+		ResourceError::Io(std::io::Error::new(kind, message))
+	}
+}
+
+impl Default for MemoryStorage {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl super::ResourceProvider for MemoryStorage {
+	fn get_internal_name(&self) -> &str {
+		"Memory"
+	}
+	
+	fn res_list(&self) -> Result<Box<dyn Iterator<Item = String>>, ResourceError> {
+		let iter: Vec<String> = self.lock()?.files.keys()
+			.cloned()
+			.collect()
+		;
+		Ok(Box::new(iter.into_iter()))
+	}
+	
+	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError> {
+		let buffer = self.lock()?.files.get(&location.get_storage_path())
+			.cloned()
+			.ok_or(ResourceError::NotFound)?;
+		
+		Ok(Box::new(Cursor::new(buffer)))
+	}
+	
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		match self.lock() {
+			Ok(state) => state.files.contains_key(&location.get_storage_path()),
+			Err(_) => false,
+		}
+	}
+}
+
+impl ResourceStorage for MemoryStorage {
+	fn write_stream(&self, location: &ResourceLocation) -> Result<Box<dyn StorageWriter>, ResourceError> {
+		let path = location.get_storage_path();
+		
+		if self.lock()?.is_dir(&path) {
+			return Err(Self::error(std::io::ErrorKind::Other, "Is a directory"));
+		}
+		
+		Ok(Box::new(MemoryWriter {
+			state: self.state.clone(),
+			path,
+			buffer: vec![],
+		}))
+	}
+	
+	fn create_dir(&self, location: &ResourceLocation) -> Result<(), ResourceError> {
+		let path = location.get_storage_path();
+		let mut state = self.lock()?;
+		
+		if state.files.contains_key(&path) {
+			return Err(Self::error(std::io::ErrorKind::AlreadyExists, "A resource exists at this location"));
+		}
+		
+		state.dirs.insert(path);
+		Ok(())
+	}
+	
+	fn delete(&self, location: &ResourceLocation) -> Result<(), ResourceError> {
+		let path = location.get_storage_path();
+		let mut state = self.lock()?;
+		
+		if state.files.remove(&path).is_some() {
+			return Ok(());
+		}
+		
+		let prefix = format!("{}/", path);
+		if state.files.keys().chain(state.dirs.iter()).any(|other| other.starts_with(&prefix)) {
+			return Err(Self::error(std::io::ErrorKind::Other, "The directory is not empty"));
+		}
+		
+		if state.dirs.remove(&path) {
+			Ok(())
+		} else {
+			Err(ResourceError::NotFound)
+		}
+	}
+	
+	fn rename(&self, from: &ResourceLocation, to: &ResourceLocation) -> Result<(), ResourceError> {
+		let from = from.get_storage_path();
+		let to = to.get_storage_path();
+		let mut state = self.lock()?;
+		
+		if let Some(buffer) = state.files.remove(&from) {
+			if state.is_dir(&to) {
+				state.files.insert(from, buffer);
+				return Err(Self::error(std::io::ErrorKind::Other, "Is a directory"));
+			}
+			
+			state.files.insert(to, buffer);
+			return Ok(());
+		}
+		
+		if !state.is_dir(&from) {
+			return Err(ResourceError::NotFound);
+		}
+		
+		if to.starts_with(&format!("{}/", from)) {
+			return Err(Self::error(std::io::ErrorKind::InvalidInput, "Cannot move a directory into itself"));
+		}
+		
+		if state.files.contains_key(&to) || state.is_dir(&to) {
+			return Err(Self::error(std::io::ErrorKind::AlreadyExists, "The target already exists"));
+		}
+		
+		// Move the directory, and everything in it.
+		let moved = |path: &String| if path == &from {
+			Some(to.clone())
+		} else if path.starts_with(&format!("{}/", from)) {
+			Some(format!("{}{}", to, &path[from.len()..]))
+		} else {
+			None
+		};
+		
+		let files: Vec<(String, String)> = state.files.keys()
+			.filter_map(|path| moved(path).map(|target| (path.clone(), target)))
+			.collect();
+		
+		for (path, target) in files {
+			let buffer = state.files.remove(&path).unwrap_or_default();
+			state.files.insert(target, buffer);
+		}
+		
+		state.dirs = state.dirs.iter()
+			.map(|path| moved(path).unwrap_or_else(|| path.clone()))
+			.collect();
+		
+		Ok(())
+	}
+}
+
+struct MemoryWriter {
+	state: Arc<Mutex<MemoryState>>,
+	path: String,
+	buffer: Vec<u8>,
+}
+
+impl Write for MemoryWriter {
+	fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+		self.buffer.write(buf)
+	}
+	
+	fn flush(&mut self) -> std::io::Result<()> {
+		Ok(())
+	}
+}
+
+impl StorageWriter for MemoryWriter {
+	fn commit(self: Box<Self>) -> Result<(), ResourceError> {
+		let writer = *self;
+		let mut state = writer.state.lock()
+			.map_err(|_| ResourceError::Unknown)?;
+		
+		state.files.insert(writer.path, writer.buffer);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::ResourceProvider;
+	
+	fn location(location: &str) -> ResourceLocation {
+		ResourceLocation::parse(location).unwrap()
+	}
+	
+	fn read(storage: &MemoryStorage, path: &str) -> Option<String> {
+		storage.res_as_string(&location(path)).ok()
+	}
+	
+	fn is_io_error(result: Result<(), ResourceError>) -> bool {
+		matches!(result, Err(ResourceError::Io(_)))
+	}
+	
+	fn is_not_found(result: Result<(), ResourceError>) -> bool {
+		matches!(result, Err(ResourceError::NotFound))
+	}
+	
+	#[test]
+	fn write_then_read() {
+		let storage = MemoryStorage::default();
+		assert!(!storage.res_exists(&location("core:saves/world.dat")));
+		
+		let mut writer = storage.write_stream(&location("core:saves/world.dat")).ok().unwrap();
+		writer.write_all(b"hello ").unwrap();
+		writer.write_all(b"world").unwrap();
+		
+		// Nothing is visible until the writer is committed.
+		assert!(!storage.res_exists(&location("core:saves/world.dat")));
+		writer.commit().ok().unwrap();
+		
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("hello world"));
+		assert_eq!(storage.res_list().ok().unwrap().collect::<Vec<_>>(), vec!["core/saves/world.dat"]);
+		
+		// Writing again replaces the content.
+		storage.write_buffer(&location("core:saves/world.dat"), b"replaced").ok().unwrap();
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("replaced"));
+	}
+	
+	#[test]
+	fn dropped_writer_changes_nothing() {
+		let storage = MemoryStorage::new();
+		storage.write_buffer(&location("core:config.toml"), b"old").ok().unwrap();
+		
+		let mut writer = storage.write_stream(&location("core:config.toml")).ok().unwrap();
+		writer.write_all(b"new").unwrap();
+		drop(writer);
+		
+		assert_eq!(read(&storage, "core:config.toml").as_deref(), Some("old"));
+	}
+	
+	#[test]
+	fn create_and_delete_dir() {
+		let storage = MemoryStorage::new();
+		
+		storage.create_dir(&location("core:saves")).ok().unwrap();
+		assert!(storage.lock().ok().unwrap().is_dir("core/saves"));
+		
+		// A directory is neither a resource, nor can it be written to.
+		assert!(!storage.res_exists(&location("core:saves")));
+		assert!(storage.write_stream(&location("core:saves")).is_err());
+		
+		storage.write_buffer(&location("core:saves/world.dat"), b"world").ok().unwrap();
+		assert!(is_io_error(storage.delete(&location("core:saves"))), "deleted a directory that is not empty");
+		
+		storage.delete(&location("core:saves/world.dat")).ok().unwrap();
+		assert_eq!(read(&storage, "core:saves/world.dat"), None);
+		
+		storage.delete(&location("core:saves")).ok().unwrap();
+		assert!(!storage.lock().ok().unwrap().is_dir("core/saves"));
+	}
+	
+	#[test]
+	fn create_dir_over_resource_fails() {
+		let storage = MemoryStorage::new();
+		storage.write_buffer(&location("core:saves"), b"file").ok().unwrap();
+		
+		assert!(is_io_error(storage.create_dir(&location("core:saves"))));
+		assert_eq!(read(&storage, "core:saves").as_deref(), Some("file"));
+	}
+	
+	#[test]
+	fn rename_replaces_existing_resource() {
+		let storage = MemoryStorage::new();
+		storage.write_buffer(&location("core:a.txt"), b"a").ok().unwrap();
+		storage.write_buffer(&location("core:b.txt"), b"b").ok().unwrap();
+		
+		storage.rename(&location("core:a.txt"), &location("core:b.txt")).ok().unwrap();
+		
+		assert_eq!(read(&storage, "core:a.txt"), None);
+		assert_eq!(read(&storage, "core:b.txt").as_deref(), Some("a"));
+	}
+	
+	#[test]
+	fn rename_moves_directory_contents() {
+		let storage = MemoryStorage::new();
+		storage.write_buffer(&location("core:saves/old/world.dat"), b"world").ok().unwrap();
+		storage.create_dir(&location("core:saves/old/region")).ok().unwrap();
+		
+		storage.rename(&location("core:saves/old"), &location("core:saves/new")).ok().unwrap();
+		
+		assert_eq!(read(&storage, "core:saves/new/world.dat").as_deref(), Some("world"));
+		assert_eq!(read(&storage, "core:saves/old/world.dat"), None);
+		
+		let state = storage.lock().ok().unwrap();
+		assert!(state.is_dir("core/saves/new/region"));
+		assert!(!state.is_dir("core/saves/old"));
+	}
+	
+	#[test]
+	fn rename_errors() {
+		let storage = MemoryStorage::new();
+		storage.write_buffer(&location("core:a.txt"), b"a").ok().unwrap();
+		storage.write_buffer(&location("core:saves/world.dat"), b"world").ok().unwrap();
+		storage.write_buffer(&location("core:other/world.dat"), b"other").ok().unwrap();
+		
+		// A resource cannot replace a directory, and a directory cannot replace anything.
+		assert!(is_io_error(storage.rename(&location("core:a.txt"), &location("core:saves"))));
+		assert!(is_io_error(storage.rename(&location("core:saves"), &location("core:other"))));
+		assert!(is_io_error(storage.rename(&location("core:saves"), &location("core:saves/inner"))));
+		
+		// Nothing was changed by the failed renames.
+		assert_eq!(read(&storage, "core:a.txt").as_deref(), Some("a"));
+		assert_eq!(read(&storage, "core:saves/world.dat").as_deref(), Some("world"));
+		assert_eq!(read(&storage, "core:other/world.dat").as_deref(), Some("other"));
+	}
+	
+	#[test]
+	fn missing_paths() {
+		let storage = MemoryStorage::new();
+		
+		match storage.res_as_stream(&location("core:missing.txt")) {
+			Err(ResourceError::NotFound) => (),
+			_ => panic!("Expected the resource to be missing"),
+		}
+		
+		assert!(is_not_found(storage.delete(&location("core:missing.txt"))));
+		assert!(is_not_found(storage.rename(&location("core:missing.txt"), &location("core:other.txt"))));
+		assert!(!storage.res_exists(&location("core:other.txt")));
+	}
+}
@@ -2,6 +2,7 @@ use super::ResourceLocation;
 use super::ResourceError;
 use std::io::Read;
 use std::ffi::CString;
+use std::time::SystemTime;
 
 pub mod filesystem;
 pub use filesystem::*;
@@ -12,13 +13,31 @@ pub use include::*;
 pub mod archive;
 pub use archive::*;
 
-pub trait ResourceProvider {
+pub mod memory;
+pub use memory::*;
+
+/// A source of resources.
+///
+/// Providers are shared with worker threads, so they must be `Send` and `Sync`.
+pub trait ResourceProvider: Send + Sync {
 	fn get_internal_name(&self) -> &str;
 	
 	fn res_list(&self) -> Result<Box<dyn Iterator<Item = String>>, ResourceError>;
 	
 	fn res_as_stream(&self, location: &ResourceLocation) -> Result<Box<dyn Read>, ResourceError>;
 	
+	/// Returns whether this provider has the given resource, without reading it.
+	fn res_exists(&self, location: &ResourceLocation) -> bool {
+		self.res_as_stream(location).is_ok()
+	}
+	
+	/// Lists the resources that can change while the program is running, with the time each was last modified.
+	///
+	/// Providers whose resources never change (like embedded files) list nothing.
+	fn res_list_modified(&self) -> Result<Box<dyn Iterator<Item = (String, SystemTime)>>, ResourceError> {
+		Ok(Box::new(std::iter::empty()))
+	}
+	
 	fn res_as_buffer(&self, location: &ResourceLocation) -> Result<Vec<u8>, ResourceError> {
 		let mut read = self.res_as_stream(location)?;
 		let mut buf = Vec::<u8>::new();
@@ -0,0 +1,37 @@
+use super::{ResourceProvider, ResourceLocation, ResourceError};
+use std::io::Write;
+
+/// A provider that can also be written to, like the user data: Saves, settings and exported files.
+///
+/// Writes are atomic: A resource opened with `write_stream` keeps its old content (or does not exist)
+/// until the writer is committed, and a writer that is dropped without being committed changes nothing.
+pub trait ResourceStorage: ResourceProvider {
+	/// Opens the resource for writing, replacing it once the writer is committed.
+	///
+	/// Missing parent directories are created.
+	fn write_stream(&self, location: &ResourceLocation) -> Result<Box<dyn StorageWriter>, ResourceError>;
+	
+	/// Creates a directory, and all missing parent directories.
+	fn create_dir(&self, location: &ResourceLocation) -> Result<(), ResourceError>;
+	
+	/// Deletes a resource, or an empty directory.
+	fn delete(&self, location: &ResourceLocation) -> Result<(), ResourceError>;
+	
+	/// Moves a resource or directory, replacing the resource at `to`.
+	fn rename(&self, from: &ResourceLocation, to: &ResourceLocation) -> Result<(), ResourceError>;
+	
+	fn write_buffer(&self, location: &ResourceLocation, buffer: &[u8]) -> Result<(), ResourceError> {
+		let mut writer = self.write_stream(location)?;
+		
+		writer.write_all(buffer)
+			.map_err(|ioe| ResourceError::Io(ioe))?;
+		
+		writer.commit()
+	}
+}
+
+/// The writer of a `ResourceStorage`.
+pub trait StorageWriter: Write {
+	/// Replaces the resource with everything written so far.
+	fn commit(self: Box<Self>) -> Result<(), ResourceError>;
+}
@@ -0,0 +1,128 @@
+use super::ResourceProvider;
+use super::ResourceLocation;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// Detects changed resources, by comparing their modification times between polls.
+///
+/// Only resources listed by `ResourceProvider::res_list_modified` are watched.
+pub struct ResourceWatcher {
+	times: HashMap<String, SystemTime>
+}
+
+impl ResourceWatcher {
+	/// Creates a watcher, taking the current state of the given provider as unchanged.
+	pub fn new(provider: &dyn ResourceProvider) -> Self {
+		Self {
+			times: Self::snapshot(provider).unwrap_or_default()
+		}
+	}
+	
+	/// Returns the locations of all resources that were modified, created or deleted since the last poll, sorted.
+	///
+	/// If the resources cannot be listed, nothing is reported as changed.
+	/// Files that are not stored at a valid location are ignored.
+	pub fn poll(&mut self, provider: &dyn ResourceProvider) -> Vec<ResourceLocation> {
+		let times = match Self::snapshot(provider) {
+			Some(times) => times,
+			None => return vec![],
+		};
+		
+		let mut changed: Vec<ResourceLocation> = times.iter()
+			.filter(|(path, time)| self.times.get(*path) != Some(time))
+			.map(|(path, _)| path)
+			.chain(self.times.keys()
+				.filter(|path| !times.contains_key(*path))
+			)
+			.filter_map(|path| ResourceLocation::from_storage_path(path))
+			.collect();
+		
+		changed.sort();
+		self.times = times;
+		changed
+	}
+	
+	fn snapshot(provider: &dyn ResourceProvider) -> Option<HashMap<String, SystemTime>> {
+		match provider.res_list_modified() {
+			Ok(iter) => Some(iter.collect()),
+			Err(_) => {
+				warn!("Failed to list modified resources of '{}'.", provider.get_internal_name());
+				None
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::FilesystemProvider;
+	use super::super::providers::filesystem::tests::TempDir;
+	use std::path::Path;
+	
+	fn write(root: &Path, path: &str, content: &str) {
+		let path = root.join(path);
+		std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+		std::fs::write(path, content).unwrap();
+	}
+	
+	/// Rewrites an existing file until its modification time changes, as file systems may store it coarsely.
+	fn modify(root: &Path, path: &str, content: &str) {
+		let path = root.join(path);
+		let before = std::fs::metadata(&path).unwrap().modified().unwrap();
+		
+		loop {
+			std::fs::write(&path, content).unwrap();
+			if std::fs::metadata(&path).unwrap().modified().unwrap() != before {
+				break;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(5));
+		}
+	}
+	
+	fn names(changed: Vec<ResourceLocation>) -> Vec<String> {
+		changed.iter().map(|location| location.to_string()).collect()
+	}
+	
+	#[test]
+	fn reports_modified_created_and_deleted() {
+		let dir = TempDir::new("watch");
+		write(&dir.0, "core/lang/en.txt", "hello");
+		write(&dir.0, "core/lists/blocks.txt", "adm");
+		write(&dir.0, "core/shaders/block.glsl", "void main() {}");
+		
+		let provider = FilesystemProvider::new(dir.0.clone());
+		let mut watcher = ResourceWatcher::new(&provider);
+		assert!(watcher.poll(&provider).is_empty());
+		
+		modify(&dir.0, "core/shaders/block.glsl", "void main() { }");
+		write(&dir.0, "core/textures/block/adm.png", "png");
+		std::fs::remove_file(dir.0.join("core/lang/en.txt")).unwrap();
+		
+		assert_eq!(names(watcher.poll(&provider)), vec![
+			"core:lang/en.txt",
+			"core:shaders/block.glsl",
+			"core:textures/block/adm.png",
+		]);
+		
+		// Every change is only reported once.
+		assert!(watcher.poll(&provider).is_empty());
+		
+		modify(&dir.0, "core/lists/blocks.txt", "adm\nadm_fence");
+		assert_eq!(names(watcher.poll(&provider)), vec!["core:lists/blocks.txt"]);
+		assert!(watcher.poll(&provider).is_empty());
+	}
+	
+	#[test]
+	fn ignores_files_outside_valid_locations() {
+		let dir = TempDir::new("watch-invalid");
+		let provider = FilesystemProvider::new(dir.0.clone());
+		let mut watcher = ResourceWatcher::new(&provider);
+		
+		write(&dir.0, "loose.txt", "no namespace");
+		write(&dir.0, "core/Bad Name.txt", "invalid characters");
+		write(&dir.0, "core/good.txt", "valid");
+		
+		assert_eq!(names(watcher.poll(&provider)), vec!["core:good.txt"]);
+	}
+}